@@ -1,4 +1,5 @@
 mod governance;
+mod lp_collateral;
 mod oracle;
 //mod storage;
 mod storage_impl;
@@ -26,10 +27,14 @@ use near_contract_standards::fungible_token::metadata::{
 };
 use near_contract_standards::fungible_token::{events::FtBurn, events::FtMint, FungibleToken};
 
+use lp_collateral::LpCollateral;
 use oracle::{ExchangeRate, Price, PriceData};
 use std::fmt::Debug;
 use views::U256;
 
+/// Collateral key: a NEP-141 token account id, or `exchange_id@pool_id` for exchange LP shares.
+pub type CollateralId = String;
+
 #[ext_contract(ext_ft_receiver)]
 pub trait FungibleTokenReceiver {
     fn ft_on_transfer(
@@ -61,6 +66,7 @@ enum StorageKey {
     Accounts,
     FungibleToken,
     Metadata,
+    LpCollaterals,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
@@ -110,7 +116,7 @@ impl Default for ExpectedRate {
 #[serde(crate = "near_sdk::serde")]
 pub struct Vault {
     owner_id: AccountId,
-    token_id: AccountId,
+    token_id: CollateralId,
     deposited: U128,
     borrowed: U128,
     last_deposit: U128,
@@ -122,7 +128,7 @@ pub struct Vault {
 pub struct Liquidation {
     owner_id: AccountId,
     maker_id: AccountId,
-    token_id: AccountId,
+    token_id: CollateralId,
     collateral_amount_before: U128,
     collateral_amount_after: U128,
     borrowed_before: U128,
@@ -136,7 +142,7 @@ pub struct Liquidation {
 }
 
 impl Vault {
-    pub fn new(owner_id: &AccountId, token_id: &AccountId) -> Vault {
+    pub fn new(owner_id: &AccountId, token_id: &CollateralId) -> Vault {
         Vault {
             owner_id: owner_id.clone(),
             token_id: token_id.clone(),
@@ -167,7 +173,7 @@ impl Default for AccountDeposit {
 }
 
 impl AccountDeposit {
-    pub fn get_vault_index(&self, collateral_token_id: AccountId) -> usize {
+    pub fn get_vault_index(&self, collateral_token_id: CollateralId) -> usize {
         let length = self.vaults.len();
         let mut i = 0;
         while i < length {
@@ -180,7 +186,7 @@ impl AccountDeposit {
         i
     }
 
-    pub fn get_vault(&self, collateral_token_id: AccountId) -> Vault {
+    pub fn get_vault(&self, collateral_token_id: CollateralId) -> Vault {
         let length = self.vaults.len();
         let i = self.get_vault_index(collateral_token_id.clone());
 
@@ -193,7 +199,7 @@ impl AccountDeposit {
     pub fn get_vault_or_default(
         &self,
         owner_id: AccountId,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
     ) -> Vault {
         let length = self.vaults.len();
         let i = self.get_vault_index(collateral_token_id.clone());
@@ -208,7 +214,7 @@ impl AccountDeposit {
     pub fn deposit_or_add_vault(
         &mut self,
         account_id: &AccountId,
-        collateral_token_id: &AccountId,
+        collateral_token_id: &CollateralId,
         collateral_amount: &Balance,
     ) {
         //find the vault for collateral token
@@ -235,7 +241,7 @@ impl AccountDeposit {
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct TokenInfo {
-    pub token_id: AccountId,
+    pub token_id: CollateralId,
     pub collateral_ratio: u64, //not percentage, but per 10000, for example collateral_ratio = 15000 means 150%
     pub total_deposit: U128,
     pub total_borrowed: U128, //NAI balance
@@ -245,7 +251,7 @@ pub struct TokenInfo {
 }
 
 impl TokenInfo {
-    pub fn new(token_id: AccountId) -> TokenInfo {
+    pub fn new(token_id: CollateralId) -> TokenInfo {
         TokenInfo {
             token_id: token_id,
             collateral_ratio: 0,
@@ -264,8 +270,34 @@ pub struct Contract {
     governance: AccountId,
     black_list: LookupMap<AccountId, BlackListStatus>,
     status: ContractStatus,
-    supported_tokens: LookupMap<AccountId, TokenInfo>,
-    token_list: Vec<AccountId>,
+    supported_tokens: LookupMap<CollateralId, TokenInfo>,
+    token_list: Vec<CollateralId>,
+    accounts: LookupMap<AccountId, AccountDeposit>,
+    total_nai_borrowed: U128,
+    total_generated_fees: U128,
+    price_data: PriceData,
+    price_feeder: AccountId,
+    base_storage_usage: StorageUsage,
+    storage_usage_per_vault: StorageUsage,
+
+    token: FungibleToken,
+    metadata: LazyOption<FungibleTokenMetadata>,
+    foundation_id: AccountId,
+    borrow_fee: u128,
+    liquidation_history: Vec<Liquidation>,
+    account_list: Vec<AccountId>,
+    lp_collaterals: LookupMap<CollateralId, LpCollateral>,
+}
+
+/// State layout before LP collaterals, only used to deserialize the old state in `migrate`.
+/// Collateral ids were `AccountId`s, which have the same Borsh layout as `CollateralId`.
+#[derive(BorshDeserialize)]
+pub struct ContractV1 {
+    governance: AccountId,
+    black_list: LookupMap<AccountId, BlackListStatus>,
+    status: ContractStatus,
+    supported_tokens: LookupMap<CollateralId, TokenInfo>,
+    token_list: Vec<CollateralId>,
     accounts: LookupMap<AccountId, AccountDeposit>,
     total_nai_borrowed: U128,
     total_generated_fees: U128,
@@ -333,6 +365,7 @@ impl Contract {
             borrow_fee: 20,
             liquidation_history: vec![],
             account_list: vec![],
+            lp_collaterals: LookupMap::new(StorageKey::LpCollaterals),
         };
 
         this.token.internal_register_account(&governance);
@@ -402,9 +435,8 @@ impl Contract {
     }
 
     /// Should only be called by this contract on migration.
-    /// This is NOOP implementation. KEEP IT if you haven't changed contract state.
-    /// If you have changed state, you need to implement migration from old state (keep the old struct with different name to deserialize it first).
-    /// After migrate goes live on MainNet, return this implementation for next updates.
+    /// Migrates the state from `ContractV1` by adding the empty LP collaterals.
+    /// After migrate goes live on MainNet, return the NOOP implementation for next updates.
     #[init(ignore_state)]
     pub fn migrate() -> Self {
         assert_eq!(
@@ -412,8 +444,28 @@ impl Contract {
             env::current_account_id(),
             "ERR_NOT_ALLOWED"
         );
-        let this: Contract = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
-        this
+        let old: ContractV1 = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
+        Self {
+            governance: old.governance,
+            black_list: old.black_list,
+            status: old.status,
+            supported_tokens: old.supported_tokens,
+            token_list: old.token_list,
+            accounts: old.accounts,
+            total_nai_borrowed: old.total_nai_borrowed,
+            total_generated_fees: old.total_generated_fees,
+            price_data: old.price_data,
+            price_feeder: old.price_feeder,
+            base_storage_usage: old.base_storage_usage,
+            storage_usage_per_vault: old.storage_usage_per_vault,
+            token: old.token,
+            metadata: old.metadata,
+            foundation_id: old.foundation_id,
+            borrow_fee: old.borrow_fee,
+            liquidation_history: old.liquidation_history,
+            account_list: old.account_list,
+            lp_collaterals: LookupMap::new(StorageKey::LpCollaterals),
+        }
     }
 
     fn internal_mint(&mut self, account_id: AccountId, amount: Balance) -> (Balance, Balance) {
@@ -453,7 +505,7 @@ impl Contract {
 
         initial_storage_usage = env::storage_usage();
         let mut tmp_acc = self.accounts.get(&tmp_account_id).unwrap();
-        let tmp_token_id = AccountId::new_unchecked("a".repeat(64).to_string());
+        let tmp_token_id = "a".repeat(64);
         let vault = Vault {
             owner_id: tmp_account_id.clone(),
             token_id: tmp_token_id.clone(),
@@ -472,7 +524,7 @@ impl Contract {
     }
 
     #[payable]
-    pub fn pay_loan(&mut self, collateral_token_id: &AccountId, pay_amount: U128) -> U128 {
+    pub fn pay_loan(&mut self, collateral_token_id: &CollateralId, pay_amount: U128) -> U128 {
         //cant pay loan if collateral ratio is under min. users need to deposit more to get ratio go above min ratio
         let account_id = &env::predecessor_account_id();
         self.assert_collateral_ratio_valid(account_id, collateral_token_id);
@@ -515,6 +567,7 @@ impl Contract {
         liquidation_price_fee: Option<u64>,
     ) {
         self.assert_governance();
+        let token_id: CollateralId = token_id.into();
         require!(
             !self.is_token_supported(&token_id),
             "token already supported"
@@ -552,7 +605,7 @@ impl Contract {
         }
     }
 
-    pub fn update_cr(&mut self, collateral_token_id: AccountId, cr: u64) {
+    pub fn update_cr(&mut self, collateral_token_id: CollateralId, cr: u64) {
         self.assert_governance();
         let mut token_info = self.get_token_info(collateral_token_id.clone());
         token_info.collateral_ratio = cr;
@@ -561,7 +614,7 @@ impl Contract {
     }
 
     #[payable]
-    pub fn borrow(&mut self, collateral_token_id: &AccountId, borrow_amount: U128) -> (U128, U128) {
+    pub fn borrow(&mut self, collateral_token_id: &CollateralId, borrow_amount: U128) -> (U128, U128) {
         // Select target account.
         let borrow_amount = borrow_amount.0;
         let account = env::predecessor_account_id();
@@ -622,7 +675,7 @@ impl Contract {
     }
 
     #[payable]
-    pub fn withdraw_collateral(&mut self, collateral_token_id: AccountId, withdraw_amount: U128) {
+    pub fn withdraw_collateral(&mut self, collateral_token_id: CollateralId, withdraw_amount: U128) {
         assert_one_yocto();
         require!(withdraw_amount.0 > 0, "withdraw_amount > 0");
         let account_id = env::predecessor_account_id();
@@ -654,7 +707,7 @@ impl Contract {
     pub fn liquidate(
         &mut self,
         account_id: AccountId,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
         nai_amount: U128,
     ) {
         let prev_usage = env::storage_usage();
@@ -688,7 +741,7 @@ impl Contract {
         );
 
         let mut token_info = self.get_token_info(collateral_token_id.clone());
-        let price = self.price_data.price(&collateral_token_id);
+        let price = self.internal_collateral_price(&collateral_token_id);
         let multiplier: u128 = price.multiplier.0
             * (BORROW_FEE_DIVISOR - (token_info.liquidation_price_fee as u128))
             / BORROW_FEE_DIVISOR;
@@ -846,7 +899,7 @@ impl Contract {
         }
     }
 
-    fn abort_if_unsupported_token(&self, token_id: AccountId) {
+    fn abort_if_unsupported_token(&self, token_id: CollateralId) {
         if !self.is_token_supported(&token_id) {
             env::panic_str("The token is not supported")
         }
@@ -961,7 +1014,7 @@ impl Contract {
 
     fn deposit_to_vault(
         &mut self,
-        collateral_token_id: &AccountId,
+        collateral_token_id: &CollateralId,
         collateral_amount: &Balance,
         account_id: &AccountId,
     ) {
@@ -977,7 +1030,7 @@ impl Contract {
 
     fn internal_deposit_to_vault(
         &mut self,
-        collateral_token_id: &AccountId,
+        collateral_token_id: &CollateralId,
         collateral_amount: &Balance,
         account_id: &AccountId,
     ) {
@@ -1004,7 +1057,7 @@ impl Contract {
     pub fn assert_collateral_ratio_valid(
         &self,
         account_id: &AccountId,
-        collateral_token_id: &AccountId,
+        collateral_token_id: &CollateralId,
     ) {
         let (new_ratio, min_ratio) = self.compute_new_ratio_after_borrow(
             account_id.clone(),
@@ -1021,7 +1074,7 @@ impl Contract {
 
     pub fn finish_borrow(
         &mut self,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
         account_id: AccountId,
         borrowed: Balance,
         actual_received: Balance,
@@ -1088,4 +1141,100 @@ pub fn upgrade() {
 }
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
-mod tests {}
+mod tests {
+    use super::*;
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::test_utils::{accounts, get_created_receipts, VMContextBuilder};
+    use near_sdk::{testing_env, PromiseResult};
+
+    const ONE_NEAR: Balance = 10u128.pow(24);
+    const NOW_SEC: u64 = 1_000;
+
+    fn exchange_id() -> AccountId {
+        "exchange.near".parse().unwrap()
+    }
+
+    fn lp_token_id() -> CollateralId {
+        LpCollateral::get_collateral_id(&exchange_id(), 1)
+    }
+
+    //LP shares of pool 1 on `exchange_id` backed by accounts(3) and accounts(4) stablecoins,
+    //priced 0.99 and 1 USD, with a virtual price of 1.02 fetched at NOW_SEC
+    fn setup_contract() -> (VMContextBuilder, Contract) {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(ONE_NEAR)
+            .block_timestamp(NOW_SEC * 10u64.pow(9))
+            .build());
+        let mut contract = Contract::new(accounts(0), accounts(1));
+        contract.add_new_lp_collateral(exchange_id(), 1, vec![accounts(3), accounts(4)], 15000, None);
+        let price_data: PriceData = near_sdk::serde_json::from_value(near_sdk::serde_json::json!({
+            "timestamp": (NOW_SEC * 10u64.pow(9)).to_string(),
+            "recency_duration_sec": 90,
+            "prices": [
+                {"asset_id": accounts(3), "price": {"multiplier": "99000000", "decimals": 8}},
+                {"asset_id": accounts(4), "price": {"multiplier": "100000000", "decimals": 8}},
+            ],
+        }))
+        .unwrap();
+        contract.push_price_data(price_data);
+
+        let contract_id = env::current_account_id();
+        testing_env!(
+            context.predecessor_account_id(contract_id).attached_deposit(0).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(near_sdk::serde_json::to_vec(&U128(102_000_000)).unwrap())],
+        );
+        contract.callback_update_lp_virtual_price(lp_token_id());
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(ONE_NEAR).build());
+        contract.storage_deposit(None, None);
+        (context, contract)
+    }
+
+    fn deposit_lp_shares(context: &mut VMContextBuilder, contract: &mut Contract, amount: Balance) {
+        testing_env!(context.predecessor_account_id(exchange_id()).attached_deposit(0).build());
+        contract.mft_on_transfer(":1".to_string(), accounts(2), U128(amount), "".to_string());
+    }
+
+    #[test]
+    fn mft_on_transfer_deposits_lp_shares() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_lp_shares(&mut context, &mut contract, 5 * 10u128.pow(18));
+        let vault = contract.get_account_info(accounts(2)).get_vault(lp_token_id());
+        assert_eq!(vault.deposited.0, 5 * 10u128.pow(18));
+        assert_eq!(contract.get_token_info(lp_token_id()).total_deposit.0, 5 * 10u128.pow(18));
+    }
+
+    #[test]
+    fn lp_price_uses_the_lowest_underlying_price() {
+        let (_, contract) = setup_contract();
+        let price = contract.internal_collateral_price(&lp_token_id());
+        assert_eq!(price.multiplier.0, 100_980_000);
+        assert_eq!(price.decimals, 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "LP virtual price is outdated")]
+    fn lp_price_rejects_an_outdated_virtual_price() {
+        let (mut context, contract) = setup_contract();
+        testing_env!(context.block_timestamp((NOW_SEC + 90) * 10u64.pow(9)).build());
+        contract.internal_collateral_price(&lp_token_id());
+    }
+
+    #[test]
+    fn lp_shares_are_withdrawn_through_the_exchange() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_lp_shares(&mut context, &mut contract, 5 * 10u128.pow(18));
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.withdraw_collateral(lp_token_id(), U128(2 * 10u128.pow(18)));
+
+        let vault = contract.get_account_info(accounts(2)).get_vault(lp_token_id());
+        assert_eq!(vault.deposited.0, 3 * 10u128.pow(18));
+        let receipts = get_created_receipts();
+        assert!(receipts.iter().any(|receipt| receipt.receiver_id == exchange_id()));
+    }
+}
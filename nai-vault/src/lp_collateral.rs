@@ -0,0 +1,215 @@
+use near_sdk::{ext_contract, Gas, PromiseOrValue, PromiseResult};
+
+use crate::utils::ext_self;
+use crate::*;
+
+/// Precision of the share price returned by the exchange's `get_pool_share_price`.
+const VIRTUAL_PRICE_PRECISION: u128 = 100_000_000;
+/// Stable pool shares of nstable-exchange are minted with 18 decimals.
+const LP_SHARE_DECIMALS: u8 = 18;
+const GAS_FOR_GET_SHARE_PRICE: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_UPDATE_LP_VIRTUAL_PRICE: Gas = Gas(10_000_000_000_000);
+
+#[ext_contract(ext_exchange)]
+pub trait NStableExchange {
+    fn get_pool_share_price(&self, pool_id: u64) -> U128;
+}
+
+/// An nstable-exchange stable pool whose LP shares are accepted as collateral.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LpCollateral {
+    pub exchange_id: AccountId,
+    pub pool_id: u64,
+    pub underlying_token_ids: Vec<AccountId>,
+    pub virtual_price: U128, //precision 1e8
+    pub virtual_price_timestamp_sec: u64,
+}
+
+impl LpCollateral {
+    pub fn get_collateral_id(exchange_id: &AccountId, pool_id: u64) -> CollateralId {
+        format!("{}@{}", exchange_id, pool_id)
+    }
+
+    /// Token id of the shares in the exchange's mft interface.
+    pub fn get_mft_token_id(&self) -> String {
+        format!(":{}", self.pool_id)
+    }
+}
+
+fn parse_mft_pool_id(token_id: &String) -> u64 {
+    if !token_id.starts_with(":") {
+        env::panic_str("only pool shares are accepted");
+    }
+    str::parse::<u64>(&token_id[1..]).expect("illegal pool id")
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Registers the LP shares of `pool_id` on `exchange_id` as collateral.
+    /// The vault account must be registered for the pool shares with the exchange's `mft_register`
+    /// before users can deposit.
+    #[payable]
+    pub fn add_new_lp_collateral(
+        &mut self,
+        exchange_id: AccountId,
+        pool_id: u64,
+        underlying_token_ids: Vec<AccountId>,
+        collateral_ratio: u64,
+        liquidation_price_fee: Option<u64>,
+    ) {
+        self.assert_governance();
+        require!(!underlying_token_ids.is_empty(), "no underlying tokens");
+        let token_id = LpCollateral::get_collateral_id(&exchange_id, pool_id);
+        require!(
+            !self.is_token_supported(&token_id),
+            "token already supported"
+        );
+        let liquidation_price_fee = liquidation_price_fee.unwrap_or(10);
+        let prev_storage = env::storage_usage();
+        self.supported_tokens.insert(
+            &token_id,
+            &TokenInfo {
+                token_id: token_id.clone(),
+                collateral_ratio: collateral_ratio,
+                decimals: LP_SHARE_DECIMALS,
+                total_deposit: U128(0),
+                total_borrowed: U128(0),
+                generated_fees: U128(0),
+                liquidation_price_fee: liquidation_price_fee,
+            },
+        );
+        self.lp_collaterals.insert(
+            &token_id,
+            &LpCollateral {
+                exchange_id: exchange_id,
+                pool_id: pool_id,
+                underlying_token_ids: underlying_token_ids,
+                virtual_price: U128(0),
+                virtual_price_timestamp_sec: 0,
+            },
+        );
+        self.token_list.push(token_id.clone());
+        let storage_cost = self.storage_cost(prev_storage);
+
+        let refund = env::attached_deposit().checked_sub(storage_cost).expect(
+            format!(
+                "ERR_STORAGE_DEPOSIT need {}, attatched {}",
+                storage_cost,
+                env::attached_deposit()
+            )
+            .as_str(),
+        );
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+
+    /// Fetches the current virtual price of the pool from the exchange. Anyone can call it.
+    pub fn update_lp_virtual_price(&mut self, collateral_token_id: CollateralId) -> Promise {
+        let lp = self
+            .lp_collaterals
+            .get(&collateral_token_id)
+            .expect("not an LP collateral");
+        ext_exchange::get_pool_share_price(
+            lp.pool_id,
+            lp.exchange_id.clone(),
+            0,
+            GAS_FOR_GET_SHARE_PRICE,
+        )
+        .then(ext_self::callback_update_lp_virtual_price(
+            collateral_token_id,
+            env::current_account_id(),
+            0,
+            GAS_FOR_UPDATE_LP_VIRTUAL_PRICE,
+        ))
+    }
+
+    #[private]
+    pub fn callback_update_lp_virtual_price(&mut self, collateral_token_id: CollateralId) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            "CALLBACK_UPDATE_LP_VIRTUAL_PRICE_INVALID"
+        );
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                let virtual_price = near_sdk::serde_json::from_slice::<U128>(&value)
+                    .expect("invalid share price");
+                let mut lp = self.lp_collaterals.get(&collateral_token_id).unwrap();
+                lp.virtual_price = virtual_price;
+                lp.virtual_price_timestamp_sec = env::block_timestamp_ms() / 1000;
+                self.lp_collaterals.insert(&collateral_token_id, &lp);
+            }
+            PromiseResult::Failed => {
+                log!("failed to fetch virtual price of {}", collateral_token_id);
+            }
+        };
+    }
+
+    /// Callback on receiving exchange LP shares via `mft_transfer_call`.
+    /// `msg` format is "" for a deposit to the sender's vault.
+    pub fn mft_on_transfer(
+        &mut self,
+        token_id: String,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        self.abort_if_pause();
+        self.abort_if_blacklisted(sender_id.clone());
+        let pool_id = parse_mft_pool_id(&token_id);
+        let collateral_token_id =
+            LpCollateral::get_collateral_id(&env::predecessor_account_id(), pool_id);
+
+        self.abort_if_unsupported_token(collateral_token_id.clone());
+        if msg.is_empty() {
+            self.deposit_to_vault(&collateral_token_id, &amount.0, &sender_id);
+            PromiseOrValue::Value(U128(0))
+        } else {
+            env::panic_str("unsupported operation");
+        }
+    }
+
+    pub fn get_lp_collateral(&self, collateral_token_id: CollateralId) -> Option<LpCollateral> {
+        self.lp_collaterals.get(&collateral_token_id)
+    }
+}
+
+impl Contract {
+    /// Collateral price in USD: oracle price for tokens, computed price for LP shares.
+    pub fn internal_collateral_price(&self, collateral_token_id: &CollateralId) -> Price {
+        match self.lp_collaterals.get(collateral_token_id) {
+            Some(lp) => self.internal_lp_price(&lp),
+            None => self
+                .price_data
+                .price(&AccountId::new_unchecked(collateral_token_id.clone())),
+        }
+    }
+
+    /// Virtual price times the lowest underlying stablecoin price, so a depeg of any
+    /// underlying token is reflected in full. The virtual price must be fresher than the
+    /// oracle recency window, a drained pool would otherwise keep its last price.
+    fn internal_lp_price(&self, lp: &LpCollateral) -> Price {
+        require!(lp.virtual_price.0 > 0, "LP virtual price not updated");
+        require!(
+            env::block_timestamp()
+                < lp.virtual_price_timestamp_sec * 10u64.pow(9) + self.price_data.recency_duration(),
+            "LP virtual price is outdated"
+        );
+        let min_price = lp
+            .underlying_token_ids
+            .iter()
+            .map(|token_id| self.price_data.price(token_id))
+            .min_by_key(|price| price.multiplier.0)
+            .unwrap();
+        let multiplier = U256::from(lp.virtual_price.0) * U256::from(min_price.multiplier.0)
+            / U256::from(VIRTUAL_PRICE_PRECISION);
+        Price {
+            multiplier: U128(multiplier.as_u128()),
+            decimals: min_price.decimals,
+        }
+    }
+}
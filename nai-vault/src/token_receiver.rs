@@ -30,7 +30,7 @@ impl FungibleTokenReceiver for Contract {
     ) -> PromiseOrValue<U128> {
         self.abort_if_pause();
         self.abort_if_blacklisted(sender_id.clone());
-        let token_in = env::predecessor_account_id();
+        let token_in: CollateralId = env::predecessor_account_id().into();
 
         self.abort_if_unsupported_token(token_in.clone());
         if msg.is_empty() {
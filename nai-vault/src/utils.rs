@@ -7,7 +7,8 @@ pub const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas(20_000_000_000_000);
 
 #[ext_contract(ext_self)]
 pub trait NaiVault {
-    fn callback_post_withdraw(&mut self, token_id: AccountId, receiver_id: AccountId, amount: U128);
+    fn callback_post_withdraw(&mut self, token_id: CollateralId, receiver_id: AccountId, amount: U128);
+    fn callback_update_lp_virtual_price(&mut self, collateral_token_id: CollateralId);
 }
 
 #[ext_contract(ext_ft_core)]
@@ -15,12 +16,17 @@ pub trait FungibleTokenCore {
     fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
 }
 
+#[ext_contract(ext_mft)]
+pub trait MultiFungibleTokenCore {
+    fn mft_transfer(&mut self, token_id: String, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
 #[near_bindgen]
 impl Contract {
     #[private]
     pub fn callback_post_withdraw(
         &mut self,
-        token_id: AccountId,
+        token_id: CollateralId,
         receiver_id: AccountId,
         amount: U128,
     ) {
@@ -74,19 +80,30 @@ impl Contract {
 impl Contract {
     pub(crate) fn internal_send_tokens(
         &self,
-        token_id: &AccountId,
+        token_id: &CollateralId,
         receiver_id: &AccountId,
         amount: Balance,
     ) -> Promise {
-        ext_ft_core::ft_transfer(
-            receiver_id.clone(),
-            U128(amount),
-            None,
-            token_id.clone(),
-            1,
-            GAS_FOR_FT_TRANSFER,
-        )
-        .then(ext_self::callback_post_withdraw(
+        let transfer = match self.lp_collaterals.get(token_id) {
+            Some(lp) => ext_mft::mft_transfer(
+                lp.get_mft_token_id(),
+                receiver_id.clone(),
+                U128(amount),
+                None,
+                lp.exchange_id.clone(),
+                1,
+                GAS_FOR_FT_TRANSFER,
+            ),
+            None => ext_ft_core::ft_transfer(
+                receiver_id.clone(),
+                U128(amount),
+                None,
+                AccountId::new_unchecked(token_id.clone()),
+                1,
+                GAS_FOR_FT_TRANSFER,
+            ),
+        };
+        transfer.then(ext_self::callback_post_withdraw(
             token_id.clone(),
             receiver_id.clone(),
             U128(amount),
@@ -9,7 +9,7 @@ uint::construct_uint!(
 #[serde(crate = "near_sdk::serde")]
 pub struct BorrowInfo {
     owner_id: AccountId,
-    token_id: AccountId,
+    token_id: CollateralId,
     decimals: u8,
     deposited: U128,
     borrowed: U128,
@@ -29,11 +29,11 @@ pub struct BorrowInfo {
 
 #[near_bindgen]
 impl Contract {
-    pub fn is_token_supported(&self, token_id: &AccountId) -> bool {
+    pub fn is_token_supported(&self, token_id: &CollateralId) -> bool {
         self.supported_tokens.contains_key(token_id)
     }
 
-    pub fn get_token_info(&self, token_id: AccountId) -> TokenInfo {
+    pub fn get_token_info(&self, token_id: CollateralId) -> TokenInfo {
         if !self.is_token_supported(&token_id) {
             return TokenInfo::new(token_id.clone());
         }
@@ -57,7 +57,7 @@ impl Contract {
 
     pub fn compute_borrowable_amount(
         &self,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
         collateral_amount: U128,
     ) -> U128 {
         let borrowable = self.internal_compute_max_borrowable_amount(
@@ -80,7 +80,7 @@ impl Contract {
                 continue;
             }
             let token_info = self.get_token_info(vault.token_id.clone());
-            let price = self.price_data.price(&vault.token_id);
+            let price = self.internal_collateral_price(&vault.token_id);
             if price.multiplier.0 == 0 {
                 continue;
             }
@@ -93,7 +93,7 @@ impl Contract {
     pub fn get_account_vault(
         &self,
         account_id: AccountId,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
     ) -> Vault {
         let account_deposit = self.get_account_info(account_id.clone());
         account_deposit.get_vault(collateral_token_id.clone())
@@ -119,13 +119,13 @@ impl Contract {
         self.token_list.len()
     }
 
-    pub fn get_token_list(&self) -> &[AccountId] {
+    pub fn get_token_list(&self) -> &[CollateralId] {
         &self.token_list
     }
 
     pub fn compute_max_borrowable(
         &self,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
         collateral_amount: U128,
     ) -> U128 {
         U128(self.internal_compute_max_borrowable_amount(
@@ -142,7 +142,7 @@ impl Contract {
                 continue;
             }
             let token_info = self.get_token_info(vault.token_id.clone());
-            let price = self.price_data.price(&vault.token_id);
+            let price = self.internal_collateral_price(&vault.token_id);
             if price.multiplier.0 == 0 {
                 continue;
             }
@@ -192,14 +192,14 @@ impl Contract {
     pub fn get_current_borrow_info_for_collateral(
         &self,
         account_id: AccountId,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
         collateral_amount: Option<U128>,
         borrow: Option<U128>,
         pay_amount: Option<U128>
     ) -> BorrowInfo {
         let deposit_account = self.get_account_info(account_id.clone());
         let token_info = self.get_token_info(collateral_token_id.clone());
-        let price = self.price_data.price(&collateral_token_id);
+        let price = self.internal_collateral_price(&collateral_token_id);
         let vault = deposit_account.get_vault_or_default(account_id.clone(), collateral_token_id.clone());
         let collateral_amount = collateral_amount.unwrap_or(U128(0));
         let borrow = borrow.unwrap_or(U128(0));
@@ -249,7 +249,7 @@ impl Contract {
 
     pub fn compute_collateral_ratio(
         &self,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
         collateral_amount: U128,
         borrowed: U128,
     ) -> u64 {
@@ -263,7 +263,7 @@ impl Contract {
     pub fn compute_max_borrowable_for_account(
         &self,
         account_id: AccountId,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
         collateral_amount: U128,
     ) -> U128 {
         let account_deposit = self.get_account_info(account_id.clone());
@@ -281,7 +281,7 @@ impl Contract {
     pub fn compute_max_withdrawal(
         &self,
         account_id: AccountId,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
     ) -> U128 {
         let account_deposit = self.get_account_info(account_id.clone());
         let vault =
@@ -291,7 +291,7 @@ impl Contract {
             return vault.deposited;
         }
 
-        let price = self.price_data.price(&collateral_token_id);
+        let price = self.internal_collateral_price(&collateral_token_id);
         let token_info = self.get_token_info(collateral_token_id.clone());
         let min_collateral_ratio = token_info.collateral_ratio;
 
@@ -311,12 +311,12 @@ impl Contract {
     pub fn compute_liquidation_price(
         &self,
         account_id: AccountId,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
         collateral_amount: Option<U128>,
         borrow_amount: Option<U128>,
         pay_amount: Option<U128>
     ) -> Price {
-        let price = self.price_data.price(&collateral_token_id);
+        let price = self.internal_collateral_price(&collateral_token_id);
         let token_info = self.get_token_info(collateral_token_id.clone());
         let collateral_amount = collateral_amount.unwrap_or(U128(0));
         let borrow_amount = borrow_amount.unwrap_or(U128(0));
@@ -349,7 +349,7 @@ impl Contract {
     pub fn compute_new_ratio_after_borrow(
         &self,
         account_id: AccountId,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
         collateral_amount: U128,
         borrow_amount: U128,
     ) -> (u64, u64) {
@@ -363,7 +363,7 @@ impl Contract {
             return (100000000, token_info.collateral_ratio);
         }
 
-        let price = self.price_data.price(&vault.token_id);
+        let price = self.internal_collateral_price(&vault.token_id);
         let collateral_value = self.compute_collateral_value(&new_deposit, &price);
         let new_collateral_ratio = collateral_value
             * U256::from(10u128.pow(18 as u32))
@@ -434,12 +434,12 @@ impl Contract {
 impl Contract {
     pub fn internal_compute_collateral_ratio(
         &self,
-        collateral_token_id: &AccountId,
+        collateral_token_id: &CollateralId,
         collateral_amount: Balance,
         borrowed: Balance,
     ) -> u64 {
         let token_info = self.get_token_info(collateral_token_id.clone());
-        let price = self.price_data.price(&collateral_token_id);
+        let price = self.internal_collateral_price(&collateral_token_id);
         let collateral_value = self.compute_collateral_value(&collateral_amount, &price);
         let current_collateral_ratio =
             self.compute_cr(&collateral_value, &borrowed, token_info.decimals.clone());
@@ -448,13 +448,13 @@ impl Contract {
 
     pub fn internal_compute_max_borrowable_amount(
         &self,
-        collateral_token_id: AccountId,
+        collateral_token_id: CollateralId,
         collateral_amount: Balance,
     ) -> Balance {
         if !self.is_token_supported(&collateral_token_id) {
             return 0;
         }
-        let price_data = self.price_data.price(&collateral_token_id);
+        let price_data = self.internal_collateral_price(&collateral_token_id);
 
         let price = U256::from(price_data.multiplier.0);
         let decimals = price_data.decimals;
@@ -11,19 +11,19 @@ pub struct AccountDeposit {
     pub owner_id: AccountId,
     pub lend_token_id: AssetId,
     pub collateral_token_id: AssetId,
-    pub deposits: UnorderedMap<AssetId, Balance>,
+    pub deposits: UnorderedMap<AssetId, Balance>, //lend token deposit includes the lending interest earned
     pub borrow_amount: Balance,
 
-    //lending interest when account is a lender
-    pub lending_interest_profit_debt: Balance, //interest debt when in a lending position, this is similar to rewardDebt in sushi farming
-    pub unpaid_lending_interest_profit: Balance,
+    //lending interest when account is a lender, credited to the lend token deposit
     pub total_lending_interest_profit: Balance,
-    pub last_lending_interest_reward_update_timestamp_sec: u64,
+    pub supply_index: Balance, //pool supply index at the last update
 
     //borrowing interest when accc is a borrower
     pub unpaid_borrowing_interest: Balance, //interest unpaid in a borrowing positions
     pub total_borrowing_interest: Balance,  //interest unpaid in a borrowing positions
-    pub last_borrowing_interest_update_timestamp_sec: u64,
+    pub borrow_index: Balance, //pool borrow index at the last update
+
+    pub last_index_update_timestamp_sec: u64,
 }
 
 impl AccountDeposit {
@@ -43,13 +43,12 @@ impl AccountDeposit {
                 account_id: owner_id.clone(),
             }),
             borrow_amount: 0,
-            lending_interest_profit_debt: 0,
-            unpaid_lending_interest_profit: 0,
             total_lending_interest_profit: 0,
-            last_lending_interest_reward_update_timestamp_sec: 0,
+            supply_index: 0,
             unpaid_borrowing_interest: 0,
             total_borrowing_interest: 0,
-            last_borrowing_interest_update_timestamp_sec: 0,
+            borrow_index: 0,
+            last_index_update_timestamp_sec: 0,
         };
         account_deposit
     }
@@ -72,46 +71,25 @@ impl AccountDeposit {
         self.deposits.insert(&self.lend_token_id, &current_deposit);
     }
 
-    pub fn update_borrowing_interest(&mut self, interest_rate: u64) -> Balance {
-        let mut interest = 0u128;
-        if self.borrow_amount > 0 {
-            interest = self.compute_unrecorded_interest(interest_rate);
-            self.total_borrowing_interest += interest;
-            self.unpaid_borrowing_interest += interest;
-        }
-        self.last_borrowing_interest_update_timestamp_sec = get_next_interest_recal_time_sec();
-        interest
-    }
-
-    pub fn update_account(&mut self, interest_rate: u64, acc_interest_per_share: &Balance) {
+    /// Brings the lend token deposit and the debt up to the current pool indexes.
+    pub fn update_account(&mut self, borrow_index: &Balance, supply_index: &Balance) {
         log!("updating account {}", self.owner_id);
-        self.update_lending_profit(interest_rate, acc_interest_per_share);
-        self.update_borrowing_interest(interest_rate);
-        log!("updating account {} done", self.owner_id);
-    }
+        let lending_interest = self.compute_unrecorded_lending_interest(supply_index);
+        if lending_interest > 0 {
+            let current_deposit = self.get_token_deposit(&self.lend_token_id);
+            self.deposits
+                .insert(&self.lend_token_id, &(current_deposit + lending_interest));
+            self.total_lending_interest_profit += lending_interest;
+        }
 
-    fn update_lending_profit(&mut self, _interest_rate: u64, acc_interest_per_share: &Balance) {
-        let current_deposit = self.get_token_deposit(&self.lend_token_id);
-        self.last_lending_interest_reward_update_timestamp_sec = env::block_timestamp_ms() / 1000;
-        let total_interest_reward = (U256::from(current_deposit)
-            * U256::from(acc_interest_per_share.clone())
-            / U256::from(ACC_INTEREST_PER_SHARE_MULTIPLIER))
-        .as_u128();
-        self.unpaid_lending_interest_profit +=
-            total_interest_reward - self.lending_interest_profit_debt;
-        self.total_lending_interest_profit +=
-            total_interest_reward - self.lending_interest_profit_debt;
-        self.lending_interest_profit_debt = total_interest_reward;
-    }
+        let borrowing_interest = self.compute_unrecorded_interest(borrow_index);
+        self.unpaid_borrowing_interest += borrowing_interest;
+        self.total_borrowing_interest += borrowing_interest;
 
-    pub fn update_lending_interest_profit_debt(&mut self, acc_interest_per_share: &Balance) {
-        log!("update_lending_interest_profit_debt account {}", self.owner_id);
-        let current_deposit = self.get_token_deposit(&self.lend_token_id);
-        self.lending_interest_profit_debt = (U256::from(current_deposit)
-            * U256::from(acc_interest_per_share.clone())
-            / U256::from(ACC_INTEREST_PER_SHARE_MULTIPLIER))
-        .as_u128();
-        log!("update_lending_interest_profit_debt account {} done", self.owner_id);
+        self.supply_index = supply_index.clone();
+        self.borrow_index = borrow_index.clone();
+        self.last_index_update_timestamp_sec = env::block_timestamp_ms() / 1000;
+        log!("updating account {} done", self.owner_id);
     }
 
     pub fn internal_borrow(
@@ -121,7 +99,7 @@ impl AccountDeposit {
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
-        interest_rate: u64,
+        borrow_index: &Balance,
         min_cr: u64,
     ) -> Balance {
         let deposit_amount = self.get_token_deposit(&self.lend_token_id);
@@ -145,7 +123,7 @@ impl AccountDeposit {
             collateral_token_info,
             collateral_token_price,
             None,
-            interest_rate,
+            borrow_index,
             min_cr,
         );
         log!("max_borrowable actual {}", max_borrowable);
@@ -160,7 +138,7 @@ impl AccountDeposit {
             lend_token_price,
             collateral_token_info,
             collateral_token_price,
-            interest_rate,
+            borrow_index,
             min_cr,
         );
         borrow_amount
@@ -173,7 +151,7 @@ impl AccountDeposit {
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
-        interest_rate: u64,
+        borrow_index: &Balance,
         min_cr: u64,
     ) {
         if self.borrow_amount > 0 {
@@ -181,7 +159,7 @@ impl AccountDeposit {
                 self.get_token_deposit(&self.collateral_token_id),
                 collateral_token_info.decimals,
                 collateral_token_price,
-                self.borrow_amount + self.get_interest_owed(interest_rate),
+                self.borrow_amount + self.get_interest_owed(borrow_index),
                 lend_token_price,
                 lend_token_info.decimals,
             );
@@ -198,7 +176,7 @@ impl AccountDeposit {
         collateral_amount: Option<Balance>,
         borrow: Option<Balance>,     //borrow more
         pay_amount: Option<Balance>, //pay back
-        interest_rate: u64,
+        borrow_index: &Balance,
     ) -> u64 {
         let collateral_amount = collateral_amount.unwrap_or(0);
         let borrow = borrow.unwrap_or(0);
@@ -207,7 +185,7 @@ impl AccountDeposit {
             self.get_token_deposit(&self.collateral_token_id) + collateral_amount.clone(),
             collateral_token_info.decimals,
             collateral_token_price,
-            self.borrow_amount + self.get_interest_owed(interest_rate) + borrow.clone()
+            self.borrow_amount + self.get_interest_owed(borrow_index) + borrow.clone()
                 - pay_amount.clone(),
             lend_token_price,
             lend_token_info.decimals,
@@ -215,7 +193,7 @@ impl AccountDeposit {
         cr
     }
 
-    //return the amount withdrawn from the lend deposit token or collateral token, excluding interest if have
+    //return the amount withdrawn from the lend deposit token or collateral token
     pub fn internal_withdraw_from_account(
         &mut self,
         token_id: &AccountId,
@@ -224,33 +202,21 @@ impl AccountDeposit {
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
-        interest_rate: u64,
-        acc_interest_per_share: &Balance,
+        borrow_index: &Balance,
         min_cr: u64,
     ) -> Balance {
         let deposit_amount = self.get_token_deposit(token_id);
         if token_id.clone() == self.lend_token_id {
-            let unpaid_lending_interest_profit = self.unpaid_lending_interest_profit;
             require!(
-                amount <= deposit_amount + unpaid_lending_interest_profit.clone(),
+                amount <= deposit_amount,
                 format!(
                     "user has insufficient asset {} for withdrawal",
                     token_id.clone()
                 )
             );
-            let mut remain = amount.clone();
-            if remain >= unpaid_lending_interest_profit {
-                remain = remain - unpaid_lending_interest_profit.clone();
-                self.unpaid_lending_interest_profit = 0;
-                log!("withdrawing {}, {}", deposit_amount, remain);
-                self.deposits
-                    .insert(&self.lend_token_id, &(deposit_amount - remain));
-                self.update_lending_profit(interest_rate, acc_interest_per_share);
-                return remain;
-            } else {
-                self.unpaid_lending_interest_profit -= remain;
-                return 0;
-            }
+            self.deposits
+                .insert(&self.lend_token_id, &(deposit_amount - amount));
+            return amount;
         } else if token_id.clone() == self.collateral_token_id {
             require!(
                 amount <= deposit_amount,
@@ -260,12 +226,12 @@ impl AccountDeposit {
                 )
             );
             let collateral_after_withdrawal = deposit_amount - amount;
-            if self.borrow_amount + self.get_interest_owed(interest_rate) > 0 {
+            if self.borrow_amount + self.get_interest_owed(borrow_index) > 0 {
                 let cr = compute_cr(
                     collateral_after_withdrawal,
                     collateral_token_info.decimals,
                     collateral_token_price,
-                    self.borrow_amount + self.get_interest_owed(interest_rate),
+                    self.borrow_amount + self.get_interest_owed(borrow_index),
                     lend_token_price,
                     lend_token_info.decimals,
                 );
@@ -277,49 +243,18 @@ impl AccountDeposit {
             }
 
             self.deposits.insert(token_id, &collateral_after_withdrawal);
-            self.update_lending_profit(interest_rate, acc_interest_per_share);
             return amount;
         } else {
             env::panic_str("invalid token");
         }
     }
 
-    pub fn compute_unrecorded_interest(&self, interest_rate: u64) -> Balance {
-        if self.borrow_amount == 0 {
-            return 0u128;
-        }
-        let last_borrowing_interest_update_timestamp_sec =
-            self.last_borrowing_interest_update_timestamp_sec.clone();
-        let current_time_sec = get_next_interest_recal_time_sec();
-        let interest = self.borrow_amount
-            * (((current_time_sec - last_borrowing_interest_update_timestamp_sec) * interest_rate)
-                as u128)
-            / (INTEREST_RATE_DIVISOR * SECONDS_PER_YEAR);
-        interest
-    }
-
-    //this does not take care of interest when user in a borrowing position
-    // pub fn internal_deposit_lend_token_with_taking_interest(
-    //     &mut self,
-    //     amount: &Balance,
-    //     interest_rate: u64,
-    //     acc_interest_per_share: &Balance,
-    // ) -> Balance {
-    //     if self.borrow_amount > 0 {
-    //         self.update_borrowing_interest(interest_rate);
-    //         self.internal_deposit_lend_token(amount, interest_rate, acc_interest_per_share);
-    //     } else {
-    //         self.internal_deposit_lend_token(amount, interest_rate, acc_interest_per_share);
-    //     }
-    //     amount.clone()
-    // }
-
+    //return the debt paid, interest included, and the amount left over as lend token deposit
     pub fn internal_pay_loan(&mut self, pay_amount: Balance) -> (Balance, Balance) {
         if pay_amount == 0 {
             return (0, 0);
         }
         log!("updating account");
-        let mut actual_borrow_paid = self.borrow_amount.clone();
         let mut remain = pay_amount.clone();
         if self.unpaid_borrowing_interest > 0 {
             if self.unpaid_borrowing_interest > remain {
@@ -345,8 +280,8 @@ impl AccountDeposit {
             }
         }
         log!("borrow_amount after {}", self.borrow_amount);
-        actual_borrow_paid -= self.borrow_amount;
-        log!("actual_borrow_paid {}", actual_borrow_paid);
+        let debt_paid = pay_amount.clone() - remain.clone();
+        log!("debt_paid {}", debt_paid);
 
         self.reduce_lend_token_deposit(pay_amount.clone());
 
@@ -354,7 +289,7 @@ impl AccountDeposit {
             self.internal_deposit_lend_token(&remain);
         }
 
-        (actual_borrow_paid, remain)
+        (debt_paid, remain)
     }
 
     pub fn reduce_collateral(
@@ -378,11 +313,14 @@ impl AccountDeposit {
         self.deposits.get(token_id).unwrap_or(0u128)
     }
 
-    pub fn get_deposits(&self) -> HashMap<AssetId, U128> {
+    pub fn get_deposits(&self, supply_index: &Balance) -> HashMap<AssetId, U128> {
         let mut ret = HashMap::<AssetId, U128>::new();
         ret.insert(
             self.lend_token_id.clone(),
-            U128(self.get_token_deposit(&self.lend_token_id)),
+            U128(
+                self.get_token_deposit(&self.lend_token_id)
+                    + self.compute_unrecorded_lending_interest(supply_index),
+            ),
         );
         ret.insert(
             self.collateral_token_id.clone(),
@@ -391,12 +329,8 @@ impl AccountDeposit {
         ret
     }
 
-    pub fn get_interest_owed(&self, interest_rate: u64) -> Balance {
-        let mut unpaid_interest = self.unpaid_borrowing_interest;
-        if self.borrow_amount > 0 {
-            unpaid_interest += self.compute_unrecorded_interest(interest_rate);
-        }
-        unpaid_interest
+    pub fn get_interest_owed(&self, borrow_index: &Balance) -> Balance {
+        self.unpaid_borrowing_interest + self.compute_unrecorded_interest(borrow_index)
     }
 
     pub fn compute_max_borrowable(
@@ -406,7 +340,7 @@ impl AccountDeposit {
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
         additional_collateral: Option<Balance>,
-        interest_rate: u64,
+        borrow_index: &Balance,
         cr: u64,
     ) -> Balance {
         let mut collateral_token_deposit = self.get_token_deposit(&self.collateral_token_id);
@@ -424,7 +358,7 @@ impl AccountDeposit {
 
         let mut max_borrowable = max_borrowable.as_u128();
         max_borrowable = max_borrowable * COLLATERAL_RATIO_DIVISOR / (cr as u128);
-        let interest_owed = self.get_interest_owed(interest_rate);
+        let interest_owed = self.get_interest_owed(borrow_index);
 
         let owed = self.borrow_amount + interest_owed;
 
@@ -438,55 +372,28 @@ impl AccountDeposit {
         self.borrow_amount + self.unpaid_borrowing_interest
     }
 
-    pub fn get_pending_unpaid_lending_interest_profit(
-        &self,
-        acc_interest_per_share: &Balance,
-    ) -> Balance {
-        let current_deposit = self.get_token_deposit(&self.lend_token_id);
-
-        let total_interest_reward = (U256::from(current_deposit)
-            * U256::from(acc_interest_per_share.clone())
-            / U256::from(ACC_INTEREST_PER_SHARE_MULTIPLIER))
-        .as_u128();
-        let unpaid_lending_interest_profit = self.unpaid_lending_interest_profit
-            + total_interest_reward
-            - self.lending_interest_profit_debt;
-        return unpaid_lending_interest_profit;
+    pub fn get_pending_total_lending_interest_profit(&self, supply_index: &Balance) -> Balance {
+        self.total_lending_interest_profit + self.compute_unrecorded_lending_interest(supply_index)
     }
 
-    pub fn get_total_interest_reward(&self, acc_interest_per_share: &Balance) -> Balance {
-        let current_deposit = self.get_token_deposit(&self.lend_token_id);
-
-        let total_interest_reward = (U256::from(current_deposit)
-            * U256::from(acc_interest_per_share.clone())
-            / U256::from(ACC_INTEREST_PER_SHARE_MULTIPLIER))
-        .as_u128();
-        total_interest_reward
+    pub fn get_pending_unpaid_borrowing_interest(&self, borrow_index: &Balance) -> Balance {
+        let unrecorded = self.compute_unrecorded_interest(borrow_index);
+        self.unpaid_borrowing_interest + unrecorded
     }
 
-    pub fn get_pending_total_lending_interest_profit(
-        &self,
-        acc_interest_per_share: &Balance,
-    ) -> Balance {
-        let current_deposit = self.get_token_deposit(&self.lend_token_id);
-
-        let total_interest_reward = (U256::from(current_deposit)
-            * U256::from(acc_interest_per_share.clone())
-            / U256::from(ACC_INTEREST_PER_SHARE_MULTIPLIER))
-        .as_u128();
-        let total_lending_interest_profit = self.total_lending_interest_profit
-            + total_interest_reward
-            - self.lending_interest_profit_debt;
-        return total_lending_interest_profit;
+    pub fn get_pending_total_borrowing_interest(&self, borrow_index: &Balance) -> Balance {
+        let unrecorded = self.compute_unrecorded_interest(borrow_index);
+        self.total_borrowing_interest + unrecorded
     }
 
-    pub fn get_pending_unpaid_borrowing_interest(&self, interest_rate: u64) -> Balance {
-        let unrecorded = self.compute_unrecorded_interest(interest_rate);
-        self.unpaid_borrowing_interest + unrecorded
+    //interest accrued on the debt, unpaid interest included, since the last update
+    pub fn compute_unrecorded_interest(&self, borrow_index: &Balance) -> Balance {
+        let owed = self.borrow_amount + self.unpaid_borrowing_interest;
+        apply_index_growth(owed, borrow_index, &self.borrow_index) - owed
     }
 
-    pub fn get_pending_total_borrowing_interest(&self, interest_rate: u64) -> Balance {
-        let unrecorded = self.compute_unrecorded_interest(interest_rate);
-        self.total_borrowing_interest + unrecorded
+    pub fn compute_unrecorded_lending_interest(&self, supply_index: &Balance) -> Balance {
+        let current_deposit = self.get_token_deposit(&self.lend_token_id);
+        apply_index_growth(current_deposit, supply_index, &self.supply_index) - current_deposit
     }
 }
@@ -52,6 +52,7 @@ impl Contract {
     /// Resumes the contract. Only can be called by owner.
     pub fn resume(&mut self) {
         self.assert_governance();
+        require!(!self.is_migrating(), "account deposits not migrated");
         self.status = ContractStatus::Working;
     }
 
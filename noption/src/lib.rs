@@ -1,10 +1,16 @@
 mod governance;
+mod multi_fungible_token;
 mod oracle;
 //mod storage;
 mod storage_impl;
 //mod token_receiver;
 //mod utils;
 mod account_deposit;
+mod migration;
+mod option_amm;
+mod option_pricing;
+mod option_vault;
+mod options;
 mod pool;
 mod token_receiver;
 mod utils;
@@ -20,11 +26,16 @@ use near_sdk::{
 };
 
 use account_deposit::AccountDeposit;
+use migration::ContractV1;
+use option_amm::OptionAmm;
+use option_vault::OptionVault;
+use options::{OptionPosition, OptionSeries, OptionSeriesTerms};
 use pool::{new_pool_default, Pool};
 
 pub type AssetId = AccountId;
 
 use oracle::{Price, PriceData};
+use std::collections::HashMap;
 use std::fmt::Debug;
 
 use views::U256;
@@ -32,11 +43,10 @@ use views::U256;
 const COLLATERAL_RATIO_DIVISOR: u128 = 10000;
 const UTILIZATION_DIVISOR: u128 = 10000;
 const INTEREST_RATE_DIVISOR: u128 = 10000;
-const ACC_INTEREST_PER_SHARE_MULTIPLIER: u128 = 10u128.pow(8 as u32);
+const INTEREST_INDEX_PRECISION: u128 = 10u128.pow(18 as u32);
 const SECONDS_PER_YEAR: u128 = 365 * 86400;
 const LIQUIDATION_BONUS_DIVISOR: u128 = 10000;
 const LIQUIDATION_MARGINAL_DIVISOR: u128 = 10000;
-const INTEREST_RECAL_PERIOD_SEC: u64 = 600; //10 minutes
 
 #[derive(BorshStorageKey, BorshSerialize)]
 enum StorageKey {
@@ -49,6 +59,15 @@ enum StorageKey {
     BorrowPools,
     UserStorage,
     AccountDeposit { pool_id: u32, account_id: AccountId },
+    OptionSeries,
+    OptionSeriesByTerms,
+    OptionPositions,
+    OptionBalances,
+    OptionAmmShares { amm_id: u32 },
+    OptionAmmWritten { amm_id: u32 },
+    OptionVaultAccounts { vault_id: u32 },
+    OptionVaultEpochs { vault_id: u32 },
+    PoolMigrations,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
@@ -131,6 +150,13 @@ pub struct Contract {
     storage_usage_join_pool: StorageUsage,
     account_list: Vec<AccountId>,
     liquidation_marginal: u64, //how mujch in terms of % the treasury got
+    option_series: UnorderedMap<u64, OptionSeries>,
+    option_series_by_terms: LookupMap<OptionSeriesTerms, u64>,
+    next_option_series_id: u64,
+    option_positions: LookupMap<AccountId, HashMap<u64, OptionPosition>>,
+    option_balances: LookupMap<AccountId, HashMap<AssetId, U128>>, //exercise payoffs and reclaimed collateral to withdraw
+    option_amms: Vec<OptionAmm>,
+    option_vaults: Vec<OptionVault>,
 }
 
 #[near_bindgen]
@@ -167,6 +193,13 @@ impl Contract {
             storage_usage_join_pool: 0,
             account_list: vec![],
             liquidation_marginal: 5000,
+            option_series: UnorderedMap::new(StorageKey::OptionSeries),
+            option_series_by_terms: LookupMap::new(StorageKey::OptionSeriesByTerms),
+            next_option_series_id: 0,
+            option_positions: LookupMap::new(StorageKey::OptionPositions),
+            option_balances: LookupMap::new(StorageKey::OptionBalances),
+            option_amms: vec![],
+            option_vaults: vec![],
         };
 
         this.measure_account_storage_usage();
@@ -603,15 +636,16 @@ impl Contract {
         format!("{}:{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
     }
 
-    /// This is NOOP implementation. KEEP IT if you haven't changed contract state.
+    /// Migrates the state from the version without interest indexes and options.
     /// Should only be called by this contract on migration.
     /// This method is called from `upgrade()` method.
+    /// The contract is paused until governance converts the account deposits with `migrate_account_deposits`.
     /// For next version upgrades, change this function.
     #[init(ignore_state)]
     #[private]
     pub fn migrate() -> Self {
-        let contract: Self = env::state_read().expect("Contract is not initialized");
-        contract
+        let old_data: ContractV1 = env::state_read().expect("Contract is not initialized");
+        Contract::from_v1(old_data)
     }
 
     fn abort_if_pause(&self) {
@@ -768,7 +802,7 @@ mod tests {
     use near_contract_standards::storage_management::StorageManagement;
     use near_sdk::serde_json;
     use near_sdk::test_utils::VMContextBuilder;
-    use near_sdk::{testing_env, Balance};
+    use near_sdk::{testing_env, Balance, PromiseOrValue};
     //use near_sdk_sim::to_yocto;
 
     use super::*;
@@ -924,4 +958,717 @@ mod tests {
             }
         }
     }
+    #[test]
+    fn interest_indexes_accrue_per_second() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.total_lend_asset_deposit = 1_000_000_000;
+        pool.total_borrow = 500_000_000;
+
+        //10% APR for one year
+        testing_env!(context
+            .block_timestamp(SECONDS_PER_YEAR as u64 * 1_000_000_000)
+            .build());
+        pool.update_indexes();
+        assert_eq!(pool.total_borrow, 550_000_000);
+        assert_eq!(pool.total_lend_asset_deposit, 1_050_000_000);
+        assert_eq!(pool.borrow_index, INTEREST_INDEX_PRECISION * 11 / 10);
+        assert_eq!(pool.supply_index, INTEREST_INDEX_PRECISION * 105 / 100);
+    }
+
+    fn push_prices(contract: &mut Contract, timestamp: u64, prices: Vec<(AccountId, u128)>) {
+        let prices = prices
+            .iter()
+            .map(|(asset_id, multiplier)| {
+                format!(
+                    r#"{{"asset_id":"{}","price":{{"multiplier":"{}","decimals":8}}}}"#,
+                    asset_id, multiplier
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let price_data = serde_json::from_str(&format!(
+            r#"{{"timestamp":"{}","recency_duration_sec":60,"prices":[{}]}}"#,
+            timestamp, prices
+        ))
+        .unwrap();
+        contract.push_price_data(price_data);
+    }
+
+    //a put series expiring at 1000 sec
+    fn setup_series(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        add_supported_tokens(context, contract);
+        testing_env!(context
+            .block_timestamp(0)
+            .attached_deposit(ONE_NEAR)
+            .predecessor_account_id(get_account(5))
+            .build());
+        contract.storage_deposit(None, None);
+        contract.create_option_series(
+            get_account(4),
+            get_account(3),
+            U128(100 * 10u128.pow(8)),
+            1000,
+            options::OptionType::Put,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    #[should_panic(expected = "price data reported after the settlement window")]
+    fn series_settles_on_the_price_at_expiry() {
+        let (mut context, mut contract) =
+            setup_contract(get_account(0), get_account(1), get_account(2));
+        let series_id = setup_series(&mut context, &mut contract);
+        let timestamp = (1000 + options::SETTLEMENT_PRICE_WINDOW_SEC + 1) * 10u64.pow(9);
+        testing_env!(context
+            .block_timestamp(timestamp)
+            .attached_deposit(0)
+            .predecessor_account_id(get_account(2))
+            .build());
+        push_prices(
+            &mut contract,
+            timestamp,
+            vec![(get_account(3), 100000000), (get_account(4), 8000000000)],
+        );
+        contract.settle_option_series(series_id);
+    }
+
+    #[test]
+    fn governance_settles_missed_series() {
+        let (mut context, mut contract) =
+            setup_contract(get_account(0), get_account(1), get_account(2));
+        let series_id = setup_series(&mut context, &mut contract);
+        testing_env!(context
+            .block_timestamp((1000 + options::SETTLEMENT_PRICE_WINDOW_SEC + 1) * 10u64.pow(9))
+            .predecessor_account_id(get_account(0))
+            .build());
+        contract.settle_option_series_by_governance(series_id, U128(80 * 10u128.pow(8)));
+        assert_eq!(
+            contract.option_series.get(&series_id).unwrap().settlement_price.unwrap().0,
+            80 * 10u128.pow(8)
+        );
+    }
+
+    #[test]
+    fn put_series_exercise_and_reclaim() {
+        let (mut context, mut contract) =
+            setup_contract(get_account(0), get_account(1), get_account(2));
+        add_supported_tokens(&mut context, &mut contract);
+        let strike_token_id = get_account(3);
+        let underlying_token_id = get_account(4);
+        let writer = get_account(5);
+
+        testing_env!(context
+            .block_timestamp(0)
+            .attached_deposit(ONE_NEAR)
+            .predecessor_account_id(writer.clone())
+            .build());
+        contract.storage_deposit(None, None);
+        let series_id = contract.create_option_series(
+            underlying_token_id.clone(),
+            strike_token_id.clone(),
+            U128(100 * 10u128.pow(8)),
+            1000,
+            options::OptionType::Put,
+            None,
+            None,
+        );
+
+        //250 strike tokens cover 2.5 puts at a strike of 100
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(strike_token_id.clone())
+            .build());
+        let message = TokenReceiverMessage::WriteOption { series_id };
+        contract.ft_on_transfer(
+            writer.clone(),
+            U128(250 * 10u128.pow(8)),
+            serde_json::to_string(&message).unwrap(),
+        );
+        let position = contract.get_option_positions(writer.clone())[&series_id].clone();
+        assert_eq!(position.held.0, 25 * 10u128.pow(17));
+        assert_eq!(position.collateral.0, 250 * 10u128.pow(8));
+
+        //the underlying settles at 80
+        testing_env!(context
+            .block_timestamp(1000 * 10u64.pow(9))
+            .attached_deposit(0)
+            .predecessor_account_id(get_account(2))
+            .build());
+        push_prices(
+            &mut contract,
+            1000 * 10u64.pow(9),
+            vec![(strike_token_id.clone(), 100000000), (underlying_token_id.clone(), 8000000000)],
+        );
+        assert_eq!(contract.settle_option_series(series_id).0, 80 * 10u128.pow(8));
+
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(writer.clone())
+            .build());
+        assert_eq!(
+            contract.exercise_option(series_id, Some(U128(10u128.pow(18)))).0,
+            20 * 10u128.pow(8)
+        );
+        //the payoff of all written options stays locked for the holders
+        assert_eq!(contract.reclaim_option_collateral(series_id).0, 200 * 10u128.pow(8));
+        let series = contract.get_option_series(series_id).unwrap();
+        assert_eq!(series.total_collateral.0, 30 * 10u128.pow(8));
+        assert_eq!(
+            contract.get_option_balances(writer.clone())[&strike_token_id].0,
+            220 * 10u128.pow(8)
+        );
+    }
+
+    #[test]
+    fn transferred_call_options_are_exercised_by_holder() {
+        let (mut context, mut contract) =
+            setup_contract(get_account(0), get_account(1), get_account(2));
+        add_supported_tokens(&mut context, &mut contract);
+        let strike_token_id = get_account(3);
+        let underlying_token_id = get_account(4);
+        let writer = get_account(5);
+        let holder = get_account(6);
+
+        testing_env!(context
+            .attached_deposit(ONE_NEAR)
+            .predecessor_account_id(holder.clone())
+            .build());
+        contract.storage_deposit(None, None);
+        testing_env!(context
+            .block_timestamp(0)
+            .attached_deposit(ONE_NEAR)
+            .predecessor_account_id(writer.clone())
+            .build());
+        contract.storage_deposit(None, None);
+        let series_id = contract.create_option_series(
+            underlying_token_id.clone(),
+            strike_token_id.clone(),
+            U128(100 * 10u128.pow(8)),
+            1000,
+            options::OptionType::Call,
+            None,
+            None,
+        );
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(underlying_token_id.clone())
+            .build());
+        let message = TokenReceiverMessage::WriteOption { series_id };
+        contract.ft_on_transfer(
+            writer.clone(),
+            U128(2 * 10u128.pow(18)),
+            serde_json::to_string(&message).unwrap(),
+        );
+
+        let token_id = format!(":{}", series_id);
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(writer.clone())
+            .build());
+        contract.mft_transfer(token_id.clone(), holder.clone(), U128(10u128.pow(18)), None);
+        assert_eq!(contract.mft_balance_of(token_id.clone(), holder.clone()).0, 10u128.pow(18));
+        assert_eq!(contract.mft_balance_of(token_id.clone(), writer.clone()).0, 10u128.pow(18));
+
+        //the underlying settles at 125, a call pays 25/125 of the underlying
+        testing_env!(context
+            .block_timestamp(1000 * 10u64.pow(9))
+            .attached_deposit(0)
+            .predecessor_account_id(get_account(2))
+            .build());
+        push_prices(
+            &mut contract,
+            1000 * 10u64.pow(9),
+            vec![(strike_token_id.clone(), 100000000), (underlying_token_id.clone(), 12500000000)],
+        );
+        contract.settle_option_series(series_id);
+
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(holder.clone())
+            .build());
+        assert_eq!(contract.exercise_option(series_id, None).0, 2 * 10u128.pow(17));
+        assert_eq!(contract.mft_balance_of(token_id.clone(), holder.clone()).0, 0);
+        assert_eq!(contract.mft_total_supply(token_id).0, 10u128.pow(18));
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(writer.clone())
+            .build());
+        assert_eq!(
+            contract.reclaim_option_collateral(series_id).0,
+            16 * 10u128.pow(17)
+        );
+    }
+
+    #[test]
+    fn option_value_matches_black_scholes() {
+        let strike = 100 * 10u128.pow(8);
+        let year = SECONDS_PER_YEAR as u64;
+        let call = option_pricing::compute_option_value(strike, strike, year, 5000, 0, options::OptionType::Call);
+        let put = option_pricing::compute_option_value(strike, strike, year, 5000, 0, options::OptionType::Put);
+        //at the money with 50% volatility for a year, S * (2 * N(0.25) - 1) = 19.74
+        assert!(call > 1974 * 10u128.pow(6) && call < 1975 * 10u128.pow(6));
+        assert_eq!(call, put);
+        //put call parity with a zero interest rate
+        let spot = 120 * 10u128.pow(8);
+        let call = option_pricing::compute_option_value(spot, strike, year / 2, 6000, 0, options::OptionType::Call);
+        let put = option_pricing::compute_option_value(spot, strike, year / 2, 6000, 0, options::OptionType::Put);
+        assert!((call - put) as i128 - (spot - strike) as i128 <= 10);
+        assert!((spot - strike) as i128 - (call - put) as i128 <= 10);
+    }
+
+    #[test]
+    fn option_amm_sells_puts_and_settles_for_lps() {
+        let (mut context, mut contract) =
+            setup_contract(get_account(0), get_account(1), get_account(2));
+        add_supported_tokens(&mut context, &mut contract);
+        let strike_token_id = get_account(3);
+        let underlying_token_id = get_account(4);
+        let lp = get_account(5);
+        let buyer = get_account(6);
+        let year = SECONDS_PER_YEAR as u64;
+
+        testing_env!(context
+            .block_timestamp(0)
+            .attached_deposit(ONE_NEAR)
+            .predecessor_account_id(get_account(0))
+            .build());
+        let amm_id = contract.create_option_amm(
+            underlying_token_id.clone(),
+            strike_token_id.clone(),
+            options::OptionType::Put,
+            5000,
+            0,
+        );
+        let series_id = contract.create_option_series(
+            underlying_token_id.clone(),
+            strike_token_id.clone(),
+            U128(100 * 10u128.pow(8)),
+            year,
+            options::OptionType::Put,
+            None,
+            None,
+        );
+        for account_id in [lp.clone(), buyer.clone()] {
+            testing_env!(context
+                .attached_deposit(ONE_NEAR)
+                .predecessor_account_id(account_id)
+                .build());
+            contract.storage_deposit(None, None);
+        }
+        testing_env!(context
+            .attached_deposit(0)
+            .predecessor_account_id(get_account(2))
+            .build());
+        push_prices(
+            &mut contract,
+            0,
+            vec![(strike_token_id.clone(), 100000000), (underlying_token_id.clone(), 10000000000)],
+        );
+
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(strike_token_id.clone())
+            .build());
+        contract.ft_on_transfer(
+            lp.clone(),
+            U128(1000 * 10u128.pow(8)),
+            serde_json::to_string(&TokenReceiverMessage::AddOptionAmmLiquidity { amm_id }).unwrap(),
+        );
+        let premium = contract
+            .quote_option_premium(amm_id, series_id, U128(10u128.pow(18)))
+            .0;
+        assert!(premium > 1974 * 10u128.pow(6) && premium < 1975 * 10u128.pow(6));
+        let message = TokenReceiverMessage::BuyOption {
+            amm_id,
+            series_id,
+            amount: U128(10u128.pow(18)),
+        };
+        let refund = contract.ft_on_transfer(
+            buyer.clone(),
+            U128(50 * 10u128.pow(8)),
+            serde_json::to_string(&message).unwrap(),
+        );
+        match refund {
+            PromiseOrValue::Value(refund) => assert_eq!(refund.0, 50 * 10u128.pow(8) - premium),
+            _ => panic!("expected a refund"),
+        }
+        assert_eq!(
+            contract.mft_balance_of(format!(":{}", series_id), buyer.clone()).0,
+            10u128.pow(18)
+        );
+        let amm = contract.get_option_amm(amm_id);
+        assert_eq!(amm.free_liquidity.0, 900 * 10u128.pow(8) + premium);
+        assert_eq!(amm.locked_collateral.0, 100 * 10u128.pow(8));
+        assert_eq!(amm.open_series, vec![series_id]);
+        //the written puts are valued at the premium they sold for
+        assert_eq!(contract.get_option_amm_value(amm_id).0, 1000 * 10u128.pow(8));
+
+        //the underlying settles at 80, holders are owed 20 per put
+        testing_env!(context
+            .block_timestamp(year * 10u64.pow(9))
+            .attached_deposit(0)
+            .predecessor_account_id(get_account(2))
+            .build());
+        push_prices(
+            &mut contract,
+            year * 10u64.pow(9),
+            vec![(strike_token_id.clone(), 100000000), (underlying_token_id.clone(), 8000000000)],
+        );
+        contract.settle_option_series(series_id);
+        assert_eq!(contract.collect_option_amm_series(amm_id, series_id).0, 80 * 10u128.pow(8));
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(buyer.clone())
+            .build());
+        assert_eq!(contract.exercise_option(series_id, None).0, 20 * 10u128.pow(8));
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(lp.clone())
+            .build());
+        assert_eq!(
+            contract.remove_option_amm_liquidity(amm_id, U128(1000 * 10u128.pow(8))).0,
+            980 * 10u128.pow(8) + premium
+        );
+    }
+
+    #[test]
+    fn covered_call_vault_rolls_epochs() {
+        let (mut context, mut contract) =
+            setup_contract(get_account(0), get_account(1), get_account(2));
+        add_supported_tokens(&mut context, &mut contract);
+        let strike_token_id = get_account(3);
+        let underlying_token_id = get_account(4);
+        let (alice, bob, buyer) = (get_account(5), get_account(6), get_account(7));
+        let week = 7 * 86400u64;
+
+        testing_env!(context
+            .block_timestamp(0)
+            .attached_deposit(ONE_NEAR)
+            .predecessor_account_id(get_account(0))
+            .build());
+        let vault_id = contract.create_option_vault(
+            underlying_token_id.clone(),
+            strike_token_id.clone(),
+            options::OptionType::Call,
+            1000,
+            week,
+            8000,
+        );
+        for account_id in [alice.clone(), bob.clone(), buyer.clone()] {
+            testing_env!(context
+                .attached_deposit(ONE_NEAR)
+                .predecessor_account_id(account_id)
+                .build());
+            contract.storage_deposit(None, None);
+        }
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(underlying_token_id.clone())
+            .build());
+        contract.ft_on_transfer(
+            alice.clone(),
+            U128(10 * 10u128.pow(18)),
+            serde_json::to_string(&TokenReceiverMessage::DepositToOptionVault { vault_id }).unwrap(),
+        );
+
+        testing_env!(context
+            .attached_deposit(ONE_NEAR)
+            .predecessor_account_id(get_account(2))
+            .build());
+        push_prices(
+            &mut contract,
+            0,
+            vec![(strike_token_id.clone(), 100000000), (underlying_token_id.clone(), 10000000000)],
+        );
+        let series_id = contract.roll_option_vault(vault_id);
+        assert_eq!(
+            contract.get_option_series(series_id).unwrap().strike_price.0,
+            110 * 10u128.pow(8)
+        );
+        assert_eq!(
+            contract.get_option_vault_account(vault_id, alice.clone()).shares.0,
+            10 * 10u128.pow(18)
+        );
+
+        let premium = contract
+            .quote_vault_option_premium(vault_id, U128(4 * 10u128.pow(18)))
+            .0;
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(underlying_token_id.clone())
+            .build());
+        let message = TokenReceiverMessage::BuyVaultOption {
+            vault_id,
+            amount: U128(4 * 10u128.pow(18)),
+        };
+        contract.ft_on_transfer(buyer.clone(), U128(premium), serde_json::to_string(&message).unwrap());
+        contract.ft_on_transfer(
+            bob.clone(),
+            U128(2 * 10u128.pow(18)),
+            serde_json::to_string(&TokenReceiverMessage::DepositToOptionVault { vault_id }).unwrap(),
+        );
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(alice.clone())
+            .build());
+        contract.queue_option_vault_withdrawal(vault_id, U128(5 * 10u128.pow(18)));
+
+        //the underlying ends at 132, each call is owed 22 / 132 of the underlying
+        testing_env!(context
+            .block_timestamp(week * 10u64.pow(9))
+            .attached_deposit(ONE_NEAR)
+            .predecessor_account_id(get_account(2))
+            .build());
+        push_prices(
+            &mut contract,
+            week * 10u64.pow(9),
+            vec![(strike_token_id.clone(), 100000000), (underlying_token_id.clone(), 13200000000)],
+        );
+        contract.settle_option_series(series_id);
+        contract.roll_option_vault(vault_id);
+        let assets = 6 * 10u128.pow(18) + premium + (4 * 10u128.pow(18) - 666666666666666667);
+        let vault = contract.get_option_vault(vault_id);
+        assert_eq!(vault.epoch, 2);
+        assert_eq!(vault.free_assets.0, assets - assets / 2 + 2 * 10u128.pow(18));
+        assert_eq!(
+            contract.get_option_vault_account(vault_id, bob.clone()).shares.0,
+            2 * 10u128.pow(18) * 10u128.pow(19) / assets
+        );
+
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(alice.clone())
+            .build());
+        assert_eq!(contract.claim_option_vault_withdrawal(vault_id).0, assets / 2);
+        assert_eq!(
+            contract.get_option_vault_account(vault_id, alice.clone()).shares.0,
+            5 * 10u128.pow(18)
+        );
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(buyer.clone())
+            .build());
+        assert_eq!(contract.exercise_option(series_id, None).0, 666666666666666666);
+    }
+
+    #[test]
+    fn physical_call_exercise_and_auto_exercise() {
+        let (mut context, mut contract) =
+            setup_contract(get_account(0), get_account(1), get_account(2));
+        add_supported_tokens(&mut context, &mut contract);
+        let strike_token_id = get_account(3);
+        let underlying_token_id = get_account(4);
+        let writer = get_account(5);
+        let holder = get_account(6);
+
+        //the holder and the keeper pay the storage of the option balances they add
+        for account_id in [holder.clone(), get_account(2)] {
+            testing_env!(context
+                .attached_deposit(ONE_NEAR)
+                .predecessor_account_id(account_id)
+                .build());
+            contract.storage_deposit(None, None);
+        }
+        testing_env!(context
+            .block_timestamp(0)
+            .attached_deposit(ONE_NEAR)
+            .predecessor_account_id(writer.clone())
+            .build());
+        contract.storage_deposit(None, None);
+        let series_id = contract.create_option_series(
+            underlying_token_id.clone(),
+            strike_token_id.clone(),
+            U128(100 * 10u128.pow(8)),
+            1000,
+            options::OptionType::Call,
+            Some(options::SettlementMode::Physical),
+            Some(3600),
+        );
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(underlying_token_id.clone())
+            .build());
+        contract.ft_on_transfer(
+            writer.clone(),
+            U128(2 * 10u128.pow(18)),
+            serde_json::to_string(&TokenReceiverMessage::WriteOption { series_id }).unwrap(),
+        );
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(writer.clone())
+            .build());
+        contract.mft_transfer(format!(":{}", series_id), holder.clone(), U128(10u128.pow(18)), None);
+
+        //the holder pays the strike for one underlying during the window, the extra is refunded
+        testing_env!(context
+            .block_timestamp(1000 * 10u64.pow(9))
+            .attached_deposit(1)
+            .predecessor_account_id(strike_token_id.clone())
+            .build());
+        let message = TokenReceiverMessage::ExerciseOption {
+            series_id,
+            amount: U128(10u128.pow(18)),
+        };
+        match contract.ft_on_transfer(
+            holder.clone(),
+            U128(101 * 10u128.pow(8)),
+            serde_json::to_string(&message).unwrap(),
+        ) {
+            PromiseOrValue::Value(refund) => assert_eq!(refund.0, 10u128.pow(8)),
+            _ => panic!("expected a refund"),
+        }
+        assert_eq!(
+            contract.get_option_balances(holder.clone())[&underlying_token_id].0,
+            10u128.pow(18)
+        );
+
+        //the underlying settles at 125 and the writer's own options are auto exercised after the window
+        testing_env!(context
+            .block_timestamp(4600 * 10u64.pow(9))
+            .attached_deposit(0)
+            .predecessor_account_id(get_account(2))
+            .build());
+        push_prices(
+            &mut contract,
+            4600 * 10u64.pow(9),
+            vec![(strike_token_id.clone(), 100000000), (underlying_token_id.clone(), 12500000000)],
+        );
+        contract.settle_option_series(series_id);
+        assert_eq!(
+            contract.auto_exercise_options(series_id, vec![writer.clone(), holder.clone()]).0,
+            2 * 10u128.pow(17)
+        );
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(writer.clone())
+            .build());
+        assert_eq!(contract.reclaim_option_collateral(series_id).0, 8 * 10u128.pow(17));
+        let balances = contract.get_option_balances(writer.clone());
+        assert_eq!(balances[&underlying_token_id].0, 10u128.pow(18));
+        assert_eq!(balances[&strike_token_id].0, 100 * 10u128.pow(8));
+        assert_eq!(contract.get_option_series(series_id).unwrap().total_collateral.0, 0);
+    }
+
+    #[test]
+    fn vault_deposits_after_losing_all_assets() {
+        use option_vault::compute_deposit_shares;
+        assert_eq!(compute_deposit_shares(100, 0, 0), 100);
+        assert_eq!(compute_deposit_shares(100, 200, 50), 25);
+        //the 50 worthless shares keep 1 / 101 of the vault
+        assert_eq!(compute_deposit_shares(100, 0, 50), 5000);
+    }
+
+    #[test]
+    fn migrate_converts_pools_to_interest_indexes() {
+        use migration::{AccountDepositV1, PoolV1};
+        let governance = get_account(0);
+        let lender = get_account(5);
+        let borrower = get_account(6);
+        let lend_token_id = get_account(3);
+        let collateral_token_id = get_account(4);
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(governance.clone()).build());
+
+        let mut old_deposits: UnorderedMap<AccountId, AccountDepositV1> =
+            UnorderedMap::new(format!("p_account_deposits_{}", 0).as_bytes());
+        for (account_id, token_id, amount, borrow_amount) in [
+            (lender.clone(), lend_token_id.clone(), 1000, 0),
+            (borrower.clone(), collateral_token_id.clone(), 2000, 500),
+        ] {
+            let mut deposits = UnorderedMap::new(StorageKey::AccountDeposit {
+                pool_id: 0,
+                account_id: account_id.clone(),
+            });
+            deposits.insert(&token_id, &amount);
+            old_deposits.insert(
+                &account_id,
+                &AccountDepositV1 {
+                    pool_id: 0,
+                    owner_id: account_id.clone(),
+                    lend_token_id: lend_token_id.clone(),
+                    collateral_token_id: collateral_token_id.clone(),
+                    deposits: deposits,
+                    borrow_amount: borrow_amount,
+                    lending_interest_profit_debt: 0,
+                    unpaid_lending_interest_profit: if borrow_amount == 0 { 5 } else { 0 },
+                    total_lending_interest_profit: 0,
+                    last_lending_interest_reward_update_timestamp_sec: 0,
+                    unpaid_borrowing_interest: 0,
+                    total_borrowing_interest: 0,
+                    last_borrowing_interest_update_timestamp_sec: 0,
+                },
+            );
+        }
+        let old_pool = PoolV1 {
+            pool_id: 0,
+            owner_id: governance.clone(),
+            lend_token_id: lend_token_id.clone(),
+            collateral_token_id: collateral_token_id.clone(),
+            min_cr: 15000,
+            max_utilization: 9000,
+            min_lend_token_deposit: 0,
+            min_lend_token_borrow: 0,
+            total_lend_asset_deposit: 1000,
+            total_collateral_deposit: 2000,
+            total_borrow: 500,
+            account_deposits: UnorderedMap::try_from_slice(&old_deposits.try_to_vec().unwrap())
+                .unwrap(),
+            fixed_interest_rate: 1000,
+            acc_interest_per_share: 0,
+            last_acc_interest_update_timestamp_sec: 0,
+            liquidation_bonus: 1000,
+            liquidation_history: vec![],
+        };
+        env::state_write(&ContractV1 {
+            governance: governance.clone(),
+            black_list: LookupMap::new(StorageKey::Blacklist),
+            status: ContractStatus::Working,
+            supported_tokens: LookupMap::new(StorageKey::SupportedTokens),
+            token_list: vec![],
+            price_data: PriceData::default(),
+            price_feeder: governance.clone(),
+            foundation_id: get_account(1),
+            pool_creation_fee: 10 * ONE_NEAR,
+            pools: vec![old_pool],
+            token_to_list_lend_pools: UnorderedMap::new(StorageKey::TokenToListLendPools),
+            token_to_list_collateral_pools: UnorderedMap::new(StorageKey::TokenToListCollateralPools),
+            created_pools: UnorderedMap::new(StorageKey::CreatedPools),
+            deposited_pools: UnorderedMap::new(StorageKey::DepositedPools),
+            borrow_pools: UnorderedMap::new(StorageKey::BorrowPools),
+            storage_accounts: LookupMap::new(StorageKey::UserStorage),
+            storage_usage_add_pool: 0,
+            storage_usage_join_pool: 0,
+            account_list: vec![],
+            liquidation_marginal: 5000,
+        });
+
+        testing_env!(context
+            .block_timestamp(SECONDS_PER_YEAR as u64 * 1_000_000_000)
+            .build());
+        let mut contract = Contract::migrate();
+        assert_eq!(contract.status, ContractStatus::Paused);
+        assert!(contract.is_migrating());
+        assert_eq!(contract.migrate_account_deposits(0, 1), 1);
+        assert_eq!(contract.migrate_account_deposits(0, 10), 0);
+        assert!(!contract.is_migrating());
+        contract.resume();
+
+        let pool = &contract.pools[0];
+        //10% of the year on 500 borrowed goes to the lender
+        let lender_deposit = pool.account_deposits.get(&lender).unwrap();
+        assert_eq!(lender_deposit.get_token_deposit(&lend_token_id), 1000 + 5 + 50);
+        assert_eq!(lender_deposit.supply_index, INTEREST_INDEX_PRECISION);
+        let borrower_deposit = pool.account_deposits.get(&borrower).unwrap();
+        assert_eq!(borrower_deposit.borrow_amount, 500);
+        assert_eq!(borrower_deposit.unpaid_borrowing_interest, 50);
+        assert_eq!(borrower_deposit.get_token_deposit(&collateral_token_id), 2000);
+        assert_eq!(pool.total_lend_asset_deposit, 1055);
+        assert_eq!(pool.total_borrow, 550);
+        assert_eq!(contract.next_option_series_id, 0);
+        assert!(contract.option_amms.is_empty() && contract.option_vaults.is_empty());
+    }
 }
@@ -0,0 +1,264 @@
+use crate::*;
+use near_sdk::collections::UnorderedMap;
+use pool::Liquidation;
+
+const ACC_INTEREST_PER_SHARE_MULTIPLIER: u128 = 10u128.pow(8 as u32);
+
+/// Contract state before interest indexes and options.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    pub governance: AccountId,
+    pub black_list: LookupMap<AccountId, BlackListStatus>,
+    pub status: ContractStatus,
+    pub supported_tokens: LookupMap<AssetId, TokenInfo>,
+    pub token_list: Vec<AssetId>,
+    pub price_data: PriceData,
+    pub price_feeder: AccountId,
+    pub foundation_id: AccountId,
+    pub pool_creation_fee: Balance,
+    pub pools: Vec<PoolV1>,
+    pub token_to_list_lend_pools: UnorderedMap<AssetId, Vec<u32>>,
+    pub token_to_list_collateral_pools: UnorderedMap<AssetId, Vec<u32>>,
+    pub created_pools: UnorderedMap<AccountId, Vec<u32>>,
+    pub deposited_pools: UnorderedMap<AccountId, Vec<u32>>,
+    pub borrow_pools: UnorderedMap<AccountId, Vec<u32>>,
+    pub storage_accounts: LookupMap<AccountId, UserStorageUsage>,
+    pub storage_usage_add_pool: StorageUsage,
+    pub storage_usage_join_pool: StorageUsage,
+    pub account_list: Vec<AccountId>,
+    pub liquidation_marginal: u64,
+}
+
+/// Pool sharing its interest with lenders through `acc_interest_per_share`.
+/// The account deposits keep their map, the values are converted by `migrate_account_deposits`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PoolV1 {
+    pub pool_id: u32,
+    pub owner_id: AccountId,
+    pub lend_token_id: AssetId,
+    pub collateral_token_id: AssetId,
+    pub min_cr: u64,
+    pub max_utilization: u64,
+    pub min_lend_token_deposit: Balance,
+    pub min_lend_token_borrow: Balance,
+    pub total_lend_asset_deposit: Balance,
+    pub total_collateral_deposit: Balance,
+    pub total_borrow: Balance,
+    pub account_deposits: UnorderedMap<AccountId, AccountDeposit>,
+    pub fixed_interest_rate: u64,
+    pub acc_interest_per_share: Balance,
+    pub last_acc_interest_update_timestamp_sec: u64,
+    pub liquidation_bonus: u64,
+    pub liquidation_history: Vec<Liquidation>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountDepositV1 {
+    pub pool_id: u32,
+    pub owner_id: AccountId,
+    pub lend_token_id: AssetId,
+    pub collateral_token_id: AssetId,
+    pub deposits: UnorderedMap<AssetId, Balance>,
+    pub borrow_amount: Balance,
+    pub lending_interest_profit_debt: Balance,
+    pub unpaid_lending_interest_profit: Balance,
+    pub total_lending_interest_profit: Balance,
+    pub last_lending_interest_reward_update_timestamp_sec: u64,
+    pub unpaid_borrowing_interest: Balance,
+    pub total_borrowing_interest: Balance,
+    pub last_borrowing_interest_update_timestamp_sec: u64,
+}
+
+/// What a pool still needs to convert its account deposits, removed once all of them are converted.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PoolMigration {
+    pub fixed_interest_rate: u64,
+    pub acc_interest_per_share: Balance, //brought up to the migration time
+    pub timestamp_sec: u64,
+    pub account_count: u64, //accounts in the old layout
+    pub next_index: u64,
+}
+
+impl PoolV1 {
+    fn get_current_acc_interest_per_share(&self) -> Balance {
+        if self.total_lend_asset_deposit == 0 || self.total_borrow == 0 {
+            return self.acc_interest_per_share;
+        }
+        let elapsed_time = (env::block_timestamp_ms() / 1000)
+            .saturating_sub(self.last_acc_interest_update_timestamp_sec);
+        let generated_interest = self.total_borrow
+            * elapsed_time as u128
+            * (self.fixed_interest_rate as u128)
+            / (SECONDS_PER_YEAR * INTEREST_RATE_DIVISOR);
+        self.acc_interest_per_share
+            + generated_interest * ACC_INTEREST_PER_SHARE_MULTIPLIER / self.total_lend_asset_deposit
+    }
+
+    //the pool runs the same rate on the new indexes, the interest accrued so far is settled per account
+    pub fn into_pool(self) -> (Pool, PoolMigration) {
+        let migration = PoolMigration {
+            fixed_interest_rate: self.fixed_interest_rate,
+            acc_interest_per_share: self.get_current_acc_interest_per_share(),
+            timestamp_sec: env::block_timestamp_ms() / 1000,
+            account_count: self.account_deposits.len(),
+            next_index: 0,
+        };
+        let pool = Pool {
+            pool_id: self.pool_id,
+            owner_id: self.owner_id,
+            lend_token_id: self.lend_token_id,
+            collateral_token_id: self.collateral_token_id,
+            min_cr: self.min_cr,
+            max_utilization: self.max_utilization,
+            min_lend_token_deposit: self.min_lend_token_deposit,
+            min_lend_token_borrow: self.min_lend_token_borrow,
+            total_lend_asset_deposit: self.total_lend_asset_deposit,
+            total_collateral_deposit: self.total_collateral_deposit,
+            total_borrow: self.total_borrow,
+            account_deposits: self.account_deposits,
+            fixed_interest_rate: self.fixed_interest_rate,
+            borrow_index: INTEREST_INDEX_PRECISION,
+            supply_index: INTEREST_INDEX_PRECISION,
+            last_index_update_timestamp_sec: migration.timestamp_sec,
+            liquidation_bonus: self.liquidation_bonus,
+            liquidation_history: self.liquidation_history,
+        };
+        (pool, migration)
+    }
+}
+
+impl AccountDepositV1 {
+    //returns the account in the new layout, the lending interest credited and the borrowing interest recorded
+    pub fn into_account_deposit(
+        self,
+        migration: &PoolMigration,
+        supply_index: Balance,
+        borrow_index: Balance,
+    ) -> (AccountDeposit, Balance, Balance) {
+        let current_deposit = self.deposits.get(&self.lend_token_id).unwrap_or(0);
+        let total_interest_reward = (U256::from(current_deposit)
+            * U256::from(migration.acc_interest_per_share)
+            / U256::from(ACC_INTEREST_PER_SHARE_MULTIPLIER))
+        .as_u128();
+        let pending = total_interest_reward.saturating_sub(self.lending_interest_profit_debt);
+        let lending_interest = self.unpaid_lending_interest_profit + pending;
+
+        let mut borrowing_interest = 0;
+        if self.borrow_amount > 0 {
+            let elapsed_time = migration
+                .timestamp_sec
+                .saturating_sub(self.last_borrowing_interest_update_timestamp_sec);
+            borrowing_interest = self.borrow_amount
+                * ((elapsed_time * migration.fixed_interest_rate) as u128)
+                / (INTEREST_RATE_DIVISOR * SECONDS_PER_YEAR);
+        }
+
+        let mut account_deposit = AccountDeposit::new(
+            self.pool_id,
+            self.owner_id,
+            self.lend_token_id,
+            self.collateral_token_id,
+        );
+        account_deposit.deposits = self.deposits;
+        if lending_interest > 0 {
+            account_deposit.internal_deposit_lend_token(&lending_interest);
+        }
+        account_deposit.borrow_amount = self.borrow_amount;
+        account_deposit.total_lending_interest_profit = self.total_lending_interest_profit + pending;
+        account_deposit.supply_index = supply_index;
+        account_deposit.unpaid_borrowing_interest =
+            self.unpaid_borrowing_interest + borrowing_interest;
+        account_deposit.total_borrowing_interest =
+            self.total_borrowing_interest + borrowing_interest;
+        account_deposit.borrow_index = borrow_index;
+        account_deposit.last_index_update_timestamp_sec = migration.timestamp_sec;
+        (account_deposit, lending_interest, borrowing_interest)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Converts up to `limit` account deposits of a pool migrated from the previous layout.
+    /// The contract stays paused until every pool is converted. Returns the accounts left.
+    pub fn migrate_account_deposits(&mut self, pool_id: u32, limit: u64) -> u64 {
+        self.assert_governance();
+        require!(limit != 0, "Cannot provide limit of 0.");
+        let mut migrations = LookupMap::<u32, PoolMigration>::new(StorageKey::PoolMigrations);
+        let mut migration = migrations.get(&pool_id).expect("pool already migrated");
+        let pool = &mut self.pools[pool_id as usize];
+        //same map layout, the values are read as the previous version
+        let old_deposits: UnorderedMap<AccountId, AccountDepositV1> =
+            UnorderedMap::try_from_slice(&pool.account_deposits.try_to_vec().unwrap()).unwrap();
+        let end = std::cmp::min(migration.account_count, migration.next_index + limit);
+        for index in migration.next_index..end {
+            let account_id = old_deposits.keys_as_vector().get(index).unwrap();
+            let old_deposit = old_deposits.values_as_vector().get(index).unwrap();
+            let (account_deposit, lending_interest, borrowing_interest) = old_deposit
+                .into_account_deposit(&migration, pool.supply_index, pool.borrow_index);
+            //raw insert, the replaced value is in the old layout
+            pool.account_deposits.insert_raw(
+                &account_id.try_to_vec().unwrap(),
+                &account_deposit.try_to_vec().unwrap(),
+            );
+            pool.total_lend_asset_deposit += lending_interest;
+            pool.total_borrow += borrowing_interest;
+        }
+
+        migration.next_index = end;
+        if end == migration.account_count {
+            migrations.remove(&pool_id);
+        } else {
+            migrations.insert(&pool_id, &migration);
+        }
+        migration.account_count - end
+    }
+
+    pub fn is_migrating(&self) -> bool {
+        let migrations = LookupMap::<u32, PoolMigration>::new(StorageKey::PoolMigrations);
+        self.pools.iter().any(|pool| migrations.contains_key(&pool.pool_id))
+    }
+}
+
+impl Contract {
+    pub fn from_v1(old: ContractV1) -> Contract {
+        let mut migrations = LookupMap::<u32, PoolMigration>::new(StorageKey::PoolMigrations);
+        let mut pools = vec![];
+        for old_pool in old.pools {
+            let (pool, migration) = old_pool.into_pool();
+            if migration.account_count > 0 {
+                migrations.insert(&pool.pool_id, &migration);
+            }
+            pools.push(pool);
+        }
+        Contract {
+            governance: old.governance,
+            black_list: old.black_list,
+            //accounts are converted by migrate_account_deposits before resuming
+            status: ContractStatus::Paused,
+            supported_tokens: old.supported_tokens,
+            token_list: old.token_list,
+            price_data: old.price_data,
+            price_feeder: old.price_feeder,
+            foundation_id: old.foundation_id,
+            pool_creation_fee: old.pool_creation_fee,
+            pools: pools,
+            token_to_list_lend_pools: old.token_to_list_lend_pools,
+            token_to_list_collateral_pools: old.token_to_list_collateral_pools,
+            created_pools: old.created_pools,
+            deposited_pools: old.deposited_pools,
+            borrow_pools: old.borrow_pools,
+            storage_accounts: old.storage_accounts,
+            storage_usage_add_pool: old.storage_usage_add_pool,
+            storage_usage_join_pool: old.storage_usage_join_pool,
+            account_list: old.account_list,
+            liquidation_marginal: old.liquidation_marginal,
+            option_series: UnorderedMap::new(StorageKey::OptionSeries),
+            option_series_by_terms: LookupMap::new(StorageKey::OptionSeriesByTerms),
+            next_option_series_id: 0,
+            option_positions: LookupMap::new(StorageKey::OptionPositions),
+            option_balances: LookupMap::new(StorageKey::OptionBalances),
+            option_amms: vec![],
+            option_vaults: vec![],
+        }
+    }
+}
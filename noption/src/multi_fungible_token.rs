@@ -0,0 +1,196 @@
+use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+use near_sdk::{ext_contract, near_bindgen, PromiseOrValue, PromiseResult};
+
+use crate::options::OptionType;
+use crate::utils::{GAS_FOR_MFT_TRANSFER_CALL, GAS_FOR_RESOLVE_TRANSFER};
+use crate::*;
+
+#[ext_contract(ext_self)]
+trait MFTTokenResolver {
+    fn mft_resolve_transfer(
+        &mut self,
+        token_id: String,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128;
+}
+
+#[ext_contract(ext_share_token_receiver)]
+pub trait MFTTokenReceiver {
+    fn mft_on_transfer(
+        &mut self,
+        token_id: String,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128>;
+}
+
+/// Held options of a series, `token_id` is ":<series_id>" like exchange LP shares.
+/// Balances are in underlying token units, minted on write and burnt on exercise or close.
+fn parse_series_id(token_id: &String) -> u64 {
+    require!(token_id.starts_with(":"), "illegal series id");
+    str::parse::<u64>(&token_id[1..token_id.len()]).expect("illegal series id")
+}
+
+#[near_bindgen]
+impl Contract {
+    //moves held options only, the written side and its collateral stay with the writer
+    fn internal_mft_transfer(
+        &mut self,
+        token_id: String,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: u128,
+        memo: Option<String>,
+    ) {
+        require!(sender_id != receiver_id, "transfer to self");
+        require!(amount > 0, "amount must be positive");
+        let series_id = parse_series_id(&token_id);
+        require!(self.option_series.get(&series_id).is_some(), "series not found");
+        let mut sender_position = self
+            .get_option_position(sender_id, series_id)
+            .unwrap_or_default();
+        require!(sender_position.held.0 >= amount, "insufficient options");
+        sender_position.held = U128(sender_position.held.0 - amount);
+        self.internal_set_option_position(sender_id, series_id, sender_position);
+
+        let mut receiver_position = self
+            .get_option_position(receiver_id, series_id)
+            .unwrap_or_default();
+        receiver_position.held = U128(receiver_position.held.0 + amount);
+        self.internal_set_option_position(receiver_id, series_id, receiver_position);
+        log!(
+            "Transfer options {} series: {} from {} to {}",
+            series_id,
+            amount,
+            sender_id,
+            receiver_id
+        );
+        if let Some(memo) = memo {
+            log!("Memo: {}", memo);
+        }
+    }
+
+    fn internal_mft_balance(&self, token_id: String, account_id: &AccountId) -> Balance {
+        let series_id = parse_series_id(&token_id);
+        self.get_option_position(account_id, series_id)
+            .map(|position| position.held.0)
+            .unwrap_or(0)
+    }
+
+    /// Returns the balance of the given account. If the account doesn't exist will return `"0"`.
+    pub fn mft_balance_of(&self, token_id: String, account_id: AccountId) -> U128 {
+        self.internal_mft_balance(token_id, &account_id).into()
+    }
+
+    pub fn mft_total_supply(&self, token_id: String) -> U128 {
+        let series_id = parse_series_id(&token_id);
+        let series = self.option_series.get(&series_id).expect("series not found");
+        U128(series.total_written.0 - series.total_exercised.0)
+    }
+
+    /// The storage of the receiver's position is paid by the sender.
+    #[payable]
+    pub fn mft_transfer(
+        &mut self,
+        token_id: String,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let sender_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(sender_id.clone());
+        let prev_storage = env::storage_usage();
+        self.internal_mft_transfer(token_id, &sender_id, &receiver_id, amount.0, memo);
+        self.verify_storage(&sender_id, prev_storage, None);
+    }
+
+    #[payable]
+    pub fn mft_transfer_call(
+        &mut self,
+        token_id: String,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let sender_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(sender_id.clone());
+        let prev_storage = env::storage_usage();
+        self.internal_mft_transfer(token_id.clone(), &sender_id, &receiver_id, amount.0, memo);
+        self.verify_storage(&sender_id, prev_storage, None);
+        ext_share_token_receiver::mft_on_transfer(
+            token_id.clone(),
+            sender_id.clone(),
+            amount,
+            msg,
+            receiver_id.clone(),
+            0,
+            env::prepaid_gas() - GAS_FOR_MFT_TRANSFER_CALL,
+        )
+        .then(ext_self::mft_resolve_transfer(
+            token_id,
+            sender_id,
+            receiver_id,
+            amount,
+            env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+        .into()
+    }
+
+    /// Returns how much was refunded back to the sender.
+    #[private]
+    pub fn mft_resolve_transfer(
+        &mut self,
+        token_id: String,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128 {
+        let unused_amount = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                if let Ok(unused_amount) = near_sdk::serde_json::from_slice::<U128>(&value) {
+                    std::cmp::min(amount.0, unused_amount.0)
+                } else {
+                    amount.0
+                }
+            }
+            PromiseResult::Failed => amount.0,
+        };
+        if unused_amount > 0 {
+            let receiver_balance = self.internal_mft_balance(token_id.clone(), &receiver_id);
+            if receiver_balance > 0 {
+                let refund_amount = std::cmp::min(receiver_balance, unused_amount);
+                self.internal_mft_transfer(token_id, &receiver_id, &sender_id, refund_amount, None);
+            }
+        }
+        U128(unused_amount)
+    }
+
+    pub fn mft_metadata(&self, token_id: String) -> FungibleTokenMetadata {
+        let series_id = parse_series_id(&token_id);
+        let series = self.option_series.get(&series_id).expect("series not found");
+        let option_type = match series.option_type {
+            OptionType::Call => "call",
+            OptionType::Put => "put",
+        };
+        FungibleTokenMetadata {
+            spec: "mft-1.0.0".to_string(),
+            name: format!("noption-{}-{}", option_type, series_id),
+            symbol: format!("NOPTION-{}-{}", option_type.to_uppercase(), series_id),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: series.underlying_decimals,
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use crate::*;
+use near_sdk::collections::LookupMap;
+use options::{OptionType, SettlementMode};
+
+//series an amm has written and not collected yet, each one is valued on every liquidity change
+const MAX_OPEN_SERIES_PER_AMM: usize = 16;
+
+//a pool of collateral that writes the options of its underlying, strike token and type to buyers
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OptionAmm {
+    pub amm_id: u32,
+    pub underlying_token_id: AssetId,
+    pub strike_token_id: AssetId,
+    pub option_type: OptionType,
+    pub implied_volatility: u64, //per VOLATILITY_DIVISOR
+    pub skew: u64, //extra volatility per unit of |ln(spot / strike)|
+    pub free_liquidity: Balance,
+    pub locked_collateral: Balance,
+    pub total_premiums: Balance,
+    pub total_shares: Balance,
+    pub shares: LookupMap<AccountId, Balance>,
+    pub written: LookupMap<u64, (Balance, Balance)>, //series id to options written and their collateral
+    pub open_series: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OptionAmmInfo {
+    pub amm_id: u32,
+    pub underlying_token_id: AssetId,
+    pub strike_token_id: AssetId,
+    pub option_type: OptionType,
+    pub implied_volatility: u64,
+    pub skew: u64,
+    pub free_liquidity: U128,
+    pub locked_collateral: U128,
+    pub total_premiums: U128,
+    pub total_shares: U128,
+    pub open_series: Vec<u64>,
+}
+
+impl OptionAmm {
+    pub fn collateral_token_id(&self) -> &AssetId {
+        match self.option_type {
+            OptionType::Call => &self.underlying_token_id,
+            OptionType::Put => &self.strike_token_id,
+        }
+    }
+
+    pub fn get_shares(&self, account_id: &AccountId) -> Balance {
+        self.shares.get(account_id).unwrap_or(0)
+    }
+
+    pub fn to_info(&self) -> OptionAmmInfo {
+        OptionAmmInfo {
+            amm_id: self.amm_id,
+            underlying_token_id: self.underlying_token_id.clone(),
+            strike_token_id: self.strike_token_id.clone(),
+            option_type: self.option_type,
+            implied_volatility: self.implied_volatility,
+            skew: self.skew,
+            free_liquidity: U128(self.free_liquidity),
+            locked_collateral: U128(self.locked_collateral),
+            total_premiums: U128(self.total_premiums),
+            total_shares: U128(self.total_shares),
+            open_series: self.open_series.clone(),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn create_option_amm(
+        &mut self,
+        underlying_token_id: AssetId,
+        strike_token_id: AssetId,
+        option_type: OptionType,
+        implied_volatility: u64,
+        skew: u64,
+    ) -> u32 {
+        self.assert_governance();
+        self.abort_if_unsupported_token(underlying_token_id.clone());
+        self.abort_if_unsupported_token(strike_token_id.clone());
+        require!(implied_volatility > 0, "implied_volatility > 0");
+        let prev_storage = env::storage_usage();
+        let amm_id = self.option_amms.len() as u32;
+        self.option_amms.push(OptionAmm {
+            amm_id,
+            underlying_token_id,
+            strike_token_id,
+            option_type,
+            implied_volatility,
+            skew,
+            free_liquidity: 0,
+            locked_collateral: 0,
+            total_premiums: 0,
+            total_shares: 0,
+            shares: LookupMap::new(StorageKey::OptionAmmShares { amm_id }),
+            written: LookupMap::new(StorageKey::OptionAmmWritten { amm_id }),
+            open_series: vec![],
+        });
+        let storage_cost = self.storage_cost(prev_storage);
+        let refund = env::attached_deposit().checked_sub(storage_cost).expect(
+            format!(
+                "ERR_STORAGE_DEPOSIT need {}, attatched {}",
+                storage_cost,
+                env::attached_deposit()
+            )
+            .as_str(),
+        );
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        amm_id
+    }
+
+    pub fn set_option_amm_volatility(&mut self, amm_id: u32, implied_volatility: u64, skew: u64) {
+        self.assert_governance();
+        require!(implied_volatility > 0, "implied_volatility > 0");
+        let amm = self.get_option_amm_mut(amm_id);
+        amm.implied_volatility = implied_volatility;
+        amm.skew = skew;
+    }
+
+    /// Burns shares for their part of the pool value, paid from the free liquidity to the option balance.
+    #[payable]
+    pub fn remove_option_amm_liquidity(&mut self, amm_id: u32, shares: U128) -> U128 {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        let value = self.compute_option_amm_value(amm_id);
+        let prev_storage = env::storage_usage();
+        let amm = self.get_option_amm_mut(amm_id);
+        let account_shares = amm.get_shares(&account_id);
+        require!(shares.0 > 0 && shares.0 <= account_shares, "insufficient shares");
+        let amount = (U256::from(value) * U256::from(shares.0) / U256::from(amm.total_shares)).as_u128();
+        require!(amount <= amm.free_liquidity, "insufficient free liquidity");
+        amm.free_liquidity -= amount;
+        amm.total_shares -= shares.0;
+        if account_shares == shares.0 {
+            amm.shares.remove(&account_id);
+        } else {
+            amm.shares.insert(&account_id, &(account_shares - shares.0));
+        }
+        let collateral_token_id = amm.collateral_token_id().clone();
+        self.internal_add_option_balance(&account_id, &collateral_token_id, amount);
+        self.verify_storage(&account_id, prev_storage, None);
+        U128(amount)
+    }
+
+    /// Returns the collateral of a settled series that is not owed to holders to the pool liquidity.
+    pub fn collect_option_amm_series(&mut self, amm_id: u32, series_id: u64) -> U128 {
+        self.abort_if_pause();
+        let amm = self.get_option_amm_mut(amm_id);
+        let (written, collateral) = amm.written.remove(&series_id).expect("no written options");
+        amm.open_series.retain(|id| *id != series_id);
+        let (released, _) = self.internal_release_collateral(series_id, written, collateral);
+        let amm = self.get_option_amm_mut(amm_id);
+        amm.locked_collateral -= collateral;
+        amm.free_liquidity += released;
+        U128(released)
+    }
+}
+
+impl Contract {
+    fn get_option_amm_mut(&mut self, amm_id: u32) -> &mut OptionAmm {
+        self.option_amms
+            .get_mut(amm_id as usize)
+            .expect("amm_id out of range")
+    }
+
+    fn internal_get_option_amm(&self, amm_id: u32) -> &OptionAmm {
+        self.option_amms.get(amm_id as usize).expect("amm_id out of range")
+    }
+
+    //liquidity plus locked collateral minus the value of the written options at the amm volatility
+    fn compute_option_amm_value(&self, amm_id: u32) -> Balance {
+        let amm = self.internal_get_option_amm(amm_id);
+        let liability: Balance = amm
+            .open_series
+            .iter()
+            .map(|series_id| {
+                let series = self.option_series.get(series_id).expect("series not found");
+                let (written, _) = amm.written.get(series_id).unwrap();
+                self.compute_series_value(&series, written, amm.implied_volatility, amm.skew)
+            })
+            .sum();
+        (amm.free_liquidity + amm.locked_collateral).saturating_sub(liability)
+    }
+
+    //premium of `amount` options in the collateral token, rounded up
+    pub(crate) fn compute_option_premium(&self, amm_id: u32, series_id: u64, amount: Balance) -> Balance {
+        let amm = self.internal_get_option_amm(amm_id);
+        let series = self.option_series.get(&series_id).expect("series not found");
+        require!(
+            series.underlying_token_id == amm.underlying_token_id
+                && series.strike_token_id == amm.strike_token_id
+                && series.option_type == amm.option_type
+                && series.settlement_mode == SettlementMode::Cash,
+            "series not traded by this amm"
+        );
+        self.compute_series_premium(&series, amount, amm.implied_volatility, amm.skew)
+    }
+
+    pub(crate) fn internal_add_option_amm_liquidity(
+        &mut self,
+        account_id: &AccountId,
+        token_in: &AssetId,
+        amount: Balance,
+        amm_id: u32,
+    ) {
+        let value = self.compute_option_amm_value(amm_id);
+        let prev_storage = env::storage_usage();
+        let amm = self.get_option_amm_mut(amm_id);
+        require!(amm.collateral_token_id() == token_in, "invalid collateral token");
+        let shares = if amm.total_shares == 0 {
+            amount
+        } else {
+            require!(value > 0, "amm has no value");
+            (U256::from(amount) * U256::from(amm.total_shares) / U256::from(value)).as_u128()
+        };
+        require!(shares > 0, "amount too small");
+        amm.free_liquidity += amount;
+        amm.total_shares += shares;
+        let account_shares = amm.get_shares(account_id);
+        amm.shares.insert(account_id, &(account_shares + shares));
+        self.verify_storage(account_id, prev_storage, None);
+    }
+
+    //the pool writes the options with its free liquidity and the buyer holds them,
+    //the transferred amount is the max premium and the rest is refunded
+    pub(crate) fn internal_buy_option(
+        &mut self,
+        buyer_id: &AccountId,
+        token_in: &AssetId,
+        max_premium: Balance,
+        amm_id: u32,
+        series_id: u64,
+        amount: Balance,
+    ) -> Balance {
+        require!(amount > 0, "amount > 0");
+        let premium = self.compute_option_premium(amm_id, series_id, amount);
+        require!(premium <= max_premium, "premium above the transferred amount");
+        let series = self.option_series.get(&series_id).expect("series not found");
+        require!(series.collateral_token_id() == token_in, "invalid premium token");
+        let collateral = series.compute_collateral(amount);
+
+        let prev_storage = env::storage_usage();
+        let amm = self.get_option_amm_mut(amm_id);
+        require!(collateral <= amm.free_liquidity + premium, "insufficient amm liquidity");
+        amm.free_liquidity = amm.free_liquidity + premium - collateral;
+        amm.locked_collateral += collateral;
+        amm.total_premiums += premium;
+        let (written, locked) = match amm.written.get(&series_id) {
+            Some(written) => written,
+            None => {
+                require!(
+                    amm.open_series.len() < MAX_OPEN_SERIES_PER_AMM,
+                    "too many open series, collect settled ones first"
+                );
+                amm.open_series.push(series_id);
+                (0, 0)
+            }
+        };
+        amm.written.insert(&series_id, &(written + amount, locked + collateral));
+
+        self.internal_mint_options(series_id, buyer_id, amount, collateral);
+        self.verify_storage(buyer_id, prev_storage, None);
+        max_premium - premium
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_option_amm(&self, amm_id: u32) -> OptionAmmInfo {
+        self.internal_get_option_amm(amm_id).to_info()
+    }
+
+    pub fn get_option_amms(&self, from_index: Option<usize>, limit: Option<usize>) -> Vec<OptionAmmInfo> {
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        let start_index = from_index.unwrap_or(0);
+        self.option_amms
+            .iter()
+            .skip(start_index)
+            .take(limit)
+            .map(|amm| amm.to_info())
+            .collect::<Vec<_>>()
+    }
+
+    pub fn get_option_amm_shares(&self, amm_id: u32, account_id: AccountId) -> U128 {
+        U128(self.internal_get_option_amm(amm_id).get_shares(&account_id))
+    }
+
+    pub fn get_option_amm_value(&self, amm_id: u32) -> U128 {
+        U128(self.compute_option_amm_value(amm_id))
+    }
+
+    pub fn quote_option_premium(&self, amm_id: u32, series_id: u64, amount: U128) -> U128 {
+        U128(self.compute_option_premium(amm_id, series_id, amount.0))
+    }
+}
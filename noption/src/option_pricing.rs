@@ -0,0 +1,139 @@
+use crate::*;
+use options::OptionType;
+
+//fixed point math with 18 decimals, enough for the bounded inputs of the pricing below
+const ONE: i128 = 1_000_000_000_000_000_000;
+const LN_2: i128 = 693_147_180_559_945_309;
+const INV_SQRT_2PI: i128 = 398_942_280_401_432_678;
+//Abramowitz and Stegun 26.2.17, absolute error under 7.5e-8
+const CDF_P: i128 = 231_641_900_000_000_000;
+const CDF_B: [i128; 5] = [
+    319_381_530_000_000_000,
+    -356_563_782_000_000_000,
+    1_781_477_937_000_000_000,
+    -1_821_255_978_000_000_000,
+    1_330_274_429_000_000_000,
+];
+const MAX_D: i128 = 8 * ONE;
+const MAX_SPOT_STRIKE_RATIO: u128 = 100;
+pub const VOLATILITY_DIVISOR: u128 = 10000;
+
+fn mul(a: i128, b: i128) -> i128 {
+    a * b / ONE
+}
+
+fn div(a: i128, b: i128) -> i128 {
+    a * ONE / b
+}
+
+//only used with x <= 0, exp(x) = exp(r) / 2^n with r in (-ln2, 0]
+fn exp_neg(x: i128) -> i128 {
+    require!(x <= 0, "exp_neg of a positive number");
+    let n = -x / LN_2;
+    if n >= 60 {
+        return 0;
+    }
+    let r = x + n * LN_2;
+    let mut term = ONE;
+    let mut sum = ONE;
+    for i in 1..20 {
+        term = mul(term, r) / i;
+        sum += term;
+    }
+    sum >> n
+}
+
+//x > 0, ln(x) = n * ln2 + ln(m) with m in [1, 2) and ln(m) = 2 * atanh((m - 1) / (m + 1))
+fn ln(x: i128) -> i128 {
+    require!(x > 0, "ln of a non positive number");
+    let mut m = x;
+    let mut n: i128 = 0;
+    while m >= 2 * ONE {
+        m /= 2;
+        n += 1;
+    }
+    while m < ONE {
+        m *= 2;
+        n -= 1;
+    }
+    let z = div(m - ONE, m + ONE);
+    let z2 = mul(z, z);
+    let mut term = z;
+    let mut sum = 0;
+    for i in 0..30 {
+        sum += term / (2 * i + 1);
+        term = mul(term, z2);
+    }
+    n * LN_2 + 2 * sum
+}
+
+fn sqrt(x: i128) -> i128 {
+    (U256::from(x as u128) * U256::from(ONE as u128))
+        .integer_sqrt()
+        .as_u128() as i128
+}
+
+fn norm_cdf(x: i128) -> i128 {
+    if x < 0 {
+        return ONE - norm_cdf(-x);
+    }
+    if x >= MAX_D {
+        return ONE;
+    }
+    let t = div(ONE, ONE + mul(CDF_P, x));
+    let mut poly = 0;
+    for b in CDF_B.iter().rev() {
+        poly = mul(poly + b, t);
+    }
+    let pdf = mul(exp_neg(-mul(x, x) / 2), INV_SQRT_2PI);
+    ONE - mul(pdf, poly)
+}
+
+fn compute_intrinsic_value(spot: Balance, strike: Balance, option_type: OptionType) -> Balance {
+    match option_type {
+        OptionType::Call => spot.saturating_sub(strike),
+        OptionType::Put => strike.saturating_sub(spot),
+    }
+}
+
+/// Black-Scholes value of one whole underlying token in strike token units, with a zero interest rate.
+/// The volatility grows by `skew` per unit of |ln(spot / strike)| away from the money.
+pub fn compute_option_value(
+    spot: Balance,
+    strike: Balance,
+    time_to_expiry_sec: u64,
+    implied_volatility: u64,
+    skew: u64,
+    option_type: OptionType,
+) -> Balance {
+    let intrinsic_value = compute_intrinsic_value(spot, strike, option_type);
+    if time_to_expiry_sec == 0 || spot == 0 {
+        return intrinsic_value;
+    }
+    let ratio = (U256::from(spot) * U256::from(ONE as u128) / U256::from(strike))
+        .as_u128()
+        .max(ONE as u128 / MAX_SPOT_STRIKE_RATIO)
+        .min(ONE as u128 * MAX_SPOT_STRIKE_RATIO) as i128;
+    let ln_ratio = ln(ratio);
+    let volatility = (implied_volatility as i128) * ONE / VOLATILITY_DIVISOR as i128
+        + mul((skew as i128) * ONE / VOLATILITY_DIVISOR as i128, ln_ratio.abs());
+    let time = (time_to_expiry_sec as i128) * ONE / SECONDS_PER_YEAR as i128;
+    let vol_sqrt_time = mul(volatility, sqrt(time));
+    if vol_sqrt_time == 0 {
+        return intrinsic_value;
+    }
+    let d1 = div(ln_ratio + mul(vol_sqrt_time, vol_sqrt_time) / 2, vol_sqrt_time).max(-MAX_D).min(MAX_D);
+    let d2 = d1 - vol_sqrt_time;
+    let weighted = |amount: Balance, probability: i128| {
+        (U256::from(amount) * U256::from(probability as u128) / U256::from(ONE as u128)).as_u128()
+    };
+    let value = match option_type {
+        OptionType::Call => {
+            weighted(spot, norm_cdf(d1)).saturating_sub(weighted(strike, norm_cdf(d2)))
+        }
+        OptionType::Put => {
+            weighted(strike, norm_cdf(-d2)).saturating_sub(weighted(spot, norm_cdf(-d1)))
+        }
+    };
+    value.max(intrinsic_value)
+}
@@ -0,0 +1,433 @@
+use crate::*;
+use near_sdk::collections::LookupMap;
+use options::{OptionSeriesTerms, OptionType, SettlementMode, DEFAULT_EXERCISE_WINDOW_SEC};
+
+const STRIKE_OFFSET_DIVISOR: u128 = 10000;
+
+//a covered call (call) or cash secured put (put) strategy, each epoch it writes one out of the money
+//series with its assets and sells it, deposits and withdrawals wait for the epoch boundary
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OptionVault {
+    pub vault_id: u32,
+    pub underlying_token_id: AssetId,
+    pub strike_token_id: AssetId,
+    pub option_type: OptionType,
+    pub strike_offset: u64, //distance of the strike from spot per STRIKE_OFFSET_DIVISOR
+    pub epoch_duration_sec: u64,
+    pub implied_volatility: u64,
+    pub epoch: u64,
+    pub current_series_id: Option<u64>,
+    pub free_assets: Balance,
+    pub written: Balance,
+    pub locked_collateral: Balance,
+    pub total_premiums: Balance,
+    pub total_shares: Balance,
+    pub pending_deposits: Balance,  //not at risk until the next epoch starts
+    pub queued_shares: Balance,     //burnt when the next epoch starts
+    pub withdrawable_assets: Balance, //set aside for processed withdrawals not claimed yet
+    pub accounts: LookupMap<AccountId, VaultAccount>,
+    pub epoch_share_prices: LookupMap<u64, (Balance, Balance)>, //assets and shares when the epoch ended
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VaultAccount {
+    pub shares: U128,
+    pub pending_deposit: U128,
+    pub pending_deposit_epoch: u64,
+    pub queued_shares: U128,
+    pub queued_epoch: u64,
+    pub withdrawable: U128,
+}
+
+impl Default for VaultAccount {
+    fn default() -> VaultAccount {
+        VaultAccount {
+            shares: U128(0),
+            pending_deposit: U128(0),
+            pending_deposit_epoch: 0,
+            queued_shares: U128(0),
+            queued_epoch: 0,
+            withdrawable: U128(0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OptionVaultInfo {
+    pub vault_id: u32,
+    pub underlying_token_id: AssetId,
+    pub strike_token_id: AssetId,
+    pub option_type: OptionType,
+    pub strike_offset: u64,
+    pub epoch_duration_sec: u64,
+    pub implied_volatility: u64,
+    pub epoch: u64,
+    pub current_series_id: Option<u64>,
+    pub free_assets: U128,
+    pub written: U128,
+    pub locked_collateral: U128,
+    pub total_premiums: U128,
+    pub total_shares: U128,
+    pub pending_deposits: U128,
+    pub queued_shares: U128,
+}
+
+//shares minted for a deposit at the end of an epoch with `assets` backing `shares`,
+//a vault that lost all its assets prices them as if one unit was left, so the worthless
+//shares only keep a negligible part of the new deposits
+pub fn compute_deposit_shares(amount: Balance, assets: Balance, shares: Balance) -> Balance {
+    if shares == 0 {
+        return amount;
+    }
+    (U256::from(amount) * U256::from(shares) / U256::from(std::cmp::max(assets, 1))).as_u128()
+}
+
+impl OptionVault {
+    pub fn asset_token_id(&self) -> &AssetId {
+        match self.option_type {
+            OptionType::Call => &self.underlying_token_id,
+            OptionType::Put => &self.strike_token_id,
+        }
+    }
+
+    //converts what the account queued in ended epochs at the price of that epoch
+    pub fn sync_account(&self, account: &mut VaultAccount) {
+        if account.pending_deposit.0 > 0 && account.pending_deposit_epoch < self.epoch {
+            let (assets, shares) = self
+                .epoch_share_prices
+                .get(&account.pending_deposit_epoch)
+                .expect("epoch price not found");
+            let new_shares = compute_deposit_shares(account.pending_deposit.0, assets, shares);
+            account.shares = U128(account.shares.0 + new_shares);
+            account.pending_deposit = U128(0);
+        }
+        if account.queued_shares.0 > 0 && account.queued_epoch < self.epoch {
+            let (assets, shares) = self
+                .epoch_share_prices
+                .get(&account.queued_epoch)
+                .expect("epoch price not found");
+            let amount = (U256::from(account.queued_shares.0) * U256::from(assets)
+                / U256::from(shares))
+            .as_u128();
+            account.withdrawable = U128(account.withdrawable.0 + amount);
+            account.queued_shares = U128(0);
+        }
+    }
+
+    pub fn get_account(&self, account_id: &AccountId) -> VaultAccount {
+        let mut account = self.accounts.get(account_id).unwrap_or_default();
+        self.sync_account(&mut account);
+        account
+    }
+
+    pub fn to_info(&self) -> OptionVaultInfo {
+        OptionVaultInfo {
+            vault_id: self.vault_id,
+            underlying_token_id: self.underlying_token_id.clone(),
+            strike_token_id: self.strike_token_id.clone(),
+            option_type: self.option_type,
+            strike_offset: self.strike_offset,
+            epoch_duration_sec: self.epoch_duration_sec,
+            implied_volatility: self.implied_volatility,
+            epoch: self.epoch,
+            current_series_id: self.current_series_id,
+            free_assets: U128(self.free_assets),
+            written: U128(self.written),
+            locked_collateral: U128(self.locked_collateral),
+            total_premiums: U128(self.total_premiums),
+            total_shares: U128(self.total_shares),
+            pending_deposits: U128(self.pending_deposits),
+            queued_shares: U128(self.queued_shares),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn create_option_vault(
+        &mut self,
+        underlying_token_id: AssetId,
+        strike_token_id: AssetId,
+        option_type: OptionType,
+        strike_offset: u64,
+        epoch_duration_sec: u64,
+        implied_volatility: u64,
+    ) -> u32 {
+        self.assert_governance();
+        self.abort_if_unsupported_token(underlying_token_id.clone());
+        self.abort_if_unsupported_token(strike_token_id.clone());
+        require!(implied_volatility > 0, "implied_volatility > 0");
+        require!(epoch_duration_sec > 0, "epoch_duration_sec > 0");
+        require!((strike_offset as u128) < STRIKE_OFFSET_DIVISOR, "strike_offset too high");
+        let prev_storage = env::storage_usage();
+        let vault_id = self.option_vaults.len() as u32;
+        self.option_vaults.push(OptionVault {
+            vault_id,
+            underlying_token_id,
+            strike_token_id,
+            option_type,
+            strike_offset,
+            epoch_duration_sec,
+            implied_volatility,
+            epoch: 0,
+            current_series_id: None,
+            free_assets: 0,
+            written: 0,
+            locked_collateral: 0,
+            total_premiums: 0,
+            total_shares: 0,
+            pending_deposits: 0,
+            queued_shares: 0,
+            withdrawable_assets: 0,
+            accounts: LookupMap::new(StorageKey::OptionVaultAccounts { vault_id }),
+            epoch_share_prices: LookupMap::new(StorageKey::OptionVaultEpochs { vault_id }),
+        });
+        let storage_cost = self.storage_cost(prev_storage);
+        let refund = env::attached_deposit().checked_sub(storage_cost).expect(
+            format!(
+                "ERR_STORAGE_DEPOSIT need {}, attatched {}",
+                storage_cost,
+                env::attached_deposit()
+            )
+            .as_str(),
+        );
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        vault_id
+    }
+
+    pub fn set_option_vault_volatility(&mut self, vault_id: u32, implied_volatility: u64) {
+        self.assert_governance();
+        require!(implied_volatility > 0, "implied_volatility > 0");
+        self.get_option_vault_mut(vault_id).implied_volatility = implied_volatility;
+    }
+
+    /// Ends the current epoch once its series is settled and starts the next one, callable by any keeper.
+    /// Queued withdrawals and pending deposits are priced at the vault assets after settlement,
+    /// then the vault writes a new series `strike_offset` out of the money.
+    #[payable]
+    pub fn roll_option_vault(&mut self, vault_id: u32) -> u64 {
+        self.abort_if_pause();
+        let prev_storage = env::storage_usage();
+        let vault = self.internal_get_option_vault(vault_id);
+        if let Some(series_id) = vault.current_series_id {
+            let (written, collateral) = (vault.written, vault.locked_collateral);
+            let released = if written > 0 {
+                self.internal_release_collateral(series_id, written, collateral).0
+            } else {
+                let series = self.option_series.get(&series_id).expect("series not found");
+                require!(series.settlement_price.is_some(), "series not settled");
+                0
+            };
+            let vault = self.get_option_vault_mut(vault_id);
+            vault.free_assets += released;
+            vault.written = 0;
+            vault.locked_collateral = 0;
+        }
+
+        let spot = {
+            let vault = self.internal_get_option_vault(vault_id);
+            self.get_option_spot_price(&vault.underlying_token_id, &vault.strike_token_id)
+        };
+        let vault = self.get_option_vault_mut(vault_id);
+        let (assets, shares) = (vault.free_assets, vault.total_shares);
+        vault.epoch_share_prices.insert(&vault.epoch, &(assets, shares));
+        if vault.queued_shares > 0 {
+            let withdrawn = (U256::from(vault.queued_shares) * U256::from(assets)
+                / U256::from(shares))
+            .as_u128();
+            vault.free_assets -= withdrawn;
+            vault.withdrawable_assets += withdrawn;
+            vault.total_shares -= vault.queued_shares;
+            vault.queued_shares = 0;
+        }
+        if vault.pending_deposits > 0 {
+            let new_shares = compute_deposit_shares(vault.pending_deposits, assets, shares);
+            vault.free_assets += vault.pending_deposits;
+            vault.total_shares += new_shares;
+            vault.pending_deposits = 0;
+        }
+        vault.epoch += 1;
+
+        let offset = match vault.option_type {
+            OptionType::Call => STRIKE_OFFSET_DIVISOR + vault.strike_offset as u128,
+            OptionType::Put => STRIKE_OFFSET_DIVISOR - vault.strike_offset as u128,
+        };
+        let strike_price = (U256::from(spot) * U256::from(offset) / U256::from(STRIKE_OFFSET_DIVISOR)).as_u128();
+        require!(strike_price > 0, "strike_price > 0");
+        let expiry_timestamp_sec = env::block_timestamp_ms() / 1000 + vault.epoch_duration_sec;
+        let (underlying_token_id, strike_token_id, option_type, epoch) = (
+            vault.underlying_token_id.clone(),
+            vault.strike_token_id.clone(),
+            vault.option_type,
+            vault.epoch,
+        );
+        let terms = OptionSeriesTerms {
+            underlying_token_id,
+            strike_token_id,
+            strike_price,
+            expiry_timestamp_sec,
+            option_type,
+            settlement_mode: SettlementMode::Cash,
+            exercise_window_sec: DEFAULT_EXERCISE_WINDOW_SEC,
+        };
+        //the vault writes into a series with the same terms if there is one
+        let series_id = match self.option_series_by_terms.get(&terms) {
+            Some(series_id) => series_id,
+            None => self.internal_create_option_series(terms),
+        };
+        self.get_option_vault_mut(vault_id).current_series_id = Some(series_id);
+        log!("Vault {} epoch {} writes series {}", vault_id, epoch, series_id);
+
+        let storage_cost = self.storage_cost(prev_storage);
+        let refund = env::attached_deposit().checked_sub(storage_cost).expect(
+            format!(
+                "ERR_STORAGE_DEPOSIT need {}, attatched {}",
+                storage_cost,
+                env::attached_deposit()
+            )
+            .as_str(),
+        );
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        series_id
+    }
+
+    /// Queues shares for withdrawal at the end of the current epoch.
+    #[payable]
+    pub fn queue_option_vault_withdrawal(&mut self, vault_id: u32, shares: U128) {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        let vault = self.get_option_vault_mut(vault_id);
+        let mut account = vault.get_account(&account_id);
+        require!(shares.0 > 0 && shares.0 <= account.shares.0, "insufficient shares");
+        account.shares = U128(account.shares.0 - shares.0);
+        account.queued_shares = U128(account.queued_shares.0 + shares.0);
+        account.queued_epoch = vault.epoch;
+        vault.queued_shares += shares.0;
+        vault.accounts.insert(&account_id, &account);
+    }
+
+    /// Moves processed withdrawals and a deposit still pending in the current epoch to the option balance.
+    #[payable]
+    pub fn claim_option_vault_withdrawal(&mut self, vault_id: u32) -> U128 {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        let prev_storage = env::storage_usage();
+        let vault = self.get_option_vault_mut(vault_id);
+        let mut account = vault.get_account(&account_id);
+        let mut amount = account.withdrawable.0;
+        vault.withdrawable_assets -= amount;
+        if account.pending_deposit.0 > 0 {
+            amount += account.pending_deposit.0;
+            vault.pending_deposits -= account.pending_deposit.0;
+            account.pending_deposit = U128(0);
+        }
+        require!(amount > 0, "nothing to claim");
+        account.withdrawable = U128(0);
+        vault.accounts.insert(&account_id, &account);
+        let asset_token_id = vault.asset_token_id().clone();
+        self.internal_add_option_balance(&account_id, &asset_token_id, amount);
+        self.verify_storage(&account_id, prev_storage, None);
+        U128(amount)
+    }
+}
+
+impl Contract {
+    fn get_option_vault_mut(&mut self, vault_id: u32) -> &mut OptionVault {
+        self.option_vaults
+            .get_mut(vault_id as usize)
+            .expect("vault_id out of range")
+    }
+
+    fn internal_get_option_vault(&self, vault_id: u32) -> &OptionVault {
+        self.option_vaults.get(vault_id as usize).expect("vault_id out of range")
+    }
+
+    pub(crate) fn internal_deposit_to_option_vault(
+        &mut self,
+        account_id: &AccountId,
+        token_in: &AssetId,
+        amount: Balance,
+        vault_id: u32,
+    ) {
+        let prev_storage = env::storage_usage();
+        let vault = self.get_option_vault_mut(vault_id);
+        require!(vault.asset_token_id() == token_in, "invalid vault asset");
+        let mut account = vault.get_account(account_id);
+        account.pending_deposit = U128(account.pending_deposit.0 + amount);
+        account.pending_deposit_epoch = vault.epoch;
+        vault.pending_deposits += amount;
+        vault.accounts.insert(account_id, &account);
+        self.verify_storage(account_id, prev_storage, None);
+    }
+
+    //sells options of the current series written with the vault assets, returns the unused premium
+    pub(crate) fn internal_buy_vault_option(
+        &mut self,
+        buyer_id: &AccountId,
+        token_in: &AssetId,
+        max_premium: Balance,
+        vault_id: u32,
+        amount: Balance,
+    ) -> Balance {
+        require!(amount > 0, "amount > 0");
+        let vault = self.internal_get_option_vault(vault_id);
+        require!(vault.asset_token_id() == token_in, "invalid premium token");
+        let series_id = vault.current_series_id.expect("vault has no series");
+        let series = self.option_series.get(&series_id).expect("series not found");
+        let premium = self.compute_series_premium(&series, amount, vault.implied_volatility, 0);
+        require!(premium <= max_premium, "premium above the transferred amount");
+        let collateral = series.compute_collateral(amount);
+
+        let prev_storage = env::storage_usage();
+        let vault = self.get_option_vault_mut(vault_id);
+        require!(collateral <= vault.free_assets + premium, "insufficient vault assets");
+        vault.free_assets = vault.free_assets + premium - collateral;
+        vault.written += amount;
+        vault.locked_collateral += collateral;
+        vault.total_premiums += premium;
+        self.internal_mint_options(series_id, buyer_id, amount, collateral);
+        self.verify_storage(buyer_id, prev_storage, None);
+        max_premium - premium
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_option_vault(&self, vault_id: u32) -> OptionVaultInfo {
+        self.internal_get_option_vault(vault_id).to_info()
+    }
+
+    pub fn get_option_vaults(&self, from_index: Option<usize>, limit: Option<usize>) -> Vec<OptionVaultInfo> {
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        let start_index = from_index.unwrap_or(0);
+        self.option_vaults
+            .iter()
+            .skip(start_index)
+            .take(limit)
+            .map(|vault| vault.to_info())
+            .collect::<Vec<_>>()
+    }
+
+    pub fn get_option_vault_account(&self, vault_id: u32, account_id: AccountId) -> VaultAccount {
+        self.internal_get_option_vault(vault_id).get_account(&account_id)
+    }
+
+    pub fn quote_vault_option_premium(&self, vault_id: u32, amount: U128) -> U128 {
+        let vault = self.internal_get_option_vault(vault_id);
+        let series_id = vault.current_series_id.expect("vault has no series");
+        let series = self.option_series.get(&series_id).expect("series not found");
+        U128(self.compute_series_premium(&series, amount.0, vault.implied_volatility, 0))
+    }
+}
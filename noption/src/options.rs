@@ -0,0 +1,733 @@
+use crate::*;
+use near_sdk::{PromiseOrValue, PromiseResult};
+use std::collections::HashMap;
+use option_pricing::compute_option_value;
+use utils::{ext_ft_core, ext_self, GAS_FOR_FT_TRANSFER, GAS_FOR_RESOLVE_TRANSFER};
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum OptionType {
+    Call,
+    Put,
+}
+
+//cash series pay holders the in-the-money value in the collateral token, physical series let holders
+//trade the strike against the underlying during the exercise window
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SettlementMode {
+    Cash,
+    Physical,
+}
+
+pub const DEFAULT_EXERCISE_WINDOW_SEC: u64 = 86400;
+//after expiry, the oracle price settles a series, then governance does
+pub const SETTLEMENT_PRICE_WINDOW_SEC: u64 = 3600;
+
+//a european option series, amounts of options are counted in the smallest units of the underlying
+#[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OptionSeries {
+    pub series_id: u64,
+    pub underlying_token_id: AssetId,
+    pub strike_token_id: AssetId,
+    pub underlying_decimals: u8,
+    pub strike_price: U128, //strike token amount for one whole underlying token
+    pub expiry_timestamp_sec: u64,
+    pub option_type: OptionType,
+    pub settlement_mode: SettlementMode,
+    pub exercise_window_sec: u64, //after expiry, in-the-money options left are auto exercised in cash
+    pub total_written: U128,
+    pub total_exercised: U128,
+    pub total_physically_exercised: U128,
+    pub total_exercise_payments: U128, //strike or underlying paid by physical exercises, owed to writers
+    pub total_collateral: U128,
+    pub settlement_price: Option<U128>, //oracle price at expiry in the same unit as strike_price
+}
+
+//series are unique on their terms
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OptionSeriesTerms {
+    pub underlying_token_id: AssetId,
+    pub strike_token_id: AssetId,
+    pub strike_price: Balance,
+    pub expiry_timestamp_sec: u64,
+    pub option_type: OptionType,
+    pub settlement_mode: SettlementMode,
+    pub exercise_window_sec: u64,
+}
+
+//written options lock collateral, held options can be exercised once the series is settled
+#[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OptionPosition {
+    pub held: U128,
+    pub written: U128,
+    pub collateral: U128,
+}
+
+impl Default for OptionPosition {
+    fn default() -> OptionPosition {
+        OptionPosition {
+            held: U128(0),
+            written: U128(0),
+            collateral: U128(0),
+        }
+    }
+}
+
+impl OptionSeries {
+    pub fn collateral_token_id(&self) -> &AssetId {
+        match self.option_type {
+            OptionType::Call => &self.underlying_token_id,
+            OptionType::Put => &self.strike_token_id,
+        }
+    }
+
+    //token holders pay on physical exercise, the strike token for calls and the underlying for puts
+    pub fn exercise_payment_token_id(&self) -> &AssetId {
+        match self.option_type {
+            OptionType::Call => &self.strike_token_id,
+            OptionType::Put => &self.underlying_token_id,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        env::block_timestamp_ms() / 1000 >= self.expiry_timestamp_sec
+    }
+
+    pub fn is_exercise_window_over(&self) -> bool {
+        env::block_timestamp_ms() / 1000 >= self.expiry_timestamp_sec + self.exercise_window_sec
+    }
+
+    //strike token amount for `amount` options
+    pub fn compute_strike_amount(&self, amount: Balance, round_up: bool) -> Balance {
+        let unit = U256::from(10u128.pow(self.underlying_decimals as u32));
+        let value = U256::from(amount) * U256::from(self.strike_price.0);
+        if round_up {
+            ((value + unit - 1) / unit).as_u128()
+        } else {
+            (value / unit).as_u128()
+        }
+    }
+
+    //collateral locked for writing `amount` options, rounded up
+    pub fn compute_collateral(&self, amount: Balance) -> Balance {
+        match self.option_type {
+            OptionType::Call => amount,
+            OptionType::Put => self.compute_strike_amount(amount, true),
+        }
+    }
+
+    //options that can be written with `collateral`
+    pub fn compute_writable_amount(&self, collateral: Balance) -> Balance {
+        match self.option_type {
+            OptionType::Call => collateral,
+            OptionType::Put => (U256::from(collateral)
+                * U256::from(10u128.pow(self.underlying_decimals as u32))
+                / U256::from(self.strike_price.0))
+            .as_u128(),
+        }
+    }
+
+    //in-the-money value of `amount` options in the collateral token, holders get it rounded down
+    //and writers are charged it rounded up
+    pub fn compute_payoff(&self, amount: Balance, round_up: bool) -> Balance {
+        let settlement_price = self.settlement_price.expect("series not settled").0;
+        self.compute_payoff_at(settlement_price, amount, round_up)
+    }
+
+    //same as compute_payoff for a given price of the underlying, used to value open positions
+    pub fn compute_payoff_at(&self, settlement_price: Balance, amount: Balance, round_up: bool) -> Balance {
+        let strike_price = self.strike_price.0;
+        let (numerator, denominator) = match self.option_type {
+            OptionType::Call => {
+                if settlement_price <= strike_price {
+                    return 0;
+                }
+                (
+                    U256::from(amount) * U256::from(settlement_price - strike_price),
+                    U256::from(settlement_price),
+                )
+            }
+            OptionType::Put => {
+                if strike_price <= settlement_price {
+                    return 0;
+                }
+                (
+                    U256::from(amount) * U256::from(strike_price - settlement_price),
+                    U256::from(10u128.pow(self.underlying_decimals as u32)),
+                )
+            }
+        };
+        if round_up {
+            ((numerator + denominator - 1) / denominator).as_u128()
+        } else {
+            (numerator / denominator).as_u128()
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Creates a series or returns the id of the existing one with the same terms.
+    #[payable]
+    pub fn create_option_series(
+        &mut self,
+        underlying_token_id: AssetId,
+        strike_token_id: AssetId,
+        strike_price: U128,
+        expiry_timestamp_sec: u64,
+        option_type: OptionType,
+        settlement_mode: Option<SettlementMode>,
+        exercise_window_sec: Option<u64>,
+    ) -> u64 {
+        self.abort_if_pause();
+        let settlement_mode = settlement_mode.unwrap_or(SettlementMode::Cash);
+        let exercise_window_sec = exercise_window_sec.unwrap_or(DEFAULT_EXERCISE_WINDOW_SEC);
+        self.abort_if_unsupported_token(underlying_token_id.clone());
+        self.abort_if_unsupported_token(strike_token_id.clone());
+        require!(underlying_token_id != strike_token_id, "underlying and strike tokens must differ");
+        require!(strike_price.0 > 0, "strike_price > 0");
+        require!(
+            expiry_timestamp_sec > env::block_timestamp_ms() / 1000,
+            "expiry must be in the future"
+        );
+        let terms = OptionSeriesTerms {
+            underlying_token_id,
+            strike_token_id,
+            strike_price: strike_price.0,
+            expiry_timestamp_sec,
+            option_type,
+            settlement_mode,
+            exercise_window_sec,
+        };
+        if let Some(series_id) = self.option_series_by_terms.get(&terms) {
+            if env::attached_deposit() > 0 {
+                Promise::new(env::predecessor_account_id()).transfer(env::attached_deposit());
+            }
+            return series_id;
+        }
+
+        let prev_storage = env::storage_usage();
+        let series_id = self.internal_create_option_series(terms);
+        let storage_cost = self.storage_cost(prev_storage);
+        let refund = env::attached_deposit().checked_sub(storage_cost).expect(
+            format!(
+                "ERR_STORAGE_DEPOSIT need {}, attatched {}",
+                storage_cost,
+                env::attached_deposit()
+            )
+            .as_str(),
+        );
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        series_id
+    }
+
+    /// Records the oracle price of an expired series, the price data must be reported within
+    /// SETTLEMENT_PRICE_WINDOW_SEC after expiry.
+    pub fn settle_option_series(&mut self, series_id: u64) -> U128 {
+        self.abort_if_pause();
+        let series = self.option_series.get(&series_id).expect("series not found");
+        require!(series.is_expired(), "series not expired");
+        require!(
+            self.price_data.timestamp() >= series.expiry_timestamp_sec * 10u64.pow(9),
+            "price data reported before expiry"
+        );
+        require!(
+            self.price_data.timestamp()
+                <= (series.expiry_timestamp_sec + SETTLEMENT_PRICE_WINDOW_SEC) * 10u64.pow(9),
+            "price data reported after the settlement window"
+        );
+        let settlement_price =
+            self.get_option_spot_price(&series.underlying_token_id, &series.strike_token_id);
+        self.internal_settle_option_series(series_id, settlement_price)
+    }
+
+    /// Governance settles a series the oracle price missed, once the settlement window is over.
+    pub fn settle_option_series_by_governance(&mut self, series_id: u64, settlement_price: U128) -> U128 {
+        self.assert_governance();
+        let series = self.option_series.get(&series_id).expect("series not found");
+        require!(
+            env::block_timestamp_ms() / 1000
+                > series.expiry_timestamp_sec + SETTLEMENT_PRICE_WINDOW_SEC,
+            "settlement window not over"
+        );
+        require!(settlement_price.0 > 0, "settlement price must be positive");
+        self.internal_settle_option_series(series_id, settlement_price.0)
+    }
+
+    /// Exercises held options of a settled series in cash, the payoff goes to the option balance.
+    /// Physical series settle in cash only once their exercise window is over.
+    #[payable]
+    pub fn exercise_option(&mut self, series_id: u64, amount: Option<U128>) -> U128 {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        let series = self.option_series.get(&series_id).expect("series not found");
+        require!(
+            series.settlement_mode == SettlementMode::Cash || series.is_exercise_window_over(),
+            "physical series are exercised with a payment during the exercise window"
+        );
+        let held = self.get_option_position_unwrap(&account_id, series_id).held.0;
+        let amount = amount.map(|v| v.0).unwrap_or(held);
+        let prev_storage = env::storage_usage();
+        let payoff = self.internal_exercise_option_in_cash(&account_id, series_id, amount);
+        self.verify_storage(&account_id, prev_storage, None);
+        U128(payoff)
+    }
+
+    /// After the exercise window, exercises the in-the-money options the given holders left.
+    /// The caller pays the storage of the holders option balances, the attached deposit is added to its storage.
+    #[payable]
+    pub fn auto_exercise_options(&mut self, series_id: u64, account_ids: Vec<AccountId>) -> U128 {
+        self.abort_if_pause();
+        let prev_storage = env::storage_usage();
+        let series = self.option_series.get(&series_id).expect("series not found");
+        require!(series.is_exercise_window_over(), "exercise window not over");
+        require!(series.settlement_price.is_some(), "series not settled");
+        let mut total_payoff = 0;
+        for account_id in account_ids {
+            let held = self
+                .get_option_position(&account_id, series_id)
+                .map(|position| position.held.0)
+                .unwrap_or(0);
+            if held == 0 || series.compute_payoff(held, false) == 0 {
+                continue;
+            }
+            total_payoff += self.internal_exercise_option_in_cash(&account_id, series_id, held);
+        }
+        self.verify_storage(
+            &env::predecessor_account_id(),
+            prev_storage,
+            Some(env::attached_deposit()),
+        );
+        U128(total_payoff)
+    }
+
+    /// Gives a writer back the collateral of a settled series that is not owed to holders,
+    /// with its share of the physical exercise payments once the exercise window is over.
+    #[payable]
+    pub fn reclaim_option_collateral(&mut self, series_id: u64) -> U128 {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        let prev_storage = env::storage_usage();
+        let mut position = self.get_option_position_unwrap(&account_id, series_id);
+        require!(position.written.0 > 0, "no written options");
+        let (reclaimed, payments) =
+            self.internal_release_collateral(series_id, position.written.0, position.collateral.0);
+        position.written = U128(0);
+        position.collateral = U128(0);
+        self.internal_set_option_position(&account_id, series_id, position);
+        let series = self.option_series.get(&series_id).expect("series not found");
+        self.internal_add_option_balance(&account_id, series.collateral_token_id(), reclaimed);
+        self.internal_add_option_balance(&account_id, series.exercise_payment_token_id(), payments);
+        self.verify_storage(&account_id, prev_storage, None);
+        U128(reclaimed)
+    }
+
+    /// Before expiry, a writer holding its own options can burn them to unlock collateral.
+    #[payable]
+    pub fn close_option_position(&mut self, series_id: u64, amount: U128) -> U128 {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        let mut series = self.option_series.get(&series_id).expect("series not found");
+        require!(!series.is_expired(), "series expired");
+        let prev_storage = env::storage_usage();
+        let mut position = self.get_option_position_unwrap(&account_id, series_id);
+        require!(
+            amount.0 > 0 && amount.0 <= position.held.0 && amount.0 <= position.written.0,
+            "insufficient options"
+        );
+        let released = (U256::from(position.collateral.0) * U256::from(amount.0)
+            / U256::from(position.written.0))
+        .as_u128();
+        position.held = U128(position.held.0 - amount.0);
+        position.written = U128(position.written.0 - amount.0);
+        position.collateral = U128(position.collateral.0 - released);
+        self.internal_set_option_position(&account_id, series_id, position);
+        log!("Burn {} options of series {} from {}", amount.0, series_id, account_id);
+
+        series.total_written = U128(series.total_written.0 - amount.0);
+        series.total_collateral = U128(series.total_collateral.0 - released);
+        self.option_series.insert(&series_id, &series);
+        self.internal_add_option_balance(&account_id, &series.collateral_token_id().clone(), released);
+        self.verify_storage(&account_id, prev_storage, None);
+        U128(released)
+    }
+
+    #[payable]
+    pub fn withdraw_option_balance(&mut self, token_id: AssetId, amount: Option<U128>) -> Promise {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        let mut balances = self.option_balances.get(&account_id).unwrap_or_default();
+        let balance = balances.get(&token_id).map(|v| v.0).unwrap_or(0);
+        let amount = amount.map(|v| v.0).unwrap_or(balance);
+        require!(amount > 0 && amount <= balance, "insufficient option balance");
+        balances.insert(token_id.clone(), U128(balance - amount));
+        self.option_balances.insert(&account_id, &balances);
+        self.internal_send_option_tokens(&token_id, &account_id, amount)
+    }
+
+    #[private]
+    pub fn callback_option_transfer(&mut self, token_id: AccountId, receiver_id: AccountId, amount: U128) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            "CALLBACK_OPTION_TRANSFER_INVALID"
+        );
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {}
+            PromiseResult::Failed => {
+                self.internal_add_option_balance(&receiver_id, &token_id, amount.0)
+            }
+        };
+    }
+}
+
+impl Contract {
+    //writes as many options as the transferred collateral covers and returns the unused collateral
+    pub(crate) fn internal_write_option(
+        &mut self,
+        writer_id: &AccountId,
+        token_in: &AssetId,
+        amount: Balance,
+        series_id: u64,
+    ) -> PromiseOrValue<U128> {
+        let series = self.option_series.get(&series_id).expect("series not found");
+        require!(!series.is_expired(), "series expired");
+        require!(series.collateral_token_id() == token_in, "invalid collateral token");
+        let written = series.compute_writable_amount(amount);
+        require!(written > 0, "collateral too small");
+        let collateral = series.compute_collateral(written);
+
+        let prev_storage = env::storage_usage();
+        self.internal_mint_options(series_id, writer_id, written, collateral);
+        let mut position = self.get_option_position_unwrap(writer_id, series_id);
+        position.written = U128(position.written.0 + written);
+        position.collateral = U128(position.collateral.0 + collateral);
+        self.internal_set_option_position(writer_id, series_id, position);
+        self.verify_storage(writer_id, prev_storage, None);
+        PromiseOrValue::Value(U128(amount - collateral))
+    }
+
+    pub(crate) fn internal_create_option_series(&mut self, terms: OptionSeriesTerms) -> u64 {
+        let series_id = self.next_option_series_id;
+        self.next_option_series_id += 1;
+        self.option_series_by_terms.insert(&terms, &series_id);
+        let underlying_decimals = self.get_token_info(terms.underlying_token_id.clone()).decimals;
+        self.option_series.insert(
+            &series_id,
+            &OptionSeries {
+                series_id,
+                underlying_token_id: terms.underlying_token_id,
+                strike_token_id: terms.strike_token_id,
+                underlying_decimals,
+                strike_price: U128(terms.strike_price),
+                expiry_timestamp_sec: terms.expiry_timestamp_sec,
+                option_type: terms.option_type,
+                settlement_mode: terms.settlement_mode,
+                exercise_window_sec: terms.exercise_window_sec,
+                total_written: U128(0),
+                total_exercised: U128(0),
+                total_physically_exercised: U128(0),
+                total_exercise_payments: U128(0),
+                total_collateral: U128(0),
+                settlement_price: None,
+            },
+        );
+        series_id
+    }
+
+    //adds written options backed by `collateral` to the series and gives them to the holder
+    pub(crate) fn internal_mint_options(
+        &mut self,
+        series_id: u64,
+        holder_id: &AccountId,
+        amount: Balance,
+        collateral: Balance,
+    ) {
+        let mut series = self.option_series.get(&series_id).expect("series not found");
+        series.total_written = U128(series.total_written.0 + amount);
+        series.total_collateral = U128(series.total_collateral.0 + collateral);
+        self.option_series.insert(&series_id, &series);
+        let mut position = self.get_option_position(holder_id, series_id).unwrap_or_default();
+        position.held = U128(position.held.0 + amount);
+        self.internal_set_option_position(holder_id, series_id, position);
+        log!("Mint {} options of series {} to {}", amount, series_id, holder_id);
+    }
+
+    //premium of `amount` options in the collateral token at the given volatility, rounded up
+    pub(crate) fn compute_series_premium(
+        &self,
+        series: &OptionSeries,
+        amount: Balance,
+        implied_volatility: u64,
+        skew: u64,
+    ) -> Balance {
+        require!(!series.is_expired(), "series expired");
+        self.compute_series_value(series, amount, implied_volatility, skew)
+    }
+
+    //value of `amount` options in the collateral token at the given volatility, rounded up,
+    //expired series are worth their in-the-money value and settled ones their payoff
+    pub(crate) fn compute_series_value(
+        &self,
+        series: &OptionSeries,
+        amount: Balance,
+        implied_volatility: u64,
+        skew: u64,
+    ) -> Balance {
+        if series.settlement_price.is_some() {
+            return series.compute_payoff(amount, true);
+        }
+        let spot = self.get_option_spot_price(&series.underlying_token_id, &series.strike_token_id);
+        let value = compute_option_value(
+            spot,
+            series.strike_price.0,
+            series
+                .expiry_timestamp_sec
+                .saturating_sub(env::block_timestamp_ms() / 1000),
+            implied_volatility,
+            skew,
+            series.option_type,
+        );
+        if value == 0 {
+            return 0;
+        }
+        //calls are paid in the underlying, puts in the strike token
+        let (numerator, denominator) = match series.option_type {
+            OptionType::Call => (U256::from(amount) * U256::from(value), U256::from(spot)),
+            OptionType::Put => (
+                U256::from(amount) * U256::from(value),
+                U256::from(10u128.pow(series.underlying_decimals as u32)),
+            ),
+        };
+        ((numerator + denominator - 1) / denominator).as_u128()
+    }
+
+    //frees the collateral of `written` options of a settled series that is not owed to holders and
+    //returns it with the writer's share of the physical exercise payments, writers are assigned
+    //physical exercises and the cash payoff of the options left pro rata to what they wrote
+    pub(crate) fn internal_release_collateral(
+        &mut self,
+        series_id: u64,
+        written: Balance,
+        collateral: Balance,
+    ) -> (Balance, Balance) {
+        let mut series = self.option_series.get(&series_id).expect("series not found");
+        require!(series.settlement_price.is_some(), "series not settled");
+        let total_written = U256::from(series.total_written.0);
+        let physically_exercised = U256::from(series.total_physically_exercised.0);
+        if series.settlement_mode == SettlementMode::Physical {
+            require!(series.is_exercise_window_over(), "exercise window not over");
+        }
+        let delivered = ((U256::from(collateral) * physically_exercised + total_written - 1)
+            / total_written)
+            .as_u128();
+        let cash_settled = ((U256::from(written) * (total_written - physically_exercised)
+            + total_written
+            - 1)
+            / total_written)
+            .as_u128();
+        let released = collateral
+            .saturating_sub(delivered)
+            .saturating_sub(series.compute_payoff(cash_settled, true));
+        let payments = (U256::from(series.total_exercise_payments.0) * U256::from(written)
+            / total_written)
+            .as_u128();
+        series.total_collateral = U128(series.total_collateral.0 - released);
+        self.option_series.insert(&series_id, &series);
+        (released, payments)
+    }
+
+    fn internal_exercise_option_in_cash(
+        &mut self,
+        account_id: &AccountId,
+        series_id: u64,
+        amount: Balance,
+    ) -> Balance {
+        let mut series = self.option_series.get(&series_id).expect("series not found");
+        require!(series.settlement_price.is_some(), "series not settled");
+        let mut position = self.get_option_position_unwrap(account_id, series_id);
+        require!(amount > 0 && amount <= position.held.0, "insufficient options");
+        position.held = U128(position.held.0 - amount);
+        self.internal_set_option_position(account_id, series_id, position);
+        log!("Burn {} options of series {} from {}", amount, series_id, account_id);
+
+        let payoff = series.compute_payoff(amount, false);
+        series.total_exercised = U128(series.total_exercised.0 + amount);
+        series.total_collateral = U128(series.total_collateral.0 - payoff);
+        self.option_series.insert(&series_id, &series);
+        self.internal_add_option_balance(account_id, &series.collateral_token_id().clone(), payoff);
+        payoff
+    }
+
+    //the holder pays the strike for calls or delivers the underlying for puts and receives the other
+    //token from the collateral, returns the unused payment
+    pub(crate) fn internal_exercise_option_physically(
+        &mut self,
+        holder_id: &AccountId,
+        token_in: &AssetId,
+        payment: Balance,
+        series_id: u64,
+        amount: Balance,
+    ) -> PromiseOrValue<U128> {
+        let mut series = self.option_series.get(&series_id).expect("series not found");
+        require!(series.settlement_mode == SettlementMode::Physical, "series is cash settled");
+        require!(
+            series.is_expired() && !series.is_exercise_window_over(),
+            "not in the exercise window"
+        );
+        require!(series.exercise_payment_token_id() == token_in, "invalid payment token");
+        let (required, delivered) = match series.option_type {
+            OptionType::Call => (series.compute_strike_amount(amount, true), amount),
+            OptionType::Put => (amount, series.compute_strike_amount(amount, false)),
+        };
+        require!(required <= payment, "insufficient payment");
+        let prev_storage = env::storage_usage();
+        let mut position = self.get_option_position_unwrap(holder_id, series_id);
+        require!(amount > 0 && amount <= position.held.0, "insufficient options");
+        position.held = U128(position.held.0 - amount);
+        self.internal_set_option_position(holder_id, series_id, position);
+        log!("Burn {} options of series {} from {}", amount, series_id, holder_id);
+
+        series.total_exercised = U128(series.total_exercised.0 + amount);
+        series.total_physically_exercised = U128(series.total_physically_exercised.0 + amount);
+        series.total_exercise_payments = U128(series.total_exercise_payments.0 + required);
+        series.total_collateral = U128(series.total_collateral.0 - delivered);
+        self.option_series.insert(&series_id, &series);
+        self.internal_add_option_balance(holder_id, &series.collateral_token_id().clone(), delivered);
+        self.verify_storage(holder_id, prev_storage, None);
+        PromiseOrValue::Value(U128(payment - required))
+    }
+
+    fn internal_settle_option_series(&mut self, series_id: u64, settlement_price: Balance) -> U128 {
+        let mut series = self.option_series.get(&series_id).expect("series not found");
+        require!(series.settlement_price.is_none(), "series already settled");
+        series.settlement_price = Some(U128(settlement_price));
+        self.option_series.insert(&series_id, &series);
+        log!("Settle option series {} at {}", series_id, settlement_price);
+        U128(settlement_price)
+    }
+
+    //oracle price of one whole underlying token in strike token units
+    pub(crate) fn get_option_spot_price(&self, underlying_token_id: &AssetId, strike_token_id: &AssetId) -> Balance {
+        let underlying_rate = self.get_exchange_rate(underlying_token_id);
+        let strike_rate = self.get_exchange_rate(strike_token_id);
+        let strike_decimals = self.get_token_info(strike_token_id.clone()).decimals;
+        (U256::from(underlying_rate.multiplier())
+            * U256::from(10u128.pow(strike_rate.decimals() as u32))
+            * U256::from(10u128.pow(strike_decimals as u32))
+            / (U256::from(strike_rate.multiplier())
+                * U256::from(10u128.pow(underlying_rate.decimals() as u32))))
+        .as_u128()
+    }
+
+    pub(crate) fn get_option_position(&self, account_id: &AccountId, series_id: u64) -> Option<OptionPosition> {
+        self.option_positions
+            .get(account_id)
+            .and_then(|positions| positions.get(&series_id).cloned())
+    }
+
+    fn get_option_position_unwrap(&self, account_id: &AccountId, series_id: u64) -> OptionPosition {
+        self.get_option_position(account_id, series_id)
+            .expect("no option position")
+    }
+
+    //empty positions are dropped so the account map does not grow with expired series
+    pub(crate) fn internal_set_option_position(
+        &mut self,
+        account_id: &AccountId,
+        series_id: u64,
+        position: OptionPosition,
+    ) {
+        let mut positions = self.option_positions.get(account_id).unwrap_or_default();
+        if position.held.0 == 0 && position.written.0 == 0 {
+            positions.remove(&series_id);
+        } else {
+            positions.insert(series_id, position);
+        }
+        if positions.is_empty() {
+            self.option_positions.remove(account_id);
+        } else {
+            self.option_positions.insert(account_id, &positions);
+        }
+    }
+
+    pub(crate) fn internal_add_option_balance(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &AssetId,
+        amount: Balance,
+    ) {
+        if amount == 0 {
+            return;
+        }
+        let mut balances = self.option_balances.get(account_id).unwrap_or_default();
+        let balance = balances.get(token_id).map(|v| v.0).unwrap_or(0);
+        balances.insert(token_id.clone(), U128(balance + amount));
+        self.option_balances.insert(account_id, &balances);
+    }
+
+    fn internal_send_option_tokens(
+        &self,
+        token_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: Balance,
+    ) -> Promise {
+        ext_ft_core::ft_transfer(
+            receiver_id.clone(),
+            U128(amount),
+            None,
+            token_id.clone(),
+            1,
+            GAS_FOR_FT_TRANSFER,
+        )
+        .then(ext_self::callback_option_transfer(
+            token_id.clone(),
+            receiver_id.clone(),
+            U128(amount),
+            env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_option_series(&self, series_id: u64) -> Option<OptionSeries> {
+        self.option_series.get(&series_id)
+    }
+
+    pub fn get_option_series_list(&self, from_index: Option<usize>, limit: Option<usize>) -> Vec<OptionSeries> {
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        let start_index = from_index.unwrap_or(0);
+        self.option_series
+            .values()
+            .skip(start_index)
+            .take(limit)
+            .collect::<Vec<_>>()
+    }
+
+    pub fn get_option_positions(&self, account_id: AccountId) -> HashMap<u64, OptionPosition> {
+        self.option_positions.get(&account_id).unwrap_or_default()
+    }
+
+    pub fn get_option_balances(&self, account_id: AccountId) -> HashMap<AssetId, U128> {
+        self.option_balances.get(&account_id).unwrap_or_default()
+    }
+}
@@ -44,8 +44,9 @@ pub struct Pool {
     pub total_borrow: Balance,
     pub account_deposits: UnorderedMap<AccountId, AccountDeposit>,
     pub fixed_interest_rate: u64,
-    pub acc_interest_per_share: Balance,
-    pub last_acc_interest_update_timestamp_sec: u64,
+    pub borrow_index: Balance, //growth of a borrowed amount with interest, starts at INTEREST_INDEX_PRECISION
+    pub supply_index: Balance, //growth of a lend token deposit with interest
+    pub last_index_update_timestamp_sec: u64,
     pub liquidation_bonus: u64, //or price penalty, it means how much discount liquidators can buy collateral tokens using lend token to pay the debt
     pub liquidation_history: Vec<Liquidation>,
 }
@@ -83,8 +84,9 @@ impl Pool {
                 format!("p_account_deposits_{}", pool_id).as_bytes(),
             ),
             fixed_interest_rate: fixed_interest_rate,
-            acc_interest_per_share: 0,
-            last_acc_interest_update_timestamp_sec: 0,
+            borrow_index: INTEREST_INDEX_PRECISION,
+            supply_index: INTEREST_INDEX_PRECISION,
+            last_index_update_timestamp_sec: env::block_timestamp_ms() / 1000,
             liquidation_bonus: liquidation_bonus,
             liquidation_history: vec![],
         }
@@ -102,9 +104,8 @@ impl Pool {
                 "lower than min deposit"
             );
         }
-        //update acc_interest_per_share
-        log!("update_acc_interest_per_share");
-        self.update_acc_interest_per_share();
+        log!("update_indexes");
+        self.update_indexes();
 
         log!(
             "reading account deposit {} for token {}, lend {}, collateral {}",
@@ -114,7 +115,7 @@ impl Pool {
             self.collateral_token_id
         );
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        account_deposit.update_account(self.fixed_interest_rate, &self.acc_interest_per_share);
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
         log!(
             "success reading account deposit {} from pool {}",
             account_id.clone(),
@@ -129,7 +130,6 @@ impl Pool {
         } else {
             env::panic_str("unsupported token for pool")
         }
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
         self.account_deposits.insert(account_id, &account_deposit);
     }
 
@@ -142,7 +142,7 @@ impl Pool {
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
     ) -> Balance {
-        self.update_acc_interest_per_share();
+        self.update_indexes();
 
         require!(
             amount.clone() >= self.min_lend_token_borrow,
@@ -166,17 +166,16 @@ impl Pool {
         }
 
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        account_deposit.update_account(self.fixed_interest_rate, &self.acc_interest_per_share);
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
         let actual_borrow_amount = account_deposit.internal_borrow(
             amount,
             lend_token_info,
             lend_token_price,
             collateral_token_info,
             collateral_token_price,
-            self.fixed_interest_rate,
+            &self.borrow_index,
             self.min_cr,
         );
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
         self.account_deposits.insert(account_id, &account_deposit);
         if actual_borrow_amount != amount.clone() {
             self.total_lend_asset_deposit -= amount.clone() - actual_borrow_amount;
@@ -209,9 +208,9 @@ impl Pool {
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
     ) {
-        self.update_acc_interest_per_share();
+        self.update_indexes();
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        account_deposit.update_account(self.fixed_interest_rate, &self.acc_interest_per_share);
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
         log!(
             "balance before withdraw {}",
             account_deposit.get_token_deposit(token_id)
@@ -223,19 +222,9 @@ impl Pool {
             lend_token_price,
             collateral_token_info,
             collateral_token_price,
-            self.fixed_interest_rate,
-            &self.acc_interest_per_share,
+            &self.borrow_index,
             self.min_cr,
         );
-        if token_id.clone() == self.lend_token_id.clone() {
-            log!(
-                "withdraw {} of {}, of which {} is extracted from generated lending interest",
-                amount.clone(),
-                token_id.clone(),
-                amount.clone() - withdrawn_amount_from_deposit.clone()
-            );
-        }
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
         self.account_deposits.insert(account_id, &account_deposit);
         log!(
             "balance after withdraw {}",
@@ -249,21 +238,25 @@ impl Pool {
         }
     }
 
-    fn update_acc_interest_per_share(&mut self) {
-        self.acc_interest_per_share = self.get_current_acc_interest_per_share();
-        self.last_acc_interest_update_timestamp_sec = get_next_interest_recal_time_sec();
+    //accrues interest since the last update into the indexes and the pool totals
+    pub fn update_indexes(&mut self) {
+        let (borrow_index, supply_index, interest) = self.compute_current_indexes();
+        self.borrow_index = borrow_index;
+        self.supply_index = supply_index;
+        self.total_borrow += interest;
+        self.total_lend_asset_deposit += interest;
+        self.last_index_update_timestamp_sec = env::block_timestamp_ms() / 1000;
     }
 
     pub fn internal_pay_loan(&mut self, account_id: &AccountId, pay_amount: Balance) {
-        self.update_acc_interest_per_share();
+        self.update_indexes();
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        account_deposit.update_account(self.fixed_interest_rate, &self.acc_interest_per_share);
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
         log!("pool: paying amount {}", pay_amount);
-        let (paid_borrow, added_liquidity) = account_deposit.internal_pay_loan(pay_amount);
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
+        let (paid_debt, added_liquidity) = account_deposit.internal_pay_loan(pay_amount);
         self.account_deposits.insert(account_id, &account_deposit);
 
-        self.total_borrow -= paid_borrow;
+        self.total_borrow = self.total_borrow.saturating_sub(paid_debt);
         self.total_lend_asset_deposit =
             self.total_lend_asset_deposit + added_liquidity - pay_amount;
     }
@@ -280,12 +273,12 @@ impl Pool {
         foundation_id: AccountId,
         liquidation_marginal: u64,
     ) {
-        self.update_acc_interest_per_share();
+        self.update_indexes();
 
         let mut liquidator_account_deposit =
             self.get_account_deposit_or_revert(&liquidator_account_id);
         liquidator_account_deposit
-            .update_account(self.fixed_interest_rate, &self.acc_interest_per_share);
+            .update_account(&self.borrow_index, &self.supply_index);
         let liquidator_account_lend_token_deposit_amount =
             liquidator_account_deposit.get_token_deposit(&self.lend_token_id);
         require!(
@@ -302,7 +295,7 @@ impl Pool {
             self.get_account_deposit_or_revert(&liquidated_account_id);
         let borrowed_before = liquidated_account_deposit.borrow_amount;
         liquidated_account_deposit
-            .update_account(self.fixed_interest_rate, &self.acc_interest_per_share);
+            .update_account(&self.borrow_index, &self.supply_index);
         let liquidated_account_cr = self.compute_current_cr(
             liquidated_account_id.clone(),
             lend_token_info,
@@ -342,7 +335,7 @@ impl Pool {
         let to_liquidate_collateral_amount_to_cover_liquidator =
             to_liquidate_collateral_amount_to_cover_liquidator.as_u128();
 
-        let (actual_borrow_paid, remain) =
+        let (paid_debt, remain) =
             liquidated_account_deposit.internal_pay_loan(liquidated_borrow_amount);
 
         let liquidated_collateral_amount_before =
@@ -352,7 +345,7 @@ impl Pool {
 
         self.total_lend_asset_deposit =
             self.total_lend_asset_deposit - liquidated_borrow_amount + remain;
-        self.total_borrow = self.total_borrow - actual_borrow_paid;
+        self.total_borrow = self.total_borrow.saturating_sub(paid_debt);
 
         let liquidated_account_cr = self.compute_current_cr(
             liquidated_account_id.clone(),
@@ -378,15 +371,9 @@ impl Pool {
         liquidator_account_deposit.internal_deposit_collateral(&collateral_to_liquidator);
 
         let mut foundation_account_deposit = self.get_account_deposit_or_revert(&foundation_id);
+        foundation_account_deposit.update_account(&self.borrow_index, &self.supply_index);
         foundation_account_deposit.internal_deposit_collateral(&collateral_to_foundation);
 
-        liquidated_account_deposit
-            .update_lending_interest_profit_debt(&self.acc_interest_per_share);
-        liquidator_account_deposit
-            .update_lending_interest_profit_debt(&self.acc_interest_per_share);
-        foundation_account_deposit
-            .update_lending_interest_profit_debt(&self.acc_interest_per_share);
-
         //save accounts
         self.account_deposits
             .insert(&liquidated_account_id, &liquidated_account_deposit);
@@ -419,21 +406,20 @@ impl Pool {
     }
 
     pub fn internal_pay_borrowing_and_interest(&mut self, account_id: &AccountId) {
-        self.update_acc_interest_per_share();
+        self.update_indexes();
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        account_deposit.update_account(self.fixed_interest_rate, &self.acc_interest_per_share);
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
         let borrowing_and_interest = self.get_borrow_and_interest(account_id);
         log!(
             "internal_pay_borrowing_and_interest: borrowing_and_interest {}",
             borrowing_and_interest
         );
         
-        let (paid_borrow, added_liquidity) =
+        let (paid_debt, added_liquidity) =
             account_deposit.internal_pay_loan(borrowing_and_interest);
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
         self.account_deposits.insert(account_id, &account_deposit);
 
-        self.total_borrow -= paid_borrow;
+        self.total_borrow = self.total_borrow.saturating_sub(paid_debt);
         self.total_lend_asset_deposit =
             self.total_lend_asset_deposit + added_liquidity - borrowing_and_interest;
     }
@@ -447,13 +433,12 @@ impl Pool {
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
     ) -> Balance {
-        self.update_acc_interest_per_share();
+        self.update_indexes();
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        account_deposit.update_account(self.fixed_interest_rate, &self.acc_interest_per_share);
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
         self.account_deposits.insert(account_id, &account_deposit);
         
-        //get deposit
+        //get deposit, lending interest included
         let remaining_deposit = account_deposit.get_token_deposit(token_id);
         //withdraw
         if remaining_deposit > 0 {
@@ -504,6 +489,7 @@ impl Pool {
         }
 
         let account_deposit = self.get_account_deposit(&account_id);
+        let (borrow_index, _, _) = self.compute_current_indexes();
         account_deposit.compute_current_cr(
             lend_token_info,
             lend_token_price,
@@ -512,7 +498,7 @@ impl Pool {
             collateral_amount,
             borrow,
             pay_amount,
-            self.fixed_interest_rate,
+            &borrow_index,
         )
     }
 
@@ -529,13 +515,14 @@ impl Pool {
             return 0;
         }
         let account_deposit = self.get_account_deposit(account_id);
+        let (borrow_index, _, _) = self.compute_current_indexes();
         let max_borrowable = account_deposit.compute_max_borrowable(
             lend_token_info,
             lend_token_price,
             collateral_token_info,
             collateral_token_price,
             additional_collateral_amount,
-            self.fixed_interest_rate,
+            &borrow_index,
             self.min_cr,
         );
         let max_of_pool = self.get_max_borrowable_of_pool();
@@ -556,36 +543,37 @@ impl Pool {
             return HashMap::new();
         }
         let account_deposit = self.get_account_deposit(account_id);
-        account_deposit.get_deposits()
+        let (_, supply_index, _) = self.compute_current_indexes();
+        account_deposit.get_deposits(&supply_index)
     }
 
-    pub fn get_current_acc_interest_per_share(&self) -> Balance {
-        if self.total_lend_asset_deposit == 0 {
-            return self.acc_interest_per_share;
-        }
-        let mut last_acc_interest_update_timestamp_sec =
-            self.last_acc_interest_update_timestamp_sec;
-        if self.total_borrow == 0 {
-            last_acc_interest_update_timestamp_sec = get_last_interest_recal_time_sec();
+    /// Borrow index, supply index and interest generated, accrued from the last update to now.
+    pub fn compute_current_indexes(&self) -> (Balance, Balance, Balance) {
+        let current_time_sec = env::block_timestamp_ms() / 1000;
+        if self.total_borrow == 0 || current_time_sec <= self.last_index_update_timestamp_sec {
+            return (self.borrow_index, self.supply_index, 0);
         }
-        let elapsed_time =
-            get_next_interest_recal_time_sec() - last_acc_interest_update_timestamp_sec;
-        let elapsed_time = elapsed_time as u128;
-        let generated_interest =
-            self.total_borrow * elapsed_time * (self.fixed_interest_rate as u128)
-                / (SECONDS_PER_YEAR * INTEREST_RATE_DIVISOR);
-        return self.acc_interest_per_share
-            + generated_interest * ACC_INTEREST_PER_SHARE_MULTIPLIER
-                / self.total_lend_asset_deposit;
-    }
-
-    pub fn get_pending_unpaid_lending_interest_profit(&self, account_id: &AccountId) -> Balance {
-        if self.account_deposits.get(account_id).is_none() {
-            return 0;
+        let elapsed_time = (current_time_sec - self.last_index_update_timestamp_sec) as u128;
+        let rate_over_elapsed_time = U256::from(self.fixed_interest_rate)
+            * U256::from(elapsed_time)
+            * U256::from(INTEREST_INDEX_PRECISION)
+            / U256::from(SECONDS_PER_YEAR * INTEREST_RATE_DIVISOR);
+
+        let borrow_index = self.borrow_index
+            + (U256::from(self.borrow_index) * rate_over_elapsed_time
+                / U256::from(INTEREST_INDEX_PRECISION))
+            .as_u128();
+        let interest = (U256::from(self.total_borrow) * rate_over_elapsed_time
+            / U256::from(INTEREST_INDEX_PRECISION))
+        .as_u128();
+        if self.total_lend_asset_deposit == 0 {
+            return (borrow_index, self.supply_index, interest);
         }
-        let account_deposit = self.get_account_deposit(account_id);
-        account_deposit
-            .get_pending_unpaid_lending_interest_profit(&self.get_current_acc_interest_per_share())
+        let supply_index = self.supply_index
+            + (U256::from(self.supply_index) * U256::from(interest)
+                / U256::from(self.total_lend_asset_deposit))
+            .as_u128();
+        (borrow_index, supply_index, interest)
     }
 
     pub fn get_pending_total_lending_interest_profit(&self, account_id: &AccountId) -> Balance {
@@ -593,16 +581,8 @@ impl Pool {
             return 0;
         }
         let account_deposit = self.get_account_deposit(account_id);
-        account_deposit
-            .get_pending_total_lending_interest_profit(&self.get_current_acc_interest_per_share())
-    }
-
-    pub fn get_total_interest_reward(&self, account_id: &AccountId) -> Balance {
-        if self.account_deposits.get(account_id).is_none() {
-            return 0;
-        }
-        let account_deposit = self.get_account_deposit(account_id);
-        account_deposit.get_total_interest_reward(&self.get_current_acc_interest_per_share())
+        let (_, supply_index, _) = self.compute_current_indexes();
+        account_deposit.get_pending_total_lending_interest_profit(&supply_index)
     }
 
     pub fn get_pending_unpaid_borrowing_interest(&self, account_id: &AccountId) -> Balance {
@@ -610,7 +590,8 @@ impl Pool {
             return 0;
         }
         let account_deposit = self.get_account_deposit(account_id);
-        account_deposit.get_pending_unpaid_borrowing_interest(self.fixed_interest_rate)
+        let (borrow_index, _, _) = self.compute_current_indexes();
+        account_deposit.get_pending_unpaid_borrowing_interest(&borrow_index)
     }
 
     pub fn compute_unrecorded_interest(&self, account_id: &AccountId) -> Balance {
@@ -618,7 +599,8 @@ impl Pool {
             return 0;
         }
         let account_deposit = self.get_account_deposit(account_id);
-        account_deposit.compute_unrecorded_interest(self.fixed_interest_rate)
+        let (borrow_index, _, _) = self.compute_current_indexes();
+        account_deposit.compute_unrecorded_interest(&borrow_index)
     }
 
     pub fn get_pending_total_borrowing_interest(&self, account_id: &AccountId) -> Balance {
@@ -626,7 +608,8 @@ impl Pool {
             return 0;
         }
         let account_deposit = self.get_account_deposit(account_id);
-        account_deposit.get_pending_total_borrowing_interest(self.fixed_interest_rate)
+        let (borrow_index, _, _) = self.compute_current_indexes();
+        account_deposit.get_pending_total_borrowing_interest(&borrow_index)
     }
 
     pub fn get_token_deposit(&self, account_id: &AccountId, token_id: &AssetId) -> Balance {
@@ -647,7 +630,7 @@ impl Pool {
     }
 
     pub fn get_borrow_and_interest(&self, account_id: &AccountId) -> Balance {
-        self.get_borrow_amount(account_id) + self.compute_unrecorded_interest(account_id)
+        self.get_borrow_amount(account_id) + self.get_pending_unpaid_borrowing_interest(account_id)
     }
 }
 
@@ -11,6 +11,34 @@ pub enum TokenReceiverMessage {
     Deposit {
         pool_id: u32
     },
+    /// Buys options from an amm, the transferred amount is the max premium.
+    /// Listed before the variants it shares fields with, untagged matching ignores unknown fields.
+    BuyOption {
+        amm_id: u32,
+        series_id: u64,
+        amount: U128,
+    },
+    /// Pays for a physical exercise during the exercise window, the unused payment is refunded.
+    ExerciseOption {
+        series_id: u64,
+        amount: U128,
+    },
+    /// Locks the transferred collateral and mints options of the series to the writer.
+    WriteOption {
+        series_id: u64
+    },
+    AddOptionAmmLiquidity {
+        amm_id: u32
+    },
+    /// Buys options of the current vault series, the transferred amount is the max premium.
+    BuyVaultOption {
+        vault_id: u32,
+        amount: U128,
+    },
+    /// Deposits the vault asset, shares are minted when the next epoch starts.
+    DepositToOptionVault {
+        vault_id: u32
+    },
 }
 
 #[near_bindgen]
@@ -42,6 +70,53 @@ impl FungibleTokenReceiver for Contract {
                     self.internal_deposit(pool_id, &sender_id, &token_in, amount.0);
                     PromiseOrValue::Value(U128(0))
                 }
+                TokenReceiverMessage::ExerciseOption {
+                    series_id,
+                    amount: option_amount,
+                } => self.internal_exercise_option_physically(
+                    &sender_id,
+                    &token_in,
+                    amount.0,
+                    series_id,
+                    option_amount.0,
+                ),
+                TokenReceiverMessage::WriteOption {
+                    series_id
+                } => self.internal_write_option(&sender_id, &token_in, amount.0, series_id),
+                TokenReceiverMessage::BuyOption {
+                    amm_id,
+                    series_id,
+                    amount: option_amount,
+                } => PromiseOrValue::Value(U128(self.internal_buy_option(
+                    &sender_id,
+                    &token_in,
+                    amount.0,
+                    amm_id,
+                    series_id,
+                    option_amount.0,
+                ))),
+                TokenReceiverMessage::AddOptionAmmLiquidity {
+                    amm_id
+                } => {
+                    self.internal_add_option_amm_liquidity(&sender_id, &token_in, amount.0, amm_id);
+                    PromiseOrValue::Value(U128(0))
+                }
+                TokenReceiverMessage::BuyVaultOption {
+                    vault_id,
+                    amount: option_amount,
+                } => PromiseOrValue::Value(U128(self.internal_buy_vault_option(
+                    &sender_id,
+                    &token_in,
+                    amount.0,
+                    vault_id,
+                    option_amount.0,
+                ))),
+                TokenReceiverMessage::DepositToOptionVault {
+                    vault_id
+                } => {
+                    self.internal_deposit_to_option_vault(&sender_id, &token_in, amount.0, vault_id);
+                    PromiseOrValue::Value(U128(0))
+                }
             }
         }
     }
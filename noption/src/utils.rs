@@ -4,10 +4,12 @@ use near_sdk::{ext_contract, AccountId, Balance, Gas, PromiseResult};
 /// Amount of gas for fungible token transfers, increased to 20T to support AS token contracts.
 pub const GAS_FOR_FT_TRANSFER: Gas = Gas(20_000_000_000_000);
 pub const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas(20_000_000_000_000);
+pub const GAS_FOR_MFT_TRANSFER_CALL: Gas = Gas(25_000_000_000_000 + 20_000_000_000_000);
 
 #[ext_contract(ext_self)]
 pub trait NaiVault {
     fn callback_post_withdraw(&mut self, pool_id: u32, token_id: AccountId, receiver_id: AccountId, amount: U128);
+    fn callback_option_transfer(&mut self, token_id: AccountId, receiver_id: AccountId, amount: U128);
 }
 
 #[ext_contract(ext_ft_core)]
@@ -111,10 +113,11 @@ pub fn compute_cr(
     cr.as_u64()
 }
 
-pub fn get_last_interest_recal_time_sec() -> u64 {
-    ((env::block_timestamp_ms() / 1000) / INTEREST_RECAL_PERIOD_SEC) * INTEREST_RECAL_PERIOD_SEC
-}
-
-pub fn get_next_interest_recal_time_sec() -> u64 {
-    get_last_interest_recal_time_sec() + INTEREST_RECAL_PERIOD_SEC
+//amount grown from the index at its last update to the current index
+pub fn apply_index_growth(amount: Balance, current_index: &Balance, last_index: &Balance) -> Balance {
+    if amount == 0 || last_index.clone() == 0 {
+        return amount;
+    }
+    (U256::from(amount) * U256::from(current_index.clone()) / U256::from(last_index.clone()))
+        .as_u128()
 }
@@ -35,8 +35,8 @@ pub struct PoolInfo {
     pub total_borrow: U128,
     pub total_borrow_value_usd: U128,
     pub fixed_interest_rate: u64,
-    pub acc_interest_per_share: U128,
-    pub last_acc_interest_update_timestamp_sec: u64,
+    pub borrow_index: U128,
+    pub supply_index: U128,
     pub liquidation_bonus: u64,
     pub token_meta_info: TokenMetaInfo,
 }
@@ -55,16 +55,13 @@ pub struct AccountInfo {
     min_cr: u64,
     current_cr: u64,
 
-    //lending interest when account is a lender
-    lending_interest_profit_debt: U128, //interest debt when in a lending position, this is similar to rewardDebt in sushi farming
-    unpaid_lending_interest_profit: U128,
+    //lending interest when account is a lender, included in the lend token deposit
     total_lending_interest_profit: U128,
-    last_lending_interest_reward_update_timestamp_sec: u64,
 
     //borrowing interest when accc is a borrower
     unpaid_borrowing_interest: U128, //interest unpaid in a borrowing positions
     total_borrowing_interest: U128,  //interest unpaid in a borrowing positions
-    last_borrowing_interest_update_timestamp_sec: u64,
+    last_index_update_timestamp_sec: u64,
     max_borrowable: U128,
 
     //token info
@@ -72,8 +69,6 @@ pub struct AccountInfo {
     liquidation_price: Price,
 
     unrecorded_interest: U128,
-    acc_interest_per_share: U128,
-    total_interest_reward: U128,
 }
 
 #[near_bindgen]
@@ -171,23 +166,16 @@ impl Contract {
                 Some(borrow),
                 Some(pay_amount),
             ),
-            //lending interest when account is a lender
-            lending_interest_profit_debt: U128(account_deposit.lending_interest_profit_debt), //interest debt when in a lending position, this is similar to rewardDebt in sushi farming
-            unpaid_lending_interest_profit: U128(
-                pool.get_pending_unpaid_lending_interest_profit(&account_id),
-            ),
+            //lending interest when account is a lender, included in the lend token deposit
             total_lending_interest_profit: U128(
                 pool.get_pending_total_lending_interest_profit(&account_id),
             ),
-            last_lending_interest_reward_update_timestamp_sec: account_deposit
-                .last_lending_interest_reward_update_timestamp_sec,
             //borrowing interest when accc is a borrower
             unpaid_borrowing_interest: U128(
                 pool.get_pending_unpaid_borrowing_interest(&account_id),
             ),
             total_borrowing_interest: U128(pool.get_pending_total_borrowing_interest(&account_id)),
-            last_borrowing_interest_update_timestamp_sec: account_deposit
-                .last_borrowing_interest_update_timestamp_sec,
+            last_index_update_timestamp_sec: account_deposit.last_index_update_timestamp_sec,
             max_borrowable: pool
                 .compute_max_borrowable_for_account(
                     &account_id,
@@ -207,8 +195,6 @@ impl Contract {
                 Some(pay_amount),
             ),
             unrecorded_interest: U128(pool.compute_unrecorded_interest(&account_id)),
-            acc_interest_per_share: pool.get_current_acc_interest_per_share().into(),
-            total_interest_reward: pool.get_total_interest_reward(&account_id).into(),
         }
     }
 
@@ -255,6 +241,7 @@ impl Contract {
             current_utilization =
                 (p.total_borrow * UTILIZATION_DIVISOR as u128 / p.total_lend_asset_deposit) as u64;
         }
+        let (borrow_index, supply_index, _) = p.compute_current_indexes();
 
         let current_pool_cr = compute_cr(
             p.total_collateral_deposit,
@@ -297,8 +284,8 @@ impl Contract {
                 &token_meta_info.lend_token_price,
             ),
             fixed_interest_rate: p.fixed_interest_rate,
-            acc_interest_per_share: U128(p.acc_interest_per_share),
-            last_acc_interest_update_timestamp_sec: p.last_acc_interest_update_timestamp_sec,
+            borrow_index: U128(borrow_index),
+            supply_index: U128(supply_index),
             liquidation_bonus: p.liquidation_bonus,
             token_meta_info: token_meta_info,
         }
@@ -11,19 +11,34 @@ pub struct AccountDeposit {
     pub owner_id: AccountId,
     pub lend_token_id: AssetId,
     pub collateral_token_id: AssetId,
-    pub deposits: UnorderedMap<AssetId, Balance>,
+    pub deposits: UnorderedMap<AssetId, Balance>, //lend token deposit includes the lending interest earned
     pub borrow_amount: Balance,
 
-    //lending interest when account is a lender
-    pub lending_interest_profit_debt: Balance, //interest debt when in a lending position, this is similar to rewardDebt in sushi farming
-    pub unpaid_lending_interest_profit: Balance,
+    //lending interest when account is a lender, credited to the lend token deposit
     pub total_lending_interest_profit: Balance,
-    pub last_lending_interest_reward_update_timestamp_sec: u64,
+    pub total_lending_loss: Balance, //bad debt charged to the deposit
+    pub supply_index: Balance, //pool supply index at the last update
 
     //borrowing interest when accc is a borrower
     pub unpaid_borrowing_interest: Balance, //interest unpaid in a borrowing positions
     pub total_borrowing_interest: Balance,  //interest unpaid in a borrowing positions
-    pub last_borrowing_interest_update_timestamp_sec: u64,
+    pub borrow_index: Balance, //pool borrow index at the last update
+
+    pub last_index_update_timestamp_sec: u64,
+
+    //fixed-term loan, maturity is 0 for an open-ended loan
+    pub maturity_timestamp_sec: u64,
+    pub term_interest_rate: u64,
+
+    //credit delegation, debt up to the credit line of a borrower needs no collateral
+    pub delegated_out: Balance, //credit lines given to borrowers, backed by the lend token deposit
+    pub delegated_in: Balance,  //credit line received
+    pub credit_delegator_id: Option<AccountId>,
+    pub credit_expiry_timestamp_sec: u64,
+
+    //withdrawal queue
+    pub queued_withdrawal: Balance,    //waiting for liquidity
+    pub claimable_withdrawal: Balance, //filled, can be claimed
 }
 
 impl AccountDeposit {
@@ -43,13 +58,21 @@ impl AccountDeposit {
                 account_id: owner_id.clone(),
             }),
             borrow_amount: 0,
-            lending_interest_profit_debt: 0,
-            unpaid_lending_interest_profit: 0,
             total_lending_interest_profit: 0,
-            last_lending_interest_reward_update_timestamp_sec: 0,
+            total_lending_loss: 0,
+            supply_index: 0,
             unpaid_borrowing_interest: 0,
             total_borrowing_interest: 0,
-            last_borrowing_interest_update_timestamp_sec: 0,
+            borrow_index: 0,
+            last_index_update_timestamp_sec: 0,
+            maturity_timestamp_sec: 0,
+            term_interest_rate: 0,
+            delegated_out: 0,
+            delegated_in: 0,
+            credit_delegator_id: None,
+            credit_expiry_timestamp_sec: 0,
+            queued_withdrawal: 0,
+            claimable_withdrawal: 0,
         };
         account_deposit
     }
@@ -66,61 +89,83 @@ impl AccountDeposit {
     }
 
     //this does not take care of interest when user in a borrowing position
+    //collateral tokens of the pool other than collateral_token_id
+    pub fn internal_deposit_extra_collateral(&mut self, token_id: &AssetId, amount: &Balance) {
+        let current_deposit = self.get_token_deposit(token_id);
+        self.deposits.insert(token_id, &(current_deposit + amount.clone()));
+    }
+
     pub fn internal_deposit_lend_token(&mut self, amount: &Balance) {
         let mut current_deposit = self.get_token_deposit(&self.lend_token_id);
         current_deposit = current_deposit + amount.clone();
         self.deposits.insert(&self.lend_token_id, &current_deposit);
     }
 
-    pub fn update_borrowing_interest(&mut self, interest_rate: u64) -> Balance {
-        let mut interest = 0u128;
-        if self.borrow_amount > 0 {
-            interest = self.compute_unrecorded_interest(interest_rate);
-            self.total_borrowing_interest += interest;
-            self.unpaid_borrowing_interest += interest;
+    /// Brings the lend token deposit and the debt up to the current pool indexes.
+    pub fn update_account(&mut self, borrow_index: &Balance, supply_index: &Balance) {
+        //the supply index drops when bad debt is socialized
+        let current_deposit = self.get_token_deposit(&self.lend_token_id);
+        let updated_deposit = apply_index_growth(current_deposit, supply_index, &self.supply_index);
+        if updated_deposit > current_deposit {
+            self.total_lending_interest_profit += updated_deposit - current_deposit;
+        } else {
+            self.total_lending_loss += current_deposit - updated_deposit;
         }
-        self.last_borrowing_interest_update_timestamp_sec = get_next_interest_recal_time_sec();
-        interest
+        if updated_deposit != current_deposit {
+            self.deposits.insert(&self.lend_token_id, &updated_deposit);
+        }
+
+        let borrowing_interest = self.compute_unrecorded_interest(borrow_index);
+        self.unpaid_borrowing_interest += borrowing_interest;
+        self.total_borrowing_interest += borrowing_interest;
+
+        self.supply_index = supply_index.clone();
+        self.borrow_index = borrow_index.clone();
+        self.last_index_update_timestamp_sec = env::block_timestamp_ms() / 1000;
     }
 
-    pub fn update_account(&mut self, interest_rate: u64, acc_interest_per_share: &Balance, foundation_commission: u64) -> Balance {
-        log!("updating account {}", self.owner_id);
-        let commission = self.update_lending_profit(interest_rate, acc_interest_per_share, foundation_commission);
-        self.update_borrowing_interest(interest_rate);
-        log!("updating account {} done", self.owner_id);
-        commission
+    //locks in the interest of the whole term on the current borrow, returns the interest
+    pub fn lock_in_term(
+        &mut self,
+        loan_term: &LoanTerm,
+        lend_token_info: &TokenInfo,
+        lend_token_price: &Price,
+        collateral_token_info: &TokenInfo,
+        collateral_token_price: &Price,
+        extra_collateral: Balance,
+        borrow_index: &Balance,
+        min_cr: u64,
+    ) -> Balance {
+        if self.borrow_amount == 0 {
+            return 0;
+        }
+        let interest = self.borrow_amount
+            * (loan_term.duration_sec as u128)
+            * (loan_term.interest_rate as u128)
+            / (INTEREST_RATE_DIVISOR * SECONDS_PER_YEAR);
+        self.unpaid_borrowing_interest += interest;
+        self.total_borrowing_interest += interest;
+        self.maturity_timestamp_sec = env::block_timestamp_ms() / 1000 + loan_term.duration_sec;
+        self.term_interest_rate = loan_term.interest_rate;
+        self.assert_collateral_ratio_valid_after_borrow(
+            lend_token_info,
+            lend_token_price,
+            collateral_token_info,
+            collateral_token_price,
+            extra_collateral,
+            borrow_index,
+            min_cr,
+        );
+        interest
     }
 
-    fn update_lending_profit(&mut self, _interest_rate: u64, acc_interest_per_share: &Balance, foundation_commission: u64) -> Balance {
-        let current_deposit = self.get_token_deposit(&self.lend_token_id);
-        self.last_lending_interest_reward_update_timestamp_sec = env::block_timestamp_ms() / 1000;
-        let total_interest_reward = (U256::from(current_deposit)
-            * U256::from(acc_interest_per_share.clone())
-            / U256::from(ACC_INTEREST_PER_SHARE_MULTIPLIER))
-        .as_u128();
-        let commission = self.get_commission(total_interest_reward - self.lending_interest_profit_debt, foundation_commission);
-        self.unpaid_lending_interest_profit +=
-            total_interest_reward - self.lending_interest_profit_debt - commission.clone();
-        self.total_lending_interest_profit +=
-            total_interest_reward - self.lending_interest_profit_debt - commission.clone();
-        self.lending_interest_profit_debt = total_interest_reward;
-        commission
+    pub fn is_term_loan(&self) -> bool {
+        self.maturity_timestamp_sec > 0
     }
 
-    pub fn update_lending_interest_profit_debt(&mut self, acc_interest_per_share: &Balance) {
-        log!(
-            "update_lending_interest_profit_debt account {}",
-            self.owner_id
-        );
-        let current_deposit = self.get_token_deposit(&self.lend_token_id);
-        self.lending_interest_profit_debt = (U256::from(current_deposit)
-            * U256::from(acc_interest_per_share.clone())
-            / U256::from(ACC_INTEREST_PER_SHARE_MULTIPLIER))
-        .as_u128();
-        log!(
-            "update_lending_interest_profit_debt account {} done",
-            self.owner_id
-        );
+    pub fn is_expired(&self, grace_period_sec: u64) -> bool {
+        self.is_term_loan()
+            && env::block_timestamp_ms() / 1000 > self.maturity_timestamp_sec + grace_period_sec
     }
 
     pub fn internal_borrow(
@@ -130,20 +175,20 @@ impl AccountDeposit {
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
-        interest_rate: u64,
+        extra_collateral: Balance,
+        borrow_index: &Balance,
         min_cr: u64,
     ) -> Balance {
-        let deposit_amount = self.get_token_deposit(&self.lend_token_id);
+        let deposit_amount = self.get_free_lend_token_deposit();
         let mut borrow_amount = amount.clone();
         log!("borrow_amount {}", borrow_amount);
         if deposit_amount > 0 {
             if deposit_amount > amount.clone() {
                 borrow_amount = 0;
-                self.deposits
-                    .insert(&self.lend_token_id, &(deposit_amount - amount.clone()));
+                self.reduce_lend_token_deposit(amount.clone());
             } else {
                 borrow_amount = amount.clone() - deposit_amount;
-                self.deposits.insert(&self.lend_token_id, &0u128);
+                self.reduce_lend_token_deposit(deposit_amount);
             }
         }
         log!("borrow_amount actual {}", borrow_amount);
@@ -153,8 +198,8 @@ impl AccountDeposit {
             lend_token_price,
             collateral_token_info,
             collateral_token_price,
-            None,
-            interest_rate,
+            Some(extra_collateral),
+            borrow_index,
             min_cr,
         );
         log!("max_borrowable actual {}", max_borrowable);
@@ -169,7 +214,8 @@ impl AccountDeposit {
             lend_token_price,
             collateral_token_info,
             collateral_token_price,
-            interest_rate,
+            extra_collateral,
+            borrow_index,
             min_cr,
         );
         borrow_amount
@@ -182,15 +228,17 @@ impl AccountDeposit {
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
-        interest_rate: u64,
+        extra_collateral: Balance,
+        borrow_index: &Balance,
         min_cr: u64,
     ) {
-        if self.borrow_amount > 0 {
+        let collateralized_debt = self.get_collateralized_debt(borrow_index);
+        if collateralized_debt > 0 {
             let cr = compute_cr(
-                self.get_token_deposit(&self.collateral_token_id),
+                self.get_token_deposit(&self.collateral_token_id) + extra_collateral,
                 collateral_token_info.decimals,
                 collateral_token_price,
-                self.borrow_amount + self.get_interest_owed(interest_rate),
+                collateralized_debt,
                 lend_token_price,
                 lend_token_info.decimals,
             );
@@ -207,7 +255,7 @@ impl AccountDeposit {
         collateral_amount: Option<Balance>,
         borrow: Option<Balance>,     //borrow more
         pay_amount: Option<Balance>, //pay back
-        interest_rate: u64,
+        borrow_index: &Balance,
     ) -> u64 {
         let collateral_amount = collateral_amount.unwrap_or(0);
         let borrow = borrow.unwrap_or(0);
@@ -216,15 +264,16 @@ impl AccountDeposit {
             self.get_token_deposit(&self.collateral_token_id) + collateral_amount.clone(),
             collateral_token_info.decimals,
             collateral_token_price,
-            self.borrow_amount + self.get_interest_owed(interest_rate) + borrow.clone()
-                - pay_amount.clone(),
+            (self.borrow_amount + self.get_interest_owed(borrow_index) + borrow.clone()
+                - pay_amount.clone())
+            .saturating_sub(self.get_credit_cover()),
             lend_token_price,
             lend_token_info.decimals,
         );
         cr
     }
 
-    //return the amount withdrawn from the lend deposit token or collateral token, excluding interest if have
+    //return the amount withdrawn from the lend deposit token or collateral token
     pub fn internal_withdraw_from_account(
         &mut self,
         token_id: &AccountId,
@@ -233,32 +282,26 @@ impl AccountDeposit {
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
-        interest_rate: u64,
-        _acc_interest_per_share: &Balance,
+        extra_collateral: Balance,
+        borrow_index: &Balance,
         min_cr: u64,
     ) -> Balance {
         let deposit_amount = self.get_token_deposit(token_id);
         if token_id.clone() == self.lend_token_id {
-            let unpaid_lending_interest_profit = self.unpaid_lending_interest_profit;
             require!(
-                amount <= deposit_amount + unpaid_lending_interest_profit.clone(),
+                amount <= deposit_amount,
                 format!(
                     "user has insufficient asset {} for withdrawal",
                     token_id.clone()
                 )
             );
-            let mut remain = amount.clone();
-            if remain >= unpaid_lending_interest_profit {
-                remain = remain - unpaid_lending_interest_profit.clone();
-                self.unpaid_lending_interest_profit = 0;
-                log!("withdrawing {}, {}", deposit_amount, remain);
-                self.deposits
-                    .insert(&self.lend_token_id, &(deposit_amount - remain));
-                return remain;
-            } else {
-                self.unpaid_lending_interest_profit -= remain;
-                return 0;
-            }
+            require!(
+                deposit_amount - amount >= self.delegated_out + self.queued_withdrawal,
+                "deposit backs delegated credit lines or queued withdrawals"
+            );
+            self.deposits
+                .insert(&self.lend_token_id, &(deposit_amount - amount));
+            return amount;
         } else if token_id.clone() == self.collateral_token_id {
             require!(
                 amount <= deposit_amount,
@@ -268,12 +311,13 @@ impl AccountDeposit {
                 )
             );
             let collateral_after_withdrawal = deposit_amount - amount;
-            if self.borrow_amount + self.get_interest_owed(interest_rate) > 0 {
+            let collateralized_debt = self.get_collateralized_debt(borrow_index);
+            if collateralized_debt > 0 {
                 let cr = compute_cr(
-                    collateral_after_withdrawal,
+                    collateral_after_withdrawal + extra_collateral,
                     collateral_token_info.decimals,
                     collateral_token_price,
-                    self.borrow_amount + self.get_interest_owed(interest_rate),
+                    collateralized_debt,
                     lend_token_price,
                     lend_token_info.decimals,
                 );
@@ -291,28 +335,12 @@ impl AccountDeposit {
         }
     }
 
-    //this does not take care of interest when user in a borrowing position
-    // pub fn internal_deposit_lend_token_with_taking_interest(
-    //     &mut self,
-    //     amount: &Balance,
-    //     interest_rate: u64,
-    //     acc_interest_per_share: &Balance,
-    // ) -> Balance {
-    //     if self.borrow_amount > 0 {
-    //         self.update_borrowing_interest(interest_rate);
-    //         self.internal_deposit_lend_token(amount, interest_rate, acc_interest_per_share);
-    //     } else {
-    //         self.internal_deposit_lend_token(amount, interest_rate, acc_interest_per_share);
-    //     }
-    //     amount.clone()
-    // }
-
+    //return the debt paid, interest included, and the amount left over as lend token deposit
     pub fn internal_pay_loan(&mut self, pay_amount: Balance) -> (Balance, Balance) {
         if pay_amount == 0 {
             return (0, 0);
         }
         log!("updating account");
-        let mut actual_borrow_paid = self.borrow_amount.clone();
         let mut remain = pay_amount.clone();
         log!("borrow_amount before remain {}, borrow {}, unpaid_borrowing_interest {}", remain, self.borrow_amount, self.unpaid_borrowing_interest);
         if self.unpaid_borrowing_interest > 0 {
@@ -339,8 +367,8 @@ impl AccountDeposit {
             }
         }
         log!("borrow_amount after remain {}, borrow {}, unpaid_borrowing_interest {}", remain, self.borrow_amount, self.unpaid_borrowing_interest);
-        actual_borrow_paid -= self.borrow_amount;
-        log!("actual_borrow_paid {}", actual_borrow_paid);
+        let debt_paid = pay_amount.clone() - remain.clone();
+        log!("debt_paid {}", debt_paid);
 
         self.reduce_lend_token_deposit(pay_amount.clone());
 
@@ -348,7 +376,12 @@ impl AccountDeposit {
             self.internal_deposit_lend_token(&remain);
         }
 
-        (actual_borrow_paid, remain)
+        if self.get_owed_lend_token_amount() == 0 {
+            self.maturity_timestamp_sec = 0;
+            self.term_interest_rate = 0;
+        }
+
+        (debt_paid, remain)
     }
 
     pub fn reduce_collateral(&mut self, amount: Balance) {
@@ -358,6 +391,12 @@ impl AccountDeposit {
             .insert(&self.collateral_token_id, &(collateral_amount - amount));
     }
 
+    pub fn reduce_extra_collateral(&mut self, token_id: &AssetId, amount: Balance) {
+        let collateral_amount = self.get_token_deposit(token_id);
+        require!(amount <= collateral_amount, "!reduce_collateral");
+        self.deposits.insert(token_id, &(collateral_amount - amount));
+    }
+
     pub fn reduce_lend_token_deposit(&mut self, amount: Balance) {
         let lend_token_deposit = self.get_token_deposit(&self.lend_token_id);
         require!(amount <= lend_token_deposit, "!reduce_lend_token_deposit");
@@ -369,25 +408,34 @@ impl AccountDeposit {
         self.deposits.get(token_id).unwrap_or(0u128)
     }
 
-    pub fn get_deposits(&self) -> HashMap<AssetId, U128> {
+    //lend token deposit not backing credit lines nor waiting in the withdrawal queue
+    pub fn get_free_lend_token_deposit(&self) -> Balance {
+        self.get_token_deposit(&self.lend_token_id)
+            .saturating_sub(self.delegated_out + self.queued_withdrawal)
+    }
+
+    pub fn get_deposits(&self, supply_index: &Balance) -> HashMap<AssetId, U128> {
         let mut ret = HashMap::<AssetId, U128>::new();
         ret.insert(
             self.lend_token_id.clone(),
-            U128(self.get_token_deposit(&self.lend_token_id)),
+            U128(apply_index_growth(
+                self.get_token_deposit(&self.lend_token_id),
+                supply_index,
+                &self.supply_index,
+            )),
         );
         ret.insert(
             self.collateral_token_id.clone(),
             U128(self.get_token_deposit(&self.collateral_token_id)),
         );
+        for (token_id, amount) in self.deposits.iter() {
+            ret.entry(token_id).or_insert(U128(amount));
+        }
         ret
     }
 
-    pub fn get_interest_owed(&self, interest_rate: u64) -> Balance {
-        let mut unpaid_interest = self.unpaid_borrowing_interest;
-        if self.borrow_amount > 0 {
-            unpaid_interest += self.compute_unrecorded_interest(interest_rate);
-        }
-        unpaid_interest
+    pub fn get_interest_owed(&self, borrow_index: &Balance) -> Balance {
+        self.unpaid_borrowing_interest + self.compute_unrecorded_interest(borrow_index)
     }
 
     pub fn compute_max_borrowable(
@@ -397,7 +445,7 @@ impl AccountDeposit {
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
         additional_collateral: Option<Balance>,
-        interest_rate: u64,
+        borrow_index: &Balance,
         cr: u64,
     ) -> Balance {
         let mut collateral_token_deposit = self.get_token_deposit(&self.collateral_token_id);
@@ -415,89 +463,80 @@ impl AccountDeposit {
 
         let mut max_borrowable = max_borrowable.as_u128();
         max_borrowable = max_borrowable * COLLATERAL_RATIO_DIVISOR / (cr as u128);
-        let interest_owed = self.get_interest_owed(interest_rate);
+        let interest_owed = self.get_interest_owed(borrow_index);
 
         let owed = self.borrow_amount + interest_owed;
 
-        if owed >= max_borrowable {
-            return 0u128;
-        }
-        max_borrowable - owed
+        (max_borrowable + self.get_credit_cover()).saturating_sub(owed)
     }
 
-    pub fn get_owed_lend_token_amount(&self) -> Balance {
-        self.borrow_amount + self.unpaid_borrowing_interest
+    //the credit line stops covering debt once expired, the collateral then backs all of it
+    pub fn get_credit_cover(&self) -> Balance {
+        if env::block_timestamp_ms() / 1000 > self.credit_expiry_timestamp_sec {
+            return 0;
+        }
+        self.delegated_in
     }
 
-    pub fn get_pending_unpaid_lending_interest_profit(
-        &self,
-        acc_interest_per_share: &Balance,
-        foundation_commission: u64
-    ) -> Balance {
-        let current_deposit = self.get_token_deposit(&self.lend_token_id);
-
-        let total_interest_reward = (U256::from(current_deposit)
-            * U256::from(acc_interest_per_share.clone())
-            / U256::from(ACC_INTEREST_PER_SHARE_MULTIPLIER))
-        .as_u128();
-        let commission = self.get_commission(total_interest_reward.clone() - self.lending_interest_profit_debt, foundation_commission.clone());
-        let unpaid_lending_interest_profit = self.unpaid_lending_interest_profit
-            + total_interest_reward
-            - self.lending_interest_profit_debt - commission;
-        return unpaid_lending_interest_profit;
+    pub fn get_collateralized_debt(&self, borrow_index: &Balance) -> Balance {
+        (self.borrow_amount + self.get_interest_owed(borrow_index))
+            .saturating_sub(self.get_credit_cover())
     }
 
-    pub fn get_total_interest_reward(&self, acc_interest_per_share: &Balance) -> Balance {
-        let current_deposit = self.get_token_deposit(&self.lend_token_id);
-
-        let total_interest_reward = (U256::from(current_deposit)
-            * U256::from(acc_interest_per_share.clone())
-            / U256::from(ACC_INTEREST_PER_SHARE_MULTIPLIER))
-        .as_u128();
-        self.total_lending_interest_profit + total_interest_reward - self.lending_interest_profit_debt
+    //clears debt without payment, interest first, returns the amount written off
+    pub fn write_off_debt(&mut self, amount: Balance) -> Balance {
+        let from_interest = std::cmp::min(amount, self.unpaid_borrowing_interest);
+        self.unpaid_borrowing_interest -= from_interest;
+        let from_borrow = std::cmp::min(amount - from_interest, self.borrow_amount);
+        self.borrow_amount -= from_borrow;
+        if self.get_owed_lend_token_amount() == 0 {
+            self.maturity_timestamp_sec = 0;
+            self.term_interest_rate = 0;
+        }
+        from_interest + from_borrow
     }
 
-    pub fn get_commission(&self, total: Balance, foundation_commission: u64) -> Balance {
-        (total.clone() - self.lending_interest_profit_debt) * (foundation_commission as u128) / FOUNDATION_COMMISSION_DIVISOR
+    pub fn get_owed_lend_token_amount(&self) -> Balance {
+        self.borrow_amount + self.unpaid_borrowing_interest
     }
 
-    pub fn get_pending_total_lending_interest_profit(
-        &self,
-        acc_interest_per_share: &Balance,
-    ) -> Balance {
-        let current_deposit = self.get_token_deposit(&self.lend_token_id);
-
-        let total_interest_reward = (U256::from(current_deposit)
-            * U256::from(acc_interest_per_share.clone())
-            / U256::from(ACC_INTEREST_PER_SHARE_MULTIPLIER))
-        .as_u128();
-        let total_lending_interest_profit = self.total_lending_interest_profit
-            + total_interest_reward
-            - self.lending_interest_profit_debt;
-        return total_lending_interest_profit;
+    pub fn get_pending_total_lending_interest_profit(&self, supply_index: &Balance) -> Balance {
+        self.total_lending_interest_profit + self.compute_unrecorded_lending_interest(supply_index)
     }
 
-    pub fn get_pending_unpaid_borrowing_interest(&self, interest_rate: u64) -> Balance {
-        let unrecorded = self.compute_unrecorded_interest(interest_rate);
+    pub fn get_pending_unpaid_borrowing_interest(&self, borrow_index: &Balance) -> Balance {
+        let unrecorded = self.compute_unrecorded_interest(borrow_index);
         self.unpaid_borrowing_interest + unrecorded
     }
 
-    pub fn get_pending_total_borrowing_interest(&self, interest_rate: u64) -> Balance {
-        let unrecorded = self.compute_unrecorded_interest(interest_rate);
+    pub fn get_pending_total_borrowing_interest(&self, borrow_index: &Balance) -> Balance {
+        let unrecorded = self.compute_unrecorded_interest(borrow_index);
         self.total_borrowing_interest + unrecorded
     }
 
-    pub fn compute_unrecorded_interest(&self, interest_rate: u64) -> Balance {
-        if self.borrow_amount == 0 {
-            return 0u128;
+    //interest accrued on the debt, unpaid interest included, since the last update
+    //term loans have their interest locked in at borrow time
+    pub fn compute_unrecorded_interest(&self, borrow_index: &Balance) -> Balance {
+        if self.is_term_loan() {
+            return 0;
         }
-        let last_borrowing_interest_update_timestamp_sec =
-            self.last_borrowing_interest_update_timestamp_sec.clone();
-        let current_time_sec = get_next_interest_recal_time_sec();
-        let interest = self.borrow_amount
-            * (((current_time_sec - last_borrowing_interest_update_timestamp_sec) * interest_rate)
-                as u128)
-            / (INTEREST_RATE_DIVISOR * SECONDS_PER_YEAR);
-        interest
+        let owed = self.borrow_amount + self.unpaid_borrowing_interest;
+        apply_index_growth(owed, borrow_index, &self.borrow_index) - owed
+    }
+
+    pub fn get_pending_total_lending_loss(&self, supply_index: &Balance) -> Balance {
+        let current_deposit = self.get_token_deposit(&self.lend_token_id);
+        self.total_lending_loss
+            + current_deposit.saturating_sub(apply_index_growth(
+                current_deposit,
+                supply_index,
+                &self.supply_index,
+            ))
+    }
+
+    pub fn compute_unrecorded_lending_interest(&self, supply_index: &Balance) -> Balance {
+        let current_deposit = self.get_token_deposit(&self.lend_token_id);
+        apply_index_growth(current_deposit, supply_index, &self.supply_index)
+            .saturating_sub(current_deposit)
     }
 }
@@ -0,0 +1,43 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Lets `borrower_id` borrow up to `amount` without collateral until `expiry_timestamp_sec`.
+    /// The delegator's lend token deposit backs the credit line and bears its default.
+    #[payable]
+    pub fn delegate_credit(
+        &mut self,
+        pool_id: u32,
+        borrower_id: AccountId,
+        amount: U128,
+        expiry_timestamp_sec: u64,
+    ) {
+        let account_id = env::predecessor_account_id();
+        self.abort_if_pause();
+        self.abort_if_blacklisted(account_id.clone());
+        self.abort_if_blacklisted(borrower_id.clone());
+        self.abort_if_pool_id_valid(pool_id as usize);
+
+        let prev_storage = env::storage_usage();
+        let pool = &mut self.pools[pool_id as usize];
+        pool.internal_register_account_if_not(&borrower_id);
+        let (token_id, amount_for_foundation) = pool.internal_delegate_credit(
+            &account_id,
+            &borrower_id,
+            amount.0,
+            expiry_timestamp_sec,
+            self.foundation_commission,
+        );
+        self.deposit_foundation(&token_id, &amount_for_foundation);
+        self.verify_storage(&account_id, prev_storage, Some(env::attached_deposit()));
+    }
+
+    pub fn settle_credit_default(&mut self, pool_id: u32, borrower_id: AccountId) {
+        self.abort_if_pause();
+        self.abort_if_pool_id_valid(pool_id as usize);
+        let pool = &mut self.pools[pool_id as usize];
+        let (token_id, amount_for_foundation) =
+            pool.internal_settle_credit_default(&borrower_id, self.foundation_commission);
+        self.deposit_foundation(&token_id, &amount_for_foundation);
+    }
+}
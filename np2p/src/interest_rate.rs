@@ -0,0 +1,87 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::require;
+
+use crate::*;
+
+/// How the borrow rate of a pool follows its utilization.
+/// Rates are per INTEREST_RATE_DIVISOR (1000 = 10% APR), kinks per UTILIZATION_DIVISOR.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum InterestRateModel {
+    /// Same rate at any utilization.
+    Fixed { rate: u64 },
+    /// Rises by `slope1` from 0 to `kink` utilization, then by `slope2` from `kink` to 100%.
+    Kinked {
+        base_rate: u64,
+        slope1: u64,
+        kink: u64,
+        slope2: u64,
+    },
+    /// `multiplier` and `jump_multiplier` are the rates added per 100% of utilization
+    /// below and above `kink`.
+    JumpRate {
+        base_rate: u64,
+        multiplier: u64,
+        kink: u64,
+        jump_multiplier: u64,
+    },
+}
+
+impl InterestRateModel {
+    pub fn assert_valid(&self) {
+        match self {
+            InterestRateModel::Fixed { .. } => {}
+            InterestRateModel::Kinked { kink, .. } => {
+                require!(
+                    *kink > 0 && (*kink as u128) < UTILIZATION_DIVISOR,
+                    "kink must be between 0 and 100%"
+                );
+            }
+            InterestRateModel::JumpRate { kink, .. } => {
+                require!(
+                    (*kink as u128) <= UTILIZATION_DIVISOR,
+                    "kink must not exceed 100%"
+                );
+            }
+        }
+    }
+
+    pub fn compute_borrow_rate(&self, utilization: u64) -> u64 {
+        let utilization = std::cmp::min(utilization as u128, UTILIZATION_DIVISOR);
+        let rate = match self {
+            InterestRateModel::Fixed { rate } => *rate as u128,
+            InterestRateModel::Kinked {
+                base_rate,
+                slope1,
+                kink,
+                slope2,
+            } => {
+                let kink = *kink as u128;
+                if utilization <= kink {
+                    *base_rate as u128 + (*slope1 as u128) * utilization / kink
+                } else {
+                    *base_rate as u128
+                        + *slope1 as u128
+                        + (*slope2 as u128) * (utilization - kink) / (UTILIZATION_DIVISOR - kink)
+                }
+            }
+            InterestRateModel::JumpRate {
+                base_rate,
+                multiplier,
+                kink,
+                jump_multiplier,
+            } => {
+                let kink = *kink as u128;
+                if utilization <= kink {
+                    *base_rate as u128 + (*multiplier as u128) * utilization / UTILIZATION_DIVISOR
+                } else {
+                    *base_rate as u128
+                        + (*multiplier as u128) * kink / UTILIZATION_DIVISOR
+                        + (*jump_multiplier as u128) * (utilization - kink) / UTILIZATION_DIVISOR
+                }
+            }
+        };
+        rate as u64
+    }
+}
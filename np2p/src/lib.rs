@@ -1,11 +1,17 @@
 mod governance;
+mod interest_rate;
+mod loan_market;
+mod migration;
+mod multi_fungible_token;
 mod oracle;
 //mod storage;
 mod storage_impl;
 //mod token_receiver;
 //mod utils;
 mod account_deposit;
+mod credit_delegation;
 mod pool;
+mod pool_owner;
 mod token_receiver;
 mod utils;
 mod views;
@@ -20,11 +26,15 @@ use near_sdk::{
 };
 
 use account_deposit::AccountDeposit;
-use pool::{new_pool_default, Pool};
+use interest_rate::InterestRateModel;
+use loan_market::{Loan, LoanOffer};
+use migration::ContractV1;
+use pool::{new_pool_default, CollateralConfig, LoanTerm, PendingPoolConfig, Pool, PoolStatus, QueuedWithdrawal};
 
 pub type AssetId = AccountId;
 
 use oracle::{Price, PriceData};
+use std::collections::HashMap;
 use std::fmt::Debug;
 
 use views::U256;
@@ -32,12 +42,16 @@ use views::U256;
 const COLLATERAL_RATIO_DIVISOR: u128 = 10000;
 const UTILIZATION_DIVISOR: u128 = 10000;
 const INTEREST_RATE_DIVISOR: u128 = 10000;
-const ACC_INTEREST_PER_SHARE_MULTIPLIER: u128 = 10u128.pow(8 as u32);
+const INTEREST_INDEX_PRECISION: u128 = 10u128.pow(18 as u32);
 const SECONDS_PER_YEAR: u128 = 365 * 86400;
 const LIQUIDATION_BONUS_DIVISOR: u128 = 10000;
 const LIQUIDATION_MARGINAL_DIVISOR: u128 = 10000;
 const FOUNDATION_COMMISSION_DIVISOR: u128 = 10000;
-const INTEREST_RECAL_PERIOD_SEC: u64 = 600; //10 minutes
+const RESERVE_FACTOR_DIVISOR: u128 = 10000;
+const LOAN_LIQUIDATION_BONUS: u64 = 1000;
+const DEFAULT_MATURITY_GRACE_PERIOD_SEC: u64 = 3 * 86400;
+const POOL_CONFIG_NOTICE_PERIOD_SEC: u64 = 3 * 86400;
+const MAX_WITHDRAWAL_FILLS_PER_CALL: usize = 20;
 #[derive(BorshStorageKey, BorshSerialize)]
 enum StorageKey {
     Blacklist,
@@ -50,6 +64,10 @@ enum StorageKey {
     UserStorage,
     AccountDeposit { pool_id: u32, account_id: AccountId },
     Commissions,
+    LoanOffers,
+    Loans,
+    MarketBalances,
+    PoolMigrations,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
@@ -128,6 +146,7 @@ pub struct Contract {
     price_feeder: AccountId,
     foundation_id: AccountId,
     pool_creation_fee: Balance,
+    pool_creation_fees: Commission, //collected in NEAR for the foundation
     pools: Vec<Pool>,
     token_to_list_lend_pools: UnorderedMap<AssetId, Vec<u32>>,
     token_to_list_collateral_pools: UnorderedMap<AssetId, Vec<u32>>,
@@ -141,6 +160,11 @@ pub struct Contract {
     liquidation_marginal: u64, //how mujch in terms of % the treasury got
     foundation_commission: u64,
     commissions: UnorderedMap<AssetId, Commission>,
+    loan_offers: UnorderedMap<u64, LoanOffer>,
+    loans: UnorderedMap<u64, Loan>,
+    next_loan_offer_id: u64,
+    next_loan_id: u64,
+    market_balances: LookupMap<AccountId, HashMap<AssetId, U128>>, //offer market proceeds to withdraw
 }
 
 #[near_bindgen]
@@ -164,6 +188,10 @@ impl Contract {
             token_list: vec![],
             foundation_id: foundation.clone(),
             pool_creation_fee: 10u128.pow(24 as u32) * 10, //10 near to avoid spam
+            pool_creation_fees: Commission {
+                claimed: U128(0),
+                total: U128(0),
+            },
             pools: vec![],
             token_to_list_lend_pools: UnorderedMap::new(StorageKey::TokenToListLendPools),
             token_to_list_collateral_pools: UnorderedMap::new(
@@ -179,6 +207,11 @@ impl Contract {
             liquidation_marginal: 5000,
             foundation_commission: 1000,
             commissions: UnorderedMap::new(StorageKey::Commissions),
+            loan_offers: UnorderedMap::new(StorageKey::LoanOffers),
+            loans: UnorderedMap::new(StorageKey::Loans),
+            next_loan_offer_id: 0,
+            next_loan_id: 0,
+            market_balances: LookupMap::new(StorageKey::MarketBalances),
         };
 
         this.measure_account_storage_usage();
@@ -190,6 +223,20 @@ impl Contract {
         self.foundation_id = account_id;
     }
 
+    /// Sends the pool creation fees collected so far to the foundation.
+    #[payable]
+    pub fn withdraw_pool_creation_fees(&mut self) -> Promise {
+        assert_one_yocto();
+        require!(
+            env::predecessor_account_id() == self.foundation_id,
+            "This method can be called only by the foundation"
+        );
+        let amount = self.pool_creation_fees.total.0 - self.pool_creation_fees.claimed.0;
+        require!(amount > 0, "no pool creation fee to withdraw");
+        self.pool_creation_fees.claimed = self.pool_creation_fees.total.clone();
+        Promise::new(self.foundation_id.clone()).transfer(amount)
+    }
+
     #[payable]
     pub fn provide_storage_for_commissions(&mut self) {
         let prev_storage = env::storage_usage();
@@ -353,8 +400,9 @@ impl Contract {
         }
     }
 
+    /// `term_id` selects one of the pool's loan terms, it must be omitted for pools without terms.
     #[payable]
-    pub fn borrow(&mut self, pool_id: u32, borrow_amount: U128) -> Promise {
+    pub fn borrow(&mut self, pool_id: u32, borrow_amount: U128, term_id: Option<u32>) -> Promise {
         require!(borrow_amount.0 > 0, "borrow_amount > 0");
         let prev_storage = env::storage_usage();
         // Select target account.
@@ -373,6 +421,7 @@ impl Contract {
 
         let collateral_token_info = self.get_token_info(pool.collateral_token_id.clone());
         let collateral_token_price = self.price_data.price(&pool.collateral_token_id.clone());
+        let extra_collateral_prices = self.get_extra_collateral_prices(pool_id);
         {
             let pool = &mut self.pools[pool_id as usize];
             let (_, token_id, amount_for_foundation) = pool.internal_borrow(
@@ -382,6 +431,8 @@ impl Contract {
                 &lend_token_price,
                 &collateral_token_info,
                 &collateral_token_price,
+                &extra_collateral_prices,
+                term_id,
                 self.foundation_commission,
             );
             self.deposit_foundation(&token_id, &amount_for_foundation)
@@ -408,6 +459,11 @@ impl Contract {
         min_lend_token_borrow: Option<U128>,
         fixed_interest_rate: Option<u64>,
         liquidation_bonus: Option<u64>,
+        interest_rate_model: Option<InterestRateModel>,
+        loan_terms: Option<Vec<LoanTerm>>,
+        maturity_grace_period_sec: Option<u64>,
+        reserve_factor: Option<u64>,
+        owner_commission: Option<u64>,
     ) {
         let attached_deposit = env::attached_deposit();
         require!(
@@ -419,13 +475,21 @@ impl Contract {
         let max_utilization = max_utilization.unwrap_or(9000);
         let min_lend_token_deposit = min_lend_token_deposit.unwrap_or(U128(0));
         let min_lend_token_borrow = min_lend_token_borrow.unwrap_or(U128(0));
-        let fixed_interest_rate = fixed_interest_rate.unwrap_or(1000);
+        let interest_rate_model = interest_rate_model.unwrap_or(InterestRateModel::Fixed {
+            rate: fixed_interest_rate.unwrap_or(1000),
+        });
         let liquidation_bonus = liquidation_bonus.unwrap_or(1000);
+        let loan_terms = loan_terms.unwrap_or(vec![]);
+        let maturity_grace_period_sec =
+            maturity_grace_period_sec.unwrap_or(DEFAULT_MATURITY_GRACE_PERIOD_SEC);
+        let reserve_factor = reserve_factor.unwrap_or(0);
+        let owner_commission = owner_commission.unwrap_or(0);
 
         self.abort_if_unsupported_token(lend_token_id.clone());
         self.abort_if_unsupported_token(collateral_token_id.clone());
 
-        //TODO: add up to total pool creation fee
+        self.pool_creation_fees.total =
+            U128(self.pool_creation_fees.total.0 + self.pool_creation_fee);
 
         let account_id = env::predecessor_account_id();
         let prev_storage = env::storage_usage();
@@ -440,8 +504,12 @@ impl Contract {
             max_utilization,
             min_lend_token_deposit.0,
             min_lend_token_borrow.0,
-            fixed_interest_rate,
+            interest_rate_model,
             liquidation_bonus,
+            loan_terms,
+            maturity_grace_period_sec,
+            reserve_factor,
+            owner_commission,
         );
         pool.internal_register_account_if_not(&account_id);
         pool.internal_register_account_if_not(&self.foundation_id);
@@ -491,6 +559,7 @@ impl Contract {
 
         let collateral_token_info = self.get_token_info(pool.collateral_token_id.clone());
         let collateral_token_price = self.price_data.price(&pool.collateral_token_id.clone());
+        let extra_collateral_prices = self.get_extra_collateral_prices(pool_id);
         {
             let pool = &mut self.pools[pool_id as usize];
             let (token_id, amount_for_foundation) = pool.internal_withdraw_from_account(
@@ -501,6 +570,7 @@ impl Contract {
                 &lend_token_price,
                 &collateral_token_info,
                 &collateral_token_price,
+                &extra_collateral_prices,
                 self.foundation_commission,
             );
             self.deposit_foundation(&token_id, &amount_for_foundation);
@@ -509,6 +579,50 @@ impl Contract {
         self.internal_send_tokens(pool_id, &token_id, &account_id, amount.0)
     }
 
+    /// Queues a lend token withdrawal when the pool lacks liquidity. Repayments and new
+    /// deposits fill the queue in order, filled amounts are sent by `claim_withdrawal`.
+    /// The queued amount stays in the deposit until filled.
+    #[payable]
+    pub fn queue_withdrawal(&mut self, pool_id: u32, amount: U128) {
+        let account_id = env::predecessor_account_id();
+        self.abort_if_pause();
+        self.abort_if_blacklisted(account_id.clone());
+        self.abort_if_pool_id_valid(pool_id.clone() as usize);
+        let prev_storage = env::storage_usage();
+        {
+            let pool = &mut self.pools[pool_id as usize];
+            let (token_id, amount_for_foundation) =
+                pool.internal_queue_withdrawal(&account_id, amount.0, self.foundation_commission);
+            self.deposit_foundation(&token_id, &amount_for_foundation);
+        }
+        self.verify_storage(&account_id, prev_storage, Some(env::attached_deposit()));
+    }
+
+    #[payable]
+    pub fn claim_withdrawal(&mut self, pool_id: u32) -> Promise {
+        let account_id = env::predecessor_account_id();
+        assert_one_yocto();
+        self.abort_if_pause();
+        self.abort_if_blacklisted(account_id.clone());
+        self.abort_if_pool_id_valid(pool_id.clone() as usize);
+        let pool = &mut self.pools[pool_id as usize];
+        let token_id = pool.lend_token_id.clone();
+        let amount = pool.internal_claim_withdrawal(&account_id);
+        self.internal_send_tokens(pool_id, &token_id, &account_id, amount)
+    }
+
+    /// Fills the next queued withdrawals with the liquidity left, anyone can call it
+    /// when the queue is longer than what a deposit or repayment fills.
+    pub fn fill_withdrawal_queue(&mut self, pool_id: u32) {
+        self.abort_if_pause();
+        self.abort_if_pool_id_valid(pool_id.clone() as usize);
+        let pool = &mut self.pools[pool_id as usize];
+        let amount_for_foundation = pool.update_indexes(self.foundation_commission);
+        pool.fill_withdrawal_queue();
+        let token_id = pool.lend_token_id.clone();
+        self.deposit_foundation(&token_id, &amount_for_foundation);
+    }
+
     #[payable]
     pub fn pay_loan(&mut self, pool_id: u32, amount: U128) {
         let account_id = env::predecessor_account_id();
@@ -541,6 +655,7 @@ impl Contract {
 
         let collateral_token_info = self.get_token_info(pool.collateral_token_id.clone());
         let collateral_token_price = self.price_data.price(&pool.collateral_token_id.clone());
+        let extra_collateral_prices = self.get_extra_collateral_prices(pool_id);
         let withdrawn_amount: Balance;
         {
             let pool = &mut self.pools[pool_id as usize];
@@ -556,6 +671,7 @@ impl Contract {
                 &lend_token_price,
                 &collateral_token_info,
                 &collateral_token_price,
+                &extra_collateral_prices,
                 self.foundation_commission
             );
             withdrawn_amount = w;
@@ -591,6 +707,7 @@ impl Contract {
 
         let collateral_token_info = self.get_token_info(pool.collateral_token_id.clone());
         let collateral_token_price = self.price_data.price(&pool.collateral_token_id.clone());
+        let extra_collateral_prices = self.get_extra_collateral_prices(pool_id);
         let withdrawn_amount: Balance;
         {
             let pool = &mut self.pools[pool_id as usize];
@@ -602,6 +719,7 @@ impl Contract {
                 &lend_token_price,
                 &collateral_token_info,
                 &collateral_token_price,
+                &extra_collateral_prices,
                 self.foundation_commission
             );
             withdrawn_amount = w;
@@ -620,12 +738,14 @@ impl Contract {
         }
     }
 
+    /// `collateral_token_id` is the collateral to seize, the pool collateral token by default.
     #[payable]
     pub fn liquidate(
         &mut self,
         pool_id: u32,
         liquidated_account_id: AccountId,
         liquidated_borrow_amount: U128,
+        collateral_token_id: Option<AssetId>,
     ) {
         let prev_usage = env::storage_usage();
         let liquidator_account_id = env::predecessor_account_id();
@@ -642,16 +762,20 @@ impl Contract {
 
         let collateral_token_info = self.get_token_info(pool.collateral_token_id.clone());
         let collateral_token_price = self.price_data.price(&pool.collateral_token_id.clone());
+        let extra_collateral_prices = self.get_extra_collateral_prices(pool_id);
+        let seized_token_id = collateral_token_id.unwrap_or(collateral_token_info.token_id.clone());
         {
             let pool = &mut self.pools[pool_id as usize];
             let ret = pool.internal_liquidate(
                 liquidated_account_id.clone(),
                 liquidated_borrow_amount.0,
                 liquidator_account_id.clone(),
+                &seized_token_id,
                 &lend_token_info,
                 &lend_token_price,
                 &collateral_token_info,
                 &collateral_token_price,
+                &extra_collateral_prices,
                 self.liquidation_marginal,
                 self.foundation_commission
             );
@@ -668,6 +792,16 @@ impl Contract {
         );
     }
 
+    /// Can be called by anyone once liquidations took all the collateral of an account in debt.
+    pub fn write_off_bad_debt(&mut self, pool_id: u32, account_id: AccountId) {
+        self.abort_if_pause();
+        self.abort_if_pool_id_valid(pool_id.clone() as usize);
+        let pool = &mut self.pools[pool_id as usize];
+        let (token_id, amount_for_foundation) =
+            pool.internal_write_off_bad_debt(&account_id, self.foundation_commission);
+        self.deposit_foundation(&token_id, &amount_for_foundation);
+    }
+
     pub fn contract_status(&self) -> ContractStatus {
         self.status.clone()
     }
@@ -676,39 +810,15 @@ impl Contract {
         format!("{}:{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
     }
 
-    /// This is NOOP implementation. KEEP IT if you haven't changed contract state.
-    /// Should only be called by this contract on migration.
+    /// Migrates the state from the fixed interest rate version. Should only be called by this contract on migration.
     /// This method is called from `upgrade()` method.
+    /// The contract is paused until governance converts the account deposits with `migrate_account_deposits`.
     /// For next version upgrades, change this function.
     #[init(ignore_state)]
     #[private]
     pub fn migrate() -> Self {
-        let this: Contract = env::state_read().expect("Contract is not initialized");
-        // Contract {
-        //     governance: old_data.governance.clone(),
-        //     black_list: old_data.black_list,
-        //     status: old_data.status,
-        //     supported_tokens: old_data.supported_tokens,
-        //     price_data: old_data.price_data,
-        //     price_feeder: old_data.price_feeder,
-        //     token_list: old_data.token_list,
-        //     foundation_id: old_data.foundation_id,
-        //     pool_creation_fee: old_data.pool_creation_fee, //10 near to avoid spam
-        //     pools: old_data.pools,
-        //     token_to_list_lend_pools: old_data.token_to_list_lend_pools,
-        //     token_to_list_collateral_pools: old_data.token_to_list_collateral_pools,
-        //     created_pools: old_data.created_pools,
-        //     deposited_pools: old_data.deposited_pools,
-        //     borrow_pools: old_data.borrow_pools,
-        //     storage_accounts: old_data.storage_accounts,
-        //     storage_usage_add_pool: old_data.storage_usage_add_pool,
-        //     storage_usage_join_pool: old_data.storage_usage_join_pool,
-        //     account_list: old_data.account_list,
-        //     liquidation_marginal: old_data.liquidation_marginal,
-        //     foundation_commission: 1000,
-        //     commissions: UnorderedMap::new(StorageKey::Commissions)
-        // }
-        this
+        let old_data: ContractV1 = env::state_read().expect("Contract is not initialized");
+        Contract::from_v1(old_data)
     }
 
     fn abort_if_pause(&self) {
@@ -737,6 +847,20 @@ impl Contract {
 }
 
 impl Contract {
+    //token info and price of the pool extra collaterals, in the order of `extra_collaterals`
+    pub(crate) fn get_extra_collateral_prices(&self, pool_id: u32) -> Vec<(TokenInfo, Price)> {
+        self.pools[pool_id as usize]
+            .extra_collaterals
+            .iter()
+            .map(|c| {
+                (
+                    self.get_token_info(c.token_id.clone()),
+                    self.price_data.price(&c.token_id),
+                )
+            })
+            .collect()
+    }
+
     fn deposit_foundation(&mut self, token_id: &AssetId, amount: &Balance) {
         let mut current = self.commissions.get(token_id).unwrap_or(Commission {
             claimed: U128(0),
@@ -879,7 +1003,7 @@ mod tests {
     //use near_sdk_sim::to_yocto;
 
     use super::*;
-    use token_receiver::TokenReceiverMessage;
+    use token_receiver::{LoanMessage, TokenReceiverMessage};
     const ONE_NEAR: Balance = 10u128.pow(24);
 
     fn get_account(id: u32) -> AccountId {
@@ -980,6 +1104,15 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            );
+            assert_eq!(
+                contract.get_pool_creation_fees().total.0,
+                (i as u128 + 1) * 10 * ONE_NEAR
             );
 
             for j in 1..3 {
@@ -1031,4 +1164,755 @@ mod tests {
             }
         }
     }
+    #[test]
+    fn interest_rate_models() {
+        let kinked = InterestRateModel::Kinked {
+            base_rate: 200,
+            slope1: 400,
+            kink: 8000,
+            slope2: 6000,
+        };
+        assert_eq!(kinked.compute_borrow_rate(0), 200);
+        assert_eq!(kinked.compute_borrow_rate(4000), 400);
+        assert_eq!(kinked.compute_borrow_rate(8000), 600);
+        assert_eq!(kinked.compute_borrow_rate(9000), 3600);
+        assert_eq!(kinked.compute_borrow_rate(10000), 6600);
+
+        let jump_rate = InterestRateModel::JumpRate {
+            base_rate: 200,
+            multiplier: 1000,
+            kink: 8000,
+            jump_multiplier: 20000,
+        };
+        assert_eq!(jump_rate.compute_borrow_rate(5000), 700);
+        assert_eq!(jump_rate.compute_borrow_rate(9000), 3000);
+        assert_eq!(jump_rate.compute_borrow_rate(12000), 5000);
+    }
+    #[test]
+    fn interest_indexes_accrue_per_second() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.total_lend_asset_deposit = 1_000_000_000;
+        pool.total_borrow = 500_000_000;
+
+        //10% APR for one year, 10% commission
+        testing_env!(context
+            .block_timestamp(SECONDS_PER_YEAR as u64 * 1_000_000_000)
+            .build());
+        let commission = pool.update_indexes(1000);
+        assert_eq!(pool.total_borrow, 550_000_000);
+        assert_eq!(commission, 5_000_000);
+        assert_eq!(pool.total_lend_asset_deposit, 1_045_000_000);
+        assert_eq!(pool.borrow_index, INTEREST_INDEX_PRECISION * 11 / 10);
+
+        //a borrower that repays one second later owes one second more interest
+        testing_env!(context
+            .block_timestamp((SECONDS_PER_YEAR as u64 + 1) * 1_000_000_000)
+            .build());
+        let (borrow_index, _, interest, _) = pool.compute_current_indexes(1000);
+        assert!(borrow_index > pool.borrow_index);
+        assert!(interest > 0);
+    }
+
+    #[test]
+    fn term_loans_stop_accruing_and_expire() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut account_deposit =
+            AccountDeposit::new(0, get_account(0), get_account(3), get_account(4));
+        account_deposit.borrow_amount = 1_000_000_000;
+        account_deposit.unpaid_borrowing_interest = 100_000_000;
+        account_deposit.maturity_timestamp_sec = 30 * 86400;
+        account_deposit.term_interest_rate = 1000;
+
+        //locked-in interest does not grow with the borrow index
+        let borrow_index = INTEREST_INDEX_PRECISION * 2;
+        assert_eq!(account_deposit.compute_unrecorded_interest(&borrow_index), 0);
+        assert!(account_deposit.is_term_loan());
+
+        testing_env!(context
+            .block_timestamp((30 * 86400 + DEFAULT_MATURITY_GRACE_PERIOD_SEC) * 1_000_000_000)
+            .build());
+        assert!(!account_deposit.is_expired(DEFAULT_MATURITY_GRACE_PERIOD_SEC));
+        testing_env!(context
+            .block_timestamp((30 * 86400 + DEFAULT_MATURITY_GRACE_PERIOD_SEC + 1) * 1_000_000_000)
+            .build());
+        assert!(account_deposit.is_expired(DEFAULT_MATURITY_GRACE_PERIOD_SEC));
+    }
+
+    #[test]
+    fn term_interest_accrues_until_maturity() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.total_lend_asset_deposit = 1_000_000_000;
+        pool.total_term_borrow = 100_001_050;
+        pool.add_term_interest(1_050, 100, 100);
+        assert_eq!(pool.total_lend_asset_deposit, 1_000_000_000);
+
+        //lenders earn 10 per second, the rounding remainder comes at maturity
+        testing_env!(context.block_timestamp(50 * 1_000_000_000).build());
+        assert_eq!(pool.update_indexes(0), 0);
+        assert_eq!(pool.total_lend_asset_deposit, 1_000_000_500);
+        testing_env!(context.block_timestamp(200 * 1_000_000_000).build());
+        pool.update_indexes(0);
+        assert_eq!(pool.total_lend_asset_deposit, 1_000_001_050);
+        assert_eq!(pool.total_borrow, 0);
+        assert_eq!(pool.total_term_borrow, 100_001_050);
+        assert_eq!(pool.term_interest_per_sec, 0);
+        assert!(pool.term_maturities.is_empty());
+    }
+
+    #[test]
+    fn pool_config_applies_after_notice_period() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.propose_config(
+            Some(16000),
+            None,
+            None,
+            None,
+            None,
+            Some(InterestRateModel::Fixed { rate: 500 }),
+            None,
+            None,
+        );
+        assert_eq!(pool.min_cr, 15000);
+        assert_eq!(pool.max_utilization, 9000);
+
+        testing_env!(context
+            .block_timestamp(POOL_CONFIG_NOTICE_PERIOD_SEC * 1_000_000_000)
+            .build());
+        pool.apply_pending_config(1000);
+        assert_eq!(pool.min_cr, 16000);
+        assert_eq!(pool.max_utilization, 9000);
+        assert_eq!(pool.get_current_borrow_rate(), 500);
+        assert!(pool.pending_config.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "min_cr can only be increased")]
+    fn pool_min_cr_cannot_be_lowered() {
+        let context = VMContextBuilder::new();
+        testing_env!(context.build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.propose_config(Some(14000), None, None, None, None, None, None, None);
+    }
+
+    #[test]
+    fn extra_collateral_changes_follow_the_pool_config() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.propose_extra_collateral(&get_account(5), 20000, 500);
+        //the pool parameters proposed next keep the pending collateral
+        pool.propose_config(Some(16000), None, None, None, None, None, None, None);
+        testing_env!(context
+            .block_timestamp(POOL_CONFIG_NOTICE_PERIOD_SEC * 1_000_000_000)
+            .build());
+        pool.apply_pending_config(0);
+        assert_eq!(pool.min_cr, 16000);
+        assert_eq!(pool.extra_collaterals[0].min_cr, 20000);
+
+        pool.propose_extra_collateral_update(&get_account(5), 22000, 300);
+        assert_eq!(pool.extra_collaterals[0].min_cr, 20000);
+        pool.internal_register_account_if_not(&get_account(2));
+        pool.internal_deposit(&get_account(2), &get_account(5), 2000, 0);
+        testing_env!(context
+            .block_timestamp(2 * POOL_CONFIG_NOTICE_PERIOD_SEC * 1_000_000_000)
+            .build());
+        pool.apply_pending_config(0);
+        assert_eq!(pool.extra_collaterals[0].min_cr, 22000);
+        assert_eq!(pool.extra_collaterals[0].total_deposit.0, 2000);
+
+        pool.propose_extra_collateral(&get_account(6), 16000, 500);
+        pool.remove_extra_collateral(&get_account(6));
+        assert!(pool.pending_config.unwrap().extra_collaterals.len() == 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "extra collateral min_cr below the pool min_cr")]
+    fn extra_collateral_min_cr_not_below_pool_min_cr() {
+        let context = VMContextBuilder::new();
+        testing_env!(context.build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.propose_extra_collateral(&get_account(5), 10100, 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "collateral token still deposited")]
+    fn deposited_extra_collateral_cannot_be_removed() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.propose_extra_collateral(&get_account(5), 20000, 500);
+        testing_env!(context
+            .block_timestamp(POOL_CONFIG_NOTICE_PERIOD_SEC * 1_000_000_000)
+            .build());
+        pool.apply_pending_config(0);
+        pool.internal_register_account_if_not(&get_account(2));
+        pool.internal_deposit(&get_account(2), &get_account(5), 2000, 0);
+        pool.remove_extra_collateral(&get_account(5));
+    }
+
+    #[test]
+    fn restricted_pool_only_accepts_allowed_accounts() {
+        let context = VMContextBuilder::new();
+        testing_env!(context.build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.restrict_lenders = true;
+        assert!(pool.is_allowed_lender(&get_account(0)));
+        assert!(!pool.is_allowed_lender(&get_account(1)));
+        assert!(pool.is_allowed_borrower(&get_account(1)));
+
+        pool.allowed_lenders.insert(&get_account(1));
+        assert!(pool.is_allowed_lender(&get_account(1)));
+        pool.assert_can_deposit(&get_account(1), &get_account(3), 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "not an allowed borrower")]
+    fn restricted_pool_rejects_unlisted_collateral() {
+        let context = VMContextBuilder::new();
+        testing_env!(context.build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.restrict_borrowers = true;
+        pool.assert_can_deposit(&get_account(1), &get_account(4), 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "not an allowed lender")]
+    fn restricted_pool_only_takes_repayments_from_unlisted_borrowers() {
+        let context = VMContextBuilder::new();
+        testing_env!(context.build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.restrict_lenders = true;
+        pool.internal_register_account_if_not(&get_account(1));
+        let mut account_deposit = pool.get_account_deposit(&get_account(1));
+        account_deposit.borrow_amount = 100;
+        pool.account_deposits.insert(&get_account(1), &account_deposit);
+
+        pool.assert_can_deposit(&get_account(1), &get_account(4), 100);
+        pool.assert_can_deposit(&get_account(1), &get_account(3), 100);
+        pool.assert_can_deposit(&get_account(1), &get_account(3), 101);
+    }
+
+    #[test]
+    fn credit_default_is_charged_to_delegator() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.internal_register_account_if_not(&get_account(1));
+        pool.internal_register_account_if_not(&get_account(2));
+        pool.internal_deposit(&get_account(1), &get_account(3), 1000, 0);
+        pool.internal_delegate_credit(&get_account(1), &get_account(2), 500, 100, 0);
+
+        let mut borrower = pool.get_account_deposit(&get_account(2));
+        assert_eq!(borrower.get_collateralized_debt(&pool.borrow_index), 0);
+        borrower.borrow_amount = 400;
+        assert_eq!(borrower.get_collateralized_debt(&pool.borrow_index), 0);
+        pool.account_deposits.insert(&get_account(2), &borrower);
+        pool.total_borrow = 400;
+
+        testing_env!(context.block_timestamp(101 * 1_000_000_000).build());
+        pool.internal_settle_credit_default(&get_account(2), 0);
+        let borrower = pool.get_account_deposit(&get_account(2));
+        let delegator = pool.get_account_deposit(&get_account(1));
+        assert_eq!(borrower.get_owed_lend_token_amount(), 0);
+        assert_eq!(borrower.delegated_in, 0);
+        assert_eq!(delegator.delegated_out, 0);
+        assert_eq!(pool.total_borrow, 0);
+        assert_eq!(
+            delegator.get_token_deposit(&get_account(3)),
+            pool.total_lend_asset_deposit
+        );
+    }
+
+    #[test]
+    fn lend_receipts_transfer_with_interest() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.internal_register_account_if_not(&get_account(1));
+        pool.share_register(&get_account(2));
+        pool.internal_deposit(&get_account(1), &get_account(3), 1000, 0);
+        pool.total_borrow = 1000;
+
+        testing_env!(context
+            .block_timestamp(SECONDS_PER_YEAR as u64 * 1_000_000_000)
+            .build());
+        assert_eq!(pool.share_total_balance(0), 1100);
+        pool.internal_share_transfer(&get_account(1), &get_account(2), 600, 0);
+        assert_eq!(
+            pool.get_account_deposit(&get_account(1))
+                .get_token_deposit(&get_account(3)),
+            500
+        );
+        assert_eq!(
+            pool.get_account_deposit(&get_account(2))
+                .get_token_deposit(&get_account(3)),
+            600
+        );
+        assert_eq!(pool.share_total_balance(0), 1100);
+        //shares queued for withdrawal cannot be moved back
+        pool.internal_queue_withdrawal(&get_account(2), 400, 0);
+        assert_eq!(pool.get_free_lend_token_deposit(&get_account(2), 0), 200);
+    }
+
+    #[test]
+    fn withdrawal_queue_fills_in_order() {
+        let context = VMContextBuilder::new();
+        testing_env!(context.build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.internal_register_account_if_not(&get_account(1));
+        pool.internal_register_account_if_not(&get_account(2));
+        pool.internal_deposit(&get_account(1), &get_account(3), 1000, 0);
+        pool.total_borrow = 1000;
+
+        pool.internal_queue_withdrawal(&get_account(1), 300, 0);
+        assert_eq!(pool.total_queued_withdrawal, 300);
+        assert_eq!(pool.get_utilization(), UTILIZATION_DIVISOR as u64);
+
+        pool.internal_deposit(&get_account(2), &get_account(3), 200, 0);
+        let lender = pool.get_account_deposit(&get_account(1));
+        assert_eq!(lender.claimable_withdrawal, 200);
+        assert_eq!(lender.queued_withdrawal, 100);
+        assert_eq!(pool.withdrawal_queue.len(), 1);
+
+        assert_eq!(pool.internal_claim_withdrawal(&get_account(1)), 200);
+        assert_eq!(pool.total_claimable_withdrawal, 0);
+    }
+
+    #[test]
+    fn queued_withdrawals_share_bad_debt() {
+        let context = VMContextBuilder::new();
+        testing_env!(context.build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.internal_register_account_if_not(&get_account(1));
+        pool.internal_deposit(&get_account(1), &get_account(3), 1000, 0);
+        pool.total_borrow = 1000;
+        pool.internal_queue_withdrawal(&get_account(1), 800, 0);
+        assert_eq!(pool.total_lend_asset_deposit, 1000);
+
+        //lenders lose half of their deposits, the queued amount included
+        pool.supply_index /= 2;
+        pool.total_lend_asset_deposit = 500;
+        pool.total_borrow = 0;
+        pool.fill_withdrawal_queue();
+        let lender = pool.get_account_deposit(&get_account(1));
+        assert_eq!(lender.claimable_withdrawal, 500);
+        assert_eq!(lender.queued_withdrawal, 0);
+        assert_eq!(lender.get_token_deposit(&get_account(3)), 0);
+        assert!(pool.withdrawal_queue.is_empty());
+        assert_eq!(pool.total_queued_withdrawal, 0);
+        assert_eq!(pool.total_lend_asset_deposit, 0);
+    }
+
+    #[test]
+    fn bad_debt_is_covered_by_reserve_then_lenders() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.reserve_factor = 1000;
+        pool.internal_register_account_if_not(&get_account(1));
+        pool.internal_register_account_if_not(&get_account(2));
+        pool.internal_deposit(&get_account(1), &get_account(3), 1000, 0);
+        let mut borrower = pool.get_account_deposit(&get_account(2));
+        borrower.borrow_amount = 500;
+        borrower.borrow_index = pool.borrow_index;
+        pool.account_deposits.insert(&get_account(2), &borrower);
+        pool.total_borrow = 500;
+
+        //10% APR for one year, 10% of the interest goes to the reserve
+        testing_env!(context
+            .block_timestamp(SECONDS_PER_YEAR as u64 * 1_000_000_000)
+            .build());
+        pool.internal_write_off_bad_debt(&get_account(2), 0);
+        assert_eq!(pool.total_bad_debt, 550);
+        assert_eq!(pool.reserve, 0);
+        assert_eq!(pool.total_borrow, 0);
+        assert_eq!(pool.total_lend_asset_deposit, 500);
+
+        pool.internal_deposit(&get_account(1), &get_account(3), 100, 0);
+        let lender = pool.get_account_deposit(&get_account(1));
+        assert_eq!(lender.get_token_deposit(&get_account(3)), 600);
+        assert_eq!(lender.total_lending_loss, 500);
+    }
+
+    #[test]
+    fn loan_offer_messages_and_interest() {
+        let msg = serde_json::from_str::<TokenReceiverMessage>(
+            "{\"TakeLoanOffer\": {\"offer_id\": 3, \"borrow_amount\": \"100\"}}",
+        )
+        .unwrap();
+        assert!(matches!(
+            msg,
+            TokenReceiverMessage::LoanMarket(LoanMessage::TakeLoanOffer { offer_id: 3, .. })
+        ));
+        let msg =
+            serde_json::from_str::<TokenReceiverMessage>("{\"LiquidateLoan\": {\"loan_id\": 1}}")
+                .unwrap();
+        assert!(matches!(
+            msg,
+            TokenReceiverMessage::LoanMarket(LoanMessage::LiquidateLoan { loan_id: 1 })
+        ));
+        let msg = serde_json::from_str::<TokenReceiverMessage>("{\"pool_id\": 1}").unwrap();
+        assert!(matches!(msg, TokenReceiverMessage::Deposit { pool_id: 1 }));
+
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut loan = loan_market::Loan {
+            loan_id: 0,
+            offer_id: 0,
+            lender_id: get_account(1),
+            borrower_id: get_account(2),
+            lend_token_id: get_account(3),
+            collateral_token_id: get_account(4),
+            principal: U128(1000),
+            collateral_amount: U128(2000),
+            interest_rate: 1000,
+            min_cr: 15000,
+            unpaid_interest: U128(0),
+            last_interest_timestamp_sec: 0,
+            maturity_timestamp_sec: SECONDS_PER_YEAR as u64,
+        };
+        testing_env!(context
+            .block_timestamp(SECONDS_PER_YEAR as u64 / 2 * 1_000_000_000)
+            .build());
+        assert_eq!(loan.get_owed(), 1050);
+        assert_eq!(loan.pay(300), (250, 50));
+        assert_eq!(loan.get_owed(), 750);
+        assert!(!loan.is_defaulted());
+    }
+
+    #[test]
+    fn loan_settles_without_borrower_storage_left() {
+        let (mut context, mut contract) = setup_contract(get_account(0), get_account(1), get_account(2));
+        add_supported_tokens(&mut context, &mut contract);
+        let price_data = serde_json::from_str::<PriceData>(
+            "{\"timestamp\": \"0\", \"recency_duration_sec\": 4294967295, \"prices\": [\
+            {\"asset_id\": \"id-3\", \"price\": {\"multiplier\": \"100000000\", \"decimals\": 8}},\
+            {\"asset_id\": \"id-4\", \"price\": {\"multiplier\": \"100000000\", \"decimals\": 8}}]}",
+        )
+        .unwrap();
+        testing_env!(context.predecessor_account_id(get_account(2)).attached_deposit(ONE_NEAR).build());
+        contract.push_price_data(price_data);
+        for i in 5..7 {
+            testing_env!(context.predecessor_account_id(get_account(i)).attached_deposit(ONE_NEAR).build());
+            contract.storage_deposit(None, None);
+        }
+
+        testing_env!(context.predecessor_account_id(get_account(3)).attached_deposit(0).build());
+        let message = TokenReceiverMessage::LoanMarket(LoanMessage::PostLoanOffer {
+            collateral_token_id: get_account(4),
+            interest_rate: 1000,
+            min_cr: 15000,
+            duration_sec: 86400,
+        });
+        contract.ft_on_transfer(get_account(5), U128(1000 * 10u128.pow(8)), serde_json::to_string(&message).unwrap());
+        testing_env!(context.predecessor_account_id(get_account(4)).build());
+        let message = TokenReceiverMessage::LoanMarket(LoanMessage::TakeLoanOffer {
+            offer_id: 0,
+            borrow_amount: U128(1000 * 10u128.pow(8)),
+        });
+        contract.ft_on_transfer(get_account(6), U128(2000 * 10u128.pow(18)), serde_json::to_string(&message).unwrap());
+        //the balance entries are paid with the loan, the borrower has no storage deposit left
+        let mut storage_account = contract.storage_accounts.get(&get_account(6)).unwrap();
+        storage_account.near_amount = contract.compute_storage_usage_near(get_account(6)).0;
+        contract.storage_accounts.insert(&get_account(6), &storage_account);
+
+        testing_env!(context
+            .predecessor_account_id(get_account(5))
+            .attached_deposit(1)
+            .block_timestamp((86400 + DEFAULT_MATURITY_GRACE_PERIOD_SEC + 1) * 1_000_000_000)
+            .build());
+        contract.foreclose_loan(0);
+        assert!(contract.get_loan(0).is_none());
+        assert!(contract.get_market_balances(get_account(6)).get(&get_account(4)).unwrap().0 > 0);
+    }
+    #[test]
+    fn max_liquidatable_borrow_keeps_account_under_min_cr() {
+        let context = VMContextBuilder::new();
+        testing_env!(context.build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        let lend_token_info = TokenInfo::new(get_account(3), 6);
+        let collateral_token_info = TokenInfo::new(get_account(4), 6);
+        let price = Price {
+            multiplier: U128(100000000),
+            decimals: 8,
+        };
+        pool.internal_register_account_if_not(&get_account(1));
+        pool.internal_register_account_if_not(&get_account(2));
+        pool.internal_deposit(&get_account(1), &get_account(3), 10000, 0);
+        pool.internal_deposit(&get_account(2), &get_account(4), 1400, 0);
+        let mut borrower = pool.get_account_deposit(&get_account(2));
+        borrower.borrow_amount = 1000;
+        borrower.borrow_index = pool.borrow_index;
+        pool.account_deposits.insert(&get_account(2), &borrower);
+        pool.total_borrow = 1000;
+
+        let max_repay = pool.compute_max_liquidatable_borrow(
+            &get_account(2),
+            &get_account(4),
+            &lend_token_info,
+            &price,
+            &collateral_token_info,
+            &price,
+            &vec![],
+        );
+        assert_eq!(max_repay, 248);
+        let (seized, to_liquidator, to_foundation) = utils::compute_liquidation_amounts(
+            max_repay,
+            &lend_token_info,
+            &price,
+            &collateral_token_info,
+            &price,
+            pool.liquidation_bonus,
+            5000,
+        );
+        assert_eq!(seized, to_liquidator + to_foundation);
+        let mut borrower = pool.get_account_deposit(&get_account(2));
+        borrower.reduce_collateral(seized);
+        borrower.borrow_amount -= max_repay;
+        pool.account_deposits.insert(&get_account(2), &borrower);
+        let cr = pool.compute_current_cr(
+            get_account(2),
+            &lend_token_info,
+            &price,
+            &collateral_token_info,
+            &price,
+            &vec![],
+            None,
+            None,
+            None,
+        );
+        assert!(cr < pool.min_cr);
+        assert!(cr > 14900);
+    }
+
+    #[test]
+    fn extra_collateral_adds_borrowing_capacity() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.propose_extra_collateral(&get_account(5), 20000, 500);
+        assert!(pool.extra_collaterals.is_empty());
+        testing_env!(context
+            .block_timestamp(POOL_CONFIG_NOTICE_PERIOD_SEC * 1_000_000_000)
+            .build());
+        pool.apply_pending_config(0);
+        let lend_token_info = TokenInfo::new(get_account(3), 6);
+        let collateral_token_info = TokenInfo::new(get_account(4), 6);
+        let price = Price {
+            multiplier: U128(100000000),
+            decimals: 8,
+        };
+        let mut extra_collateral_prices = vec![(TokenInfo::new(get_account(5), 6), price.clone())];
+        pool.internal_register_account_if_not(&get_account(1));
+        pool.internal_register_account_if_not(&get_account(2));
+        pool.internal_deposit(&get_account(1), &get_account(3), 10000, 0);
+        pool.internal_deposit(&get_account(2), &get_account(4), 1500, 0);
+        pool.internal_deposit(&get_account(2), &get_account(5), 2000, 0);
+        assert_eq!(pool.extra_collaterals[0].total_deposit.0, 2000);
+
+        //1500 + 2000 * 15000 / 20000 of collateral at min_cr 150%
+        assert_eq!(
+            pool.compute_max_borrowable_for_account(
+                &get_account(2),
+                &lend_token_info,
+                &price,
+                &collateral_token_info,
+                &price,
+                &extra_collateral_prices,
+                None,
+            ),
+            2000
+        );
+        pool.internal_borrow(
+            &get_account(2),
+            &2000,
+            &lend_token_info,
+            &price,
+            &collateral_token_info,
+            &price,
+            &extra_collateral_prices,
+            None,
+            0,
+        );
+
+        //the extra collateral halves in price, all of it can be seized
+        extra_collateral_prices[0].1.multiplier = U128(50000000);
+        let max_repay = pool.compute_max_liquidatable_borrow(
+            &get_account(2),
+            &get_account(5),
+            &lend_token_info,
+            &price,
+            &collateral_token_info,
+            &price,
+            &extra_collateral_prices,
+        );
+        assert_eq!(max_repay, 950);
+        let (seized, _, _) = utils::compute_liquidation_amounts(
+            max_repay,
+            &lend_token_info,
+            &price,
+            &extra_collateral_prices[0].0,
+            &extra_collateral_prices[0].1,
+            500,
+            5000,
+        );
+        assert_eq!(seized, 2000);
+        let mut borrower = pool.get_account_deposit(&get_account(2));
+        borrower.reduce_extra_collateral(&get_account(5), seized);
+        borrower.borrow_amount -= max_repay;
+        pool.account_deposits.insert(&get_account(2), &borrower);
+        let cr = pool.compute_current_cr(
+            get_account(2),
+            &lend_token_info,
+            &price,
+            &collateral_token_info,
+            &price,
+            &extra_collateral_prices,
+            None,
+            None,
+            None,
+        );
+        assert!(cr < pool.min_cr);
+    }
+
+    #[test]
+    fn owner_commission_accrues_claimable_revenue() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(0).build());
+        let mut pool = new_pool_default(0, get_account(0), get_account(3), get_account(4));
+        pool.owner_commission = 1000;
+        pool.total_lend_asset_deposit = 1_000_000_000;
+        pool.total_borrow = 500_000_000;
+
+        //10% APR for one year, 10% to the foundation and 10% to the owner
+        testing_env!(context
+            .block_timestamp(SECONDS_PER_YEAR as u64 * 1_000_000_000)
+            .build());
+        assert_eq!(pool.get_pending_owner_revenue(1000), 5_000_000);
+        assert_eq!(pool.get_current_supply_rate(1000), 400);
+        let (owner_revenue, commission) = pool.internal_claim_owner_revenue(1000);
+        assert_eq!(owner_revenue, 5_000_000);
+        assert_eq!(commission, 5_000_000);
+        assert_eq!(pool.total_lend_asset_deposit, 1_040_000_000);
+        assert_eq!(pool.owner_revenue, 0);
+        assert_eq!(pool.total_owner_revenue, 5_000_000);
+    }
+
+    #[test]
+    fn migrate_converts_fixed_rate_pools() {
+        use migration::{AccountDepositV1, PoolV1};
+        let governance = get_account(0);
+        let lender = get_account(5);
+        let borrower = get_account(6);
+        let lend_token_id = get_account(3);
+        let collateral_token_id = get_account(4);
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(governance.clone()).build());
+
+        let mut old_deposits: UnorderedMap<AccountId, AccountDepositV1> =
+            UnorderedMap::new(format!("p_account_deposits_{}", 0).as_bytes());
+        for (account_id, token_id, amount, borrow_amount) in [
+            (lender.clone(), lend_token_id.clone(), 1000, 0),
+            (borrower.clone(), collateral_token_id.clone(), 2000, 500),
+        ] {
+            let mut deposits = UnorderedMap::new(StorageKey::AccountDeposit {
+                pool_id: 0,
+                account_id: account_id.clone(),
+            });
+            deposits.insert(&token_id, &amount);
+            old_deposits.insert(
+                &account_id,
+                &AccountDepositV1 {
+                    pool_id: 0,
+                    owner_id: account_id.clone(),
+                    lend_token_id: lend_token_id.clone(),
+                    collateral_token_id: collateral_token_id.clone(),
+                    deposits: deposits,
+                    borrow_amount: borrow_amount,
+                    lending_interest_profit_debt: 0,
+                    unpaid_lending_interest_profit: if borrow_amount == 0 { 5 } else { 0 },
+                    total_lending_interest_profit: 0,
+                    last_lending_interest_reward_update_timestamp_sec: 0,
+                    unpaid_borrowing_interest: 0,
+                    total_borrowing_interest: 0,
+                    last_borrowing_interest_update_timestamp_sec: 0,
+                },
+            );
+        }
+        let old_pool = PoolV1 {
+            pool_id: 0,
+            owner_id: governance.clone(),
+            lend_token_id: lend_token_id.clone(),
+            collateral_token_id: collateral_token_id.clone(),
+            min_cr: 15000,
+            max_utilization: 9000,
+            min_lend_token_deposit: 0,
+            min_lend_token_borrow: 0,
+            total_lend_asset_deposit: 1000,
+            total_collateral_deposit: 2000,
+            total_borrow: 500,
+            account_deposits: UnorderedMap::try_from_slice(&old_deposits.try_to_vec().unwrap())
+                .unwrap(),
+            fixed_interest_rate: 1000,
+            acc_interest_per_share: 0,
+            last_acc_interest_update_timestamp_sec: 0,
+            liquidation_bonus: 1000,
+            liquidation_history: vec![],
+        };
+        env::state_write(&ContractV1 {
+            governance: governance.clone(),
+            black_list: LookupMap::new(StorageKey::Blacklist),
+            status: ContractStatus::Working,
+            supported_tokens: LookupMap::new(StorageKey::SupportedTokens),
+            token_list: vec![],
+            price_data: PriceData::default(),
+            price_feeder: governance.clone(),
+            foundation_id: get_account(1),
+            pool_creation_fee: 10 * ONE_NEAR,
+            pools: vec![old_pool],
+            token_to_list_lend_pools: UnorderedMap::new(StorageKey::TokenToListLendPools),
+            token_to_list_collateral_pools: UnorderedMap::new(StorageKey::TokenToListCollateralPools),
+            created_pools: UnorderedMap::new(StorageKey::CreatedPools),
+            deposited_pools: UnorderedMap::new(StorageKey::DepositedPools),
+            borrow_pools: UnorderedMap::new(StorageKey::BorrowPools),
+            storage_accounts: LookupMap::new(StorageKey::UserStorage),
+            storage_usage_add_pool: 0,
+            storage_usage_join_pool: 0,
+            account_list: vec![],
+            liquidation_marginal: 5000,
+            foundation_commission: 1000,
+            commissions: UnorderedMap::new(StorageKey::Commissions),
+        });
+
+        testing_env!(context
+            .block_timestamp(SECONDS_PER_YEAR as u64 * 1_000_000_000)
+            .build());
+        let mut contract = Contract::migrate();
+        assert_eq!(contract.status, ContractStatus::Paused);
+        assert!(contract.is_migrating());
+        assert_eq!(contract.migrate_account_deposits(0, 1), 1);
+        assert_eq!(contract.migrate_account_deposits(0, 10), 0);
+        assert!(!contract.is_migrating());
+        contract.resume();
+
+        let pool = &contract.pools[0];
+        //10% of the year on 500 borrowed, the foundation takes 10% of the lending interest
+        let lender_deposit = pool.account_deposits.get(&lender).unwrap();
+        assert_eq!(lender_deposit.get_token_deposit(&lend_token_id), 1000 + 5 + 45);
+        assert_eq!(lender_deposit.supply_index, INTEREST_INDEX_PRECISION);
+        let borrower_deposit = pool.account_deposits.get(&borrower).unwrap();
+        assert_eq!(borrower_deposit.borrow_amount, 500);
+        assert_eq!(borrower_deposit.unpaid_borrowing_interest, 50);
+        assert_eq!(borrower_deposit.get_token_deposit(&collateral_token_id), 2000);
+        assert_eq!(pool.total_lend_asset_deposit, 1050);
+        assert_eq!(pool.total_borrow, 550);
+        assert_eq!(contract.commissions.get(&lend_token_id).unwrap().total.0, 5);
+    }
 }
@@ -0,0 +1,532 @@
+use crate::*;
+use near_sdk::{PromiseOrValue, PromiseResult};
+use token_receiver::LoanMessage;
+use std::collections::HashMap;
+use utils::{compute_cr, compute_token_value, ext_ft_core, ext_self, GAS_FOR_FT_TRANSFER, GAS_FOR_RESOLVE_TRANSFER};
+
+//an offer posted by a lender, funded with the lend token and taken by borrowers whole or in part
+#[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LoanOffer {
+    pub offer_id: u64,
+    pub lender_id: AccountId,
+    pub lend_token_id: AssetId,
+    pub collateral_token_id: AssetId,
+    pub amount_left: U128,
+    pub interest_rate: u64, //APR per INTEREST_RATE_DIVISOR
+    pub min_cr: u64,
+    pub duration_sec: u64,
+    pub created_timestamp_sec: u64,
+}
+
+//a bilateral loan between the lender of an offer and a borrower
+#[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Loan {
+    pub loan_id: u64,
+    pub offer_id: u64,
+    pub lender_id: AccountId,
+    pub borrower_id: AccountId,
+    pub lend_token_id: AssetId,
+    pub collateral_token_id: AssetId,
+    pub principal: U128,
+    pub collateral_amount: U128,
+    pub interest_rate: u64,
+    pub min_cr: u64,
+    pub unpaid_interest: U128,
+    pub last_interest_timestamp_sec: u64,
+    pub maturity_timestamp_sec: u64,
+}
+
+impl Loan {
+    //simple interest since the last payment, it keeps accruing after maturity
+    pub fn compute_unrecorded_interest(&self) -> Balance {
+        let current_time_sec = env::block_timestamp_ms() / 1000;
+        if current_time_sec <= self.last_interest_timestamp_sec {
+            return 0;
+        }
+        (U256::from(self.principal.0)
+            * U256::from(self.interest_rate)
+            * U256::from(current_time_sec - self.last_interest_timestamp_sec)
+            / U256::from(INTEREST_RATE_DIVISOR * SECONDS_PER_YEAR))
+        .as_u128()
+    }
+
+    pub fn update_interest(&mut self) {
+        self.unpaid_interest = U128(self.unpaid_interest.0 + self.compute_unrecorded_interest());
+        self.last_interest_timestamp_sec = env::block_timestamp_ms() / 1000;
+    }
+
+    pub fn get_owed(&self) -> Balance {
+        self.principal.0 + self.unpaid_interest.0 + self.compute_unrecorded_interest()
+    }
+
+    //interest first, returns the principal and interest paid
+    pub fn pay(&mut self, amount: Balance) -> (Balance, Balance) {
+        self.update_interest();
+        let interest_paid = std::cmp::min(amount, self.unpaid_interest.0);
+        self.unpaid_interest = U128(self.unpaid_interest.0 - interest_paid);
+        let principal_paid = std::cmp::min(amount - interest_paid, self.principal.0);
+        self.principal = U128(self.principal.0 - principal_paid);
+        (principal_paid, interest_paid)
+    }
+
+    pub fn is_defaulted(&self) -> bool {
+        env::block_timestamp_ms() / 1000
+            > self.maturity_timestamp_sec + DEFAULT_MATURITY_GRACE_PERIOD_SEC
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Cancels what is left of an offer, the lend tokens go back to the lender's market balance.
+    #[payable]
+    pub fn cancel_loan_offer(&mut self, offer_id: u64) {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let offer = self.loan_offers.get(&offer_id).expect("offer not found");
+        require!(
+            env::predecessor_account_id() == offer.lender_id,
+            "only the lender can cancel the offer"
+        );
+        self.loan_offers.remove(&offer_id);
+        self.internal_add_market_balance(&offer.lender_id, &offer.lend_token_id, offer.amount_left.0);
+    }
+
+    /// After maturity and the grace period, the lender can take collateral worth the debt of an
+    /// unpaid loan plus the liquidation bonus, the rest of the collateral goes back to the borrower.
+    #[payable]
+    pub fn foreclose_loan(&mut self, loan_id: u64) {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let loan = self.loans.get(&loan_id).expect("loan not found");
+        require!(
+            env::predecessor_account_id() == loan.lender_id,
+            "only the lender can foreclose"
+        );
+        require!(loan.is_defaulted(), "loan is not in default");
+        let to_lender = self.compute_loan_collateral_for_debt(&loan, loan.get_owed());
+        self.loans.remove(&loan_id);
+        self.internal_add_market_balance(&loan.lender_id, &loan.collateral_token_id, to_lender);
+        self.internal_add_market_balance(
+            &loan.borrower_id,
+            &loan.collateral_token_id,
+            loan.collateral_amount.0 - to_lender,
+        );
+    }
+
+    #[payable]
+    pub fn withdraw_market_balance(&mut self, token_id: AssetId, amount: Option<U128>) -> Promise {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        let mut balances = self.market_balances.get(&account_id).unwrap_or_default();
+        let balance = balances.get(&token_id).map(|v| v.0).unwrap_or(0);
+        let amount = amount.map(|v| v.0).unwrap_or(balance);
+        require!(amount > 0 && amount <= balance, "insufficient market balance");
+        balances.insert(token_id.clone(), U128(balance - amount));
+        self.market_balances.insert(&account_id, &balances);
+        self.internal_send_market_tokens(&token_id, &account_id, amount)
+    }
+
+    #[private]
+    pub fn callback_market_transfer(&mut self, token_id: AccountId, receiver_id: AccountId, amount: U128) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            "CALLBACK_MARKET_TRANSFER_INVALID"
+        );
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {}
+            PromiseResult::Failed => {
+                self.internal_add_market_balance(&receiver_id, &token_id, amount.0)
+            }
+        };
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_post_loan_offer(
+        &mut self,
+        lender_id: &AccountId,
+        lend_token_id: &AssetId,
+        amount: Balance,
+        collateral_token_id: AssetId,
+        interest_rate: u64,
+        min_cr: u64,
+        duration_sec: u64,
+    ) {
+        self.abort_if_unsupported_token(collateral_token_id.clone());
+        require!(
+            lend_token_id.clone() != collateral_token_id,
+            "lend and collateral tokens must be different"
+        );
+        require!(
+            (min_cr as u128) > COLLATERAL_RATIO_DIVISOR,
+            "min_cr must be above 100%"
+        );
+        require!(duration_sec > 0, "loan term duration must be positive");
+        require!(amount > 0, "amount must be positive");
+
+        let prev_storage = env::storage_usage();
+        let offer_id = self.next_loan_offer_id;
+        self.next_loan_offer_id += 1;
+        self.loan_offers.insert(
+            &offer_id,
+            &LoanOffer {
+                offer_id: offer_id,
+                lender_id: lender_id.clone(),
+                lend_token_id: lend_token_id.clone(),
+                collateral_token_id: collateral_token_id.clone(),
+                amount_left: U128(amount),
+                interest_rate: interest_rate,
+                min_cr: min_cr,
+                duration_sec: duration_sec,
+                created_timestamp_sec: env::block_timestamp_ms() / 1000,
+            },
+        );
+        //the lender is paid back in these tokens by other accounts, the entries are paid now
+        self.internal_reserve_market_balance(lender_id, lend_token_id);
+        self.internal_reserve_market_balance(lender_id, &collateral_token_id);
+        self.verify_storage(lender_id, prev_storage, None);
+        log!("loan offer {} posted by {}", offer_id, lender_id);
+    }
+
+    //the transferred collateral backs the loan, returns the promise sending the borrowed tokens
+    pub(crate) fn internal_take_loan_offer(
+        &mut self,
+        borrower_id: &AccountId,
+        collateral_token_id: &AssetId,
+        collateral_amount: Balance,
+        offer_id: u64,
+        borrow_amount: Balance,
+    ) -> Promise {
+        let mut offer = self.loan_offers.get(&offer_id).expect("offer not found");
+        require!(
+            collateral_token_id.clone() == offer.collateral_token_id,
+            "invalid collateral token for offer"
+        );
+        require!(borrower_id.clone() != offer.lender_id, "cannot take own offer");
+        require!(
+            borrow_amount > 0 && borrow_amount <= offer.amount_left.0,
+            "invalid borrow amount"
+        );
+        let lend_token_info = self.get_token_info(offer.lend_token_id.clone());
+        let lend_token_price = self.price_data.price(&offer.lend_token_id);
+        let collateral_token_info = self.get_token_info(offer.collateral_token_id.clone());
+        let collateral_token_price = self.price_data.price(&offer.collateral_token_id);
+        let cr = compute_cr(
+            collateral_amount,
+            collateral_token_info.decimals,
+            &collateral_token_price,
+            borrow_amount,
+            &lend_token_price,
+            lend_token_info.decimals,
+        );
+        require!(cr >= offer.min_cr, "collateral ratio too low");
+
+        let prev_storage = env::storage_usage();
+        offer.amount_left = U128(offer.amount_left.0 - borrow_amount);
+        if offer.amount_left.0 == 0 {
+            self.loan_offers.remove(&offer_id);
+        } else {
+            self.loan_offers.insert(&offer_id, &offer);
+        }
+
+        let current_time_sec = env::block_timestamp_ms() / 1000;
+        let loan_id = self.next_loan_id;
+        self.next_loan_id += 1;
+        self.loans.insert(
+            &loan_id,
+            &Loan {
+                loan_id: loan_id,
+                offer_id: offer_id,
+                lender_id: offer.lender_id.clone(),
+                borrower_id: borrower_id.clone(),
+                lend_token_id: offer.lend_token_id.clone(),
+                collateral_token_id: offer.collateral_token_id.clone(),
+                principal: U128(borrow_amount),
+                collateral_amount: U128(collateral_amount),
+                interest_rate: offer.interest_rate,
+                min_cr: offer.min_cr,
+                unpaid_interest: U128(0),
+                last_interest_timestamp_sec: current_time_sec,
+                maturity_timestamp_sec: current_time_sec + offer.duration_sec,
+            },
+        );
+        //collateral released by a foreclosure or a liquidation, and a failed loan transfer
+        self.internal_reserve_market_balance(borrower_id, collateral_token_id);
+        self.internal_reserve_market_balance(borrower_id, &offer.lend_token_id);
+        self.verify_storage(borrower_id, prev_storage, None);
+        log!("loan {} of {} taken by {}", loan_id, borrow_amount, borrower_id);
+        self.internal_send_market_tokens(&offer.lend_token_id, borrower_id, borrow_amount)
+    }
+
+    /// Liquidates a loan under its min CR or past maturity and grace period. The liquidator
+    /// pays the whole debt and gets collateral worth it plus the liquidation bonus.
+    pub(crate) fn internal_liquidate_loan(
+        &mut self,
+        liquidator_id: &AccountId,
+        loan_id: u64,
+        token_id: &AssetId,
+        amount: Balance,
+    ) -> Balance {
+        let mut loan = self.loans.get(&loan_id).expect("loan not found");
+        require!(token_id.clone() == loan.lend_token_id, "invalid token for loan");
+        let lend_token_info = self.get_token_info(loan.lend_token_id.clone());
+        let lend_token_price = self.price_data.price(&loan.lend_token_id);
+        let collateral_token_info = self.get_token_info(loan.collateral_token_id.clone());
+        let collateral_token_price = self.price_data.price(&loan.collateral_token_id);
+
+        let owed = loan.get_owed();
+        let cr = compute_cr(
+            loan.collateral_amount.0,
+            collateral_token_info.decimals,
+            &collateral_token_price,
+            owed,
+            &lend_token_price,
+            lend_token_info.decimals,
+        );
+        require!(
+            cr < loan.min_cr || loan.is_defaulted(),
+            "loan is not under collateral ratio for liquidation"
+        );
+        require!(amount >= owed, "liquidation must pay the whole debt");
+
+        let to_liquidator = self.compute_loan_collateral_for_debt(&loan, owed);
+        let to_borrower = loan.collateral_amount.0 - to_liquidator;
+
+        let (principal_paid, interest_paid) = loan.pay(owed);
+        self.loans.remove(&loan_id);
+        self.internal_credit_loan_payment(&loan, principal_paid, interest_paid);
+        self.internal_add_market_balance(liquidator_id, &loan.collateral_token_id, to_liquidator);
+        self.internal_add_market_balance(&loan.borrower_id, &loan.collateral_token_id, to_borrower);
+        log!(
+            "loan {} liquidated by {}, debt {}, collateral {}",
+            loan_id,
+            liquidator_id,
+            owed,
+            to_liquidator
+        );
+        amount - owed
+    }
+
+    //collateral worth `debt` at the oracle price plus the bonus, at most all of the loan collateral
+    fn compute_loan_collateral_for_debt(&self, loan: &Loan, debt: Balance) -> Balance {
+        let lend_token_info = self.get_token_info(loan.lend_token_id.clone());
+        let lend_token_price = self.price_data.price(&loan.lend_token_id);
+        let collateral_token_info = self.get_token_info(loan.collateral_token_id.clone());
+        let collateral_token_price = self.price_data.price(&loan.collateral_token_id);
+        let debt_value = compute_token_value(debt, &lend_token_price);
+        let collateral = (debt_value
+            * U256::from(10u128.pow(collateral_token_info.decimals as u32))
+            * U256::from(10u128.pow(collateral_token_price.decimals as u32))
+            * U256::from(LIQUIDATION_BONUS_DIVISOR + LOAN_LIQUIDATION_BONUS as u128)
+            / (U256::from(collateral_token_price.multiplier.0)
+                * U256::from(10u128.pow(lend_token_info.decimals as u32))
+                * U256::from(LIQUIDATION_BONUS_DIVISOR)))
+        .as_u128();
+        std::cmp::min(collateral, loan.collateral_amount.0)
+    }
+
+    //returns the unused amount, the collateral is released once the loan is fully repaid
+    pub(crate) fn internal_repay_loan(
+        &mut self,
+        loan_id: u64,
+        token_id: &AssetId,
+        amount: Balance,
+    ) -> Balance {
+        let mut loan = self.loans.get(&loan_id).expect("loan not found");
+        require!(token_id.clone() == loan.lend_token_id, "invalid token for loan");
+        let (principal_paid, interest_paid) = loan.pay(amount);
+        self.internal_credit_loan_payment(&loan, principal_paid, interest_paid);
+        if loan.principal.0 == 0 && loan.unpaid_interest.0 == 0 {
+            self.loans.remove(&loan_id);
+            self.internal_add_market_balance(
+                &loan.borrower_id,
+                &loan.collateral_token_id,
+                loan.collateral_amount.0,
+            );
+            log!("loan {} repaid", loan_id);
+        } else {
+            self.loans.insert(&loan_id, &loan);
+        }
+        amount - principal_paid - interest_paid
+    }
+
+    pub(crate) fn internal_add_loan_collateral(
+        &mut self,
+        loan_id: u64,
+        token_id: &AssetId,
+        amount: Balance,
+    ) {
+        let mut loan = self.loans.get(&loan_id).expect("loan not found");
+        require!(
+            token_id.clone() == loan.collateral_token_id,
+            "invalid collateral token for loan"
+        );
+        loan.collateral_amount = U128(loan.collateral_amount.0 + amount);
+        self.loans.insert(&loan_id, &loan);
+    }
+
+    //the foundation takes its commission on interest like in pools
+    fn internal_credit_loan_payment(&mut self, loan: &Loan, principal_paid: Balance, interest_paid: Balance) {
+        let commission = interest_paid * (self.foundation_commission as u128)
+            / FOUNDATION_COMMISSION_DIVISOR;
+        self.deposit_foundation(&loan.lend_token_id, &commission);
+        self.internal_add_market_balance(
+            &loan.lender_id,
+            &loan.lend_token_id,
+            principal_paid + interest_paid - commission,
+        );
+    }
+
+    pub(crate) fn internal_add_market_balance(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &AssetId,
+        amount: Balance,
+    ) {
+        if amount == 0 {
+            return;
+        }
+        //a new balance entry is charged to the storage deposit of the account, the entries of
+        //lenders and borrowers are reserved with the loan so that settling it never fails on storage
+        let prev_storage = env::storage_usage();
+        let mut balances = self.market_balances.get(account_id).unwrap_or_default();
+        let balance = balances.get(token_id).map(|v| v.0).unwrap_or(0);
+        balances.insert(token_id.clone(), U128(balance + amount));
+        self.market_balances.insert(account_id, &balances);
+        if env::storage_usage() > prev_storage {
+            self.verify_storage(account_id, prev_storage, None);
+        }
+    }
+
+    //an empty balance entry, the caller charges its storage
+    fn internal_reserve_market_balance(&mut self, account_id: &AccountId, token_id: &AssetId) {
+        let mut balances = self.market_balances.get(account_id).unwrap_or_default();
+        if !balances.contains_key(token_id) {
+            balances.insert(token_id.clone(), U128(0));
+            self.market_balances.insert(account_id, &balances);
+        }
+    }
+
+    fn internal_send_market_tokens(
+        &self,
+        token_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: Balance,
+    ) -> Promise {
+        ext_ft_core::ft_transfer(
+            receiver_id.clone(),
+            U128(amount),
+            None,
+            token_id.clone(),
+            1,
+            GAS_FOR_FT_TRANSFER,
+        )
+        .then(ext_self::callback_market_transfer(
+            token_id.clone(),
+            receiver_id.clone(),
+            U128(amount),
+            env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+    }
+
+    pub(crate) fn internal_on_loan_message(
+        &mut self,
+        message: LoanMessage,
+        sender_id: &AccountId,
+        token_in: &AssetId,
+        amount: Balance,
+    ) -> PromiseOrValue<U128> {
+        match message {
+            LoanMessage::PostLoanOffer {
+                collateral_token_id,
+                interest_rate,
+                min_cr,
+                duration_sec,
+            } => {
+                self.internal_post_loan_offer(
+                    sender_id,
+                    token_in,
+                    amount,
+                    collateral_token_id,
+                    interest_rate,
+                    min_cr,
+                    duration_sec,
+                );
+                PromiseOrValue::Value(U128(0))
+            }
+            LoanMessage::TakeLoanOffer {
+                offer_id,
+                borrow_amount,
+            } => {
+                self.internal_take_loan_offer(
+                    sender_id,
+                    token_in,
+                    amount,
+                    offer_id,
+                    borrow_amount.0,
+                );
+                PromiseOrValue::Value(U128(0))
+            }
+            LoanMessage::RepayLoan { loan_id } => {
+                PromiseOrValue::Value(U128(self.internal_repay_loan(loan_id, token_in, amount)))
+            }
+            LoanMessage::AddLoanCollateral { loan_id } => {
+                self.internal_add_loan_collateral(loan_id, token_in, amount);
+                PromiseOrValue::Value(U128(0))
+            }
+            LoanMessage::LiquidateLoan { loan_id } => PromiseOrValue::Value(
+                U128(self.internal_liquidate_loan(sender_id, loan_id, token_in, amount)),
+            ),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_loan_offer(&self, offer_id: u64) -> Option<LoanOffer> {
+        self.loan_offers.get(&offer_id)
+    }
+
+    pub fn get_loan_offers(&self, from_index: Option<usize>, limit: Option<usize>) -> Vec<LoanOffer> {
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        let start_index = from_index.unwrap_or(0);
+        self.loan_offers
+            .values()
+            .skip(start_index)
+            .take(limit)
+            .collect::<Vec<_>>()
+    }
+
+    pub fn get_loan(&self, loan_id: u64) -> Option<Loan> {
+        self.loans.get(&loan_id).map(|mut loan| {
+            loan.unpaid_interest = U128(loan.unpaid_interest.0 + loan.compute_unrecorded_interest());
+            loan
+        })
+    }
+
+    pub fn get_loans(&self, from_index: Option<usize>, limit: Option<usize>) -> Vec<Loan> {
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        let start_index = from_index.unwrap_or(0);
+        self.loans
+            .keys()
+            .skip(start_index)
+            .take(limit)
+            .filter_map(|loan_id| self.get_loan(loan_id))
+            .collect::<Vec<_>>()
+    }
+
+    pub fn get_market_balances(&self, account_id: AccountId) -> HashMap<AssetId, U128> {
+        self.market_balances.get(&account_id).unwrap_or_default()
+    }
+}
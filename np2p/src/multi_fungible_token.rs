@@ -0,0 +1,225 @@
+use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+use near_sdk::{ext_contract, near_bindgen, PromiseOrValue, PromiseResult};
+
+use crate::utils::{GAS_FOR_MFT_TRANSFER_CALL, GAS_FOR_RESOLVE_TRANSFER};
+use crate::*;
+
+#[ext_contract(ext_self)]
+trait MFTTokenResolver {
+    fn mft_resolve_transfer(
+        &mut self,
+        token_id: String,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128;
+}
+
+#[ext_contract(ext_share_token_receiver)]
+pub trait MFTTokenReceiver {
+    fn mft_on_transfer(
+        &mut self,
+        token_id: String,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128>;
+}
+
+/// Receipt of the lend token deposit in a pool, `token_id` is ":<pool_id>" like exchange LP shares.
+/// Balances are in lend token units and grow with the pool supply index.
+fn parse_pool_id(token_id: &String) -> u32 {
+    require!(token_id.starts_with(":"), "illegal pool id");
+    str::parse::<u32>(&token_id[1..token_id.len()]).expect("illegal pool id")
+}
+
+#[near_bindgen]
+impl Contract {
+    fn internal_mft_transfer(
+        &mut self,
+        token_id: String,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: u128,
+        memo: Option<String>,
+    ) {
+        let pool_id = parse_pool_id(&token_id);
+        self.abort_if_pool_id_valid(pool_id as usize);
+        require!(
+            self.pools[pool_id as usize].is_allowed_lender(receiver_id),
+            "not an allowed lender"
+        );
+        self.internal_move_shares(pool_id, sender_id, receiver_id, amount, memo);
+    }
+
+    //also returns the unused shares of mft_transfer_call, which must not depend on the lender allow-list
+    fn internal_move_shares(
+        &mut self,
+        pool_id: u32,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: u128,
+        memo: Option<String>,
+    ) {
+        require!(sender_id != receiver_id, "transfer to self");
+        require!(amount > 0, "amount must be positive");
+        let pool = &mut self.pools[pool_id as usize];
+        let (lend_token_id, amount_for_foundation) = pool.internal_share_transfer(
+            sender_id,
+            receiver_id,
+            amount,
+            self.foundation_commission,
+        );
+        self.deposit_foundation(&lend_token_id, &amount_for_foundation);
+        log!(
+            "Transfer shares {} pool: {} from {} to {}",
+            pool_id,
+            amount,
+            sender_id,
+            receiver_id
+        );
+        if let Some(memo) = memo {
+            log!("Memo: {}", memo);
+        }
+    }
+
+    fn internal_mft_balance(&self, token_id: String, account_id: &AccountId) -> Balance {
+        let pool_id = parse_pool_id(&token_id);
+        let pool = self.pools.get(pool_id as usize).expect("pool_id out of bound");
+        pool.get_deposits(account_id, self.foundation_commission)
+            .get(&pool.lend_token_id)
+            .map(|v| v.0)
+            .unwrap_or(0)
+    }
+
+    /// Returns the balance of the given account. If the account doesn't exist will return `"0"`.
+    pub fn mft_balance_of(&self, token_id: String, account_id: AccountId) -> U128 {
+        self.internal_mft_balance(token_id, &account_id).into()
+    }
+
+    pub fn mft_total_supply(&self, token_id: String) -> U128 {
+        let pool_id = parse_pool_id(&token_id);
+        let pool = self.pools.get(pool_id as usize).expect("pool_id out of bound");
+        U128(pool.share_total_balance(self.foundation_commission))
+    }
+
+    /// Registers the given account in the pool so it can receive receipts, storage is paid by the caller.
+    #[payable]
+    pub fn mft_register(&mut self, token_id: String, account_id: AccountId) {
+        self.abort_if_pause();
+        let pool_id = parse_pool_id(&token_id);
+        self.abort_if_pool_id_valid(pool_id as usize);
+        let prev_storage = env::storage_usage();
+        self.pools[pool_id as usize].share_register(&account_id);
+        self.add_to_deposit_pools_list(&account_id, pool_id);
+        self.verify_storage(
+            &env::predecessor_account_id(),
+            prev_storage,
+            Some(env::attached_deposit()),
+        );
+    }
+
+    #[payable]
+    pub fn mft_transfer(
+        &mut self,
+        token_id: String,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let sender_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(sender_id.clone());
+        self.internal_mft_transfer(token_id, &sender_id, &receiver_id, amount.0, memo);
+    }
+
+    #[payable]
+    pub fn mft_transfer_call(
+        &mut self,
+        token_id: String,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let sender_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(sender_id.clone());
+        self.internal_mft_transfer(token_id.clone(), &sender_id, &receiver_id, amount.0, memo);
+        ext_share_token_receiver::mft_on_transfer(
+            token_id.clone(),
+            sender_id.clone(),
+            amount,
+            msg,
+            receiver_id.clone(),
+            0,
+            env::prepaid_gas() - GAS_FOR_MFT_TRANSFER_CALL,
+        )
+        .then(ext_self::mft_resolve_transfer(
+            token_id,
+            sender_id,
+            receiver_id,
+            amount,
+            env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+        .into()
+    }
+
+    /// Returns how much was refunded back to the sender.
+    #[private]
+    pub fn mft_resolve_transfer(
+        &mut self,
+        token_id: String,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128 {
+        let unused_amount = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                if let Ok(unused_amount) = near_sdk::serde_json::from_slice::<U128>(&value) {
+                    std::cmp::min(amount.0, unused_amount.0)
+                } else {
+                    amount.0
+                }
+            }
+            PromiseResult::Failed => amount.0,
+        };
+        if unused_amount > 0 {
+            //shares the receiver lent out or queued for withdrawal meanwhile are not returned
+            let pool_id = parse_pool_id(&token_id);
+            let receiver_balance = self.pools[pool_id as usize]
+                .get_free_lend_token_deposit(&receiver_id, self.foundation_commission);
+            if receiver_balance > 0 {
+                let refund_amount = std::cmp::min(receiver_balance, unused_amount);
+                self.internal_move_shares(
+                    pool_id,
+                    &receiver_id,
+                    &sender_id,
+                    refund_amount,
+                    None,
+                );
+            }
+        }
+        U128(unused_amount)
+    }
+
+    pub fn mft_metadata(&self, token_id: String) -> FungibleTokenMetadata {
+        let pool_id = parse_pool_id(&token_id);
+        let pool = self.pools.get(pool_id as usize).expect("pool_id out of bound");
+        let lend_token_info = self.get_token_info(pool.lend_token_id.clone());
+        FungibleTokenMetadata {
+            spec: "mft-1.0.0".to_string(),
+            name: format!("np2p-pool-{}", pool_id),
+            symbol: format!("NP2P-POOL-{}", pool_id),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: lend_token_info.decimals,
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::UnorderedMap;
+use near_sdk::collections::{TreeMap, UnorderedMap, UnorderedSet};
 use near_sdk::json_types::U128;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{env, log, require, AccountId, Balance, PanicOnDefault};
@@ -27,6 +27,57 @@ pub struct Liquidation {
     price: Price,             //price before liquidation fee
 }
 
+//a borrow term offered by a pool, interest is locked in for the whole duration at borrow time
+#[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LoanTerm {
+    pub duration_sec: u64,
+    pub interest_rate: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PoolStatus {
+    Active,
+    BorrowsFrozen, //no new borrows, the owner can unfreeze
+    Sunset, //no new borrows nor lending, cannot be undone
+}
+
+//parameters announced by the pool owner, they can be applied once effective_timestamp_sec is reached
+#[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingPoolConfig {
+    pub min_cr: u64,
+    pub max_utilization: u64,
+    pub min_lend_token_deposit: U128,
+    pub min_lend_token_borrow: U128,
+    pub liquidation_bonus: u64,
+    pub interest_rate_model: InterestRateModel,
+    pub reserve_factor: u64,
+    pub owner_commission: u64,
+    pub extra_collaterals: Vec<CollateralConfig>, //total_deposit is carried over from the pool when applied
+    pub effective_timestamp_sec: u64,
+}
+
+//a lend token withdrawal waiting for liquidity, filled in request order
+#[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct QueuedWithdrawal {
+    pub account_id: AccountId,
+    pub amount: U128, //left to fill
+    pub timestamp_sec: u64,
+}
+
+//a collateral token accepted besides collateral_token_id, with its own risk parameters
+#[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CollateralConfig {
+    pub token_id: AssetId,
+    pub min_cr: u64,
+    pub liquidation_bonus: u64,
+    pub total_deposit: U128,
+}
+
 //pool can be created by any one
 //users can either provide lending assets for others to borrow
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -43,11 +94,34 @@ pub struct Pool {
     pub total_collateral_deposit: Balance,
     pub total_borrow: Balance,
     pub account_deposits: UnorderedMap<AccountId, AccountDeposit>,
-    pub fixed_interest_rate: u64,
-    pub acc_interest_per_share: Balance,
-    pub last_acc_interest_update_timestamp_sec: u64,
+    pub interest_rate_model: InterestRateModel,
+    pub borrow_index: Balance, //growth of a borrowed amount with interest, starts at INTEREST_INDEX_PRECISION
+    pub supply_index: Balance, //growth of a lend token deposit with interest after foundation commission
+    pub last_index_update_timestamp_sec: u64,
     pub liquidation_bonus: u64, //or price penalty, it means how much discount liquidators can buy collateral tokens using lend token to pay the debt
     pub liquidation_history: Vec<Liquidation>,
+    pub loan_terms: Vec<LoanTerm>, //empty if the pool only offers open-ended loans
+    pub maturity_grace_period_sec: u64, //after maturity plus this period, a term loan can be liquidated at any CR
+    pub total_term_borrow: Balance, //debt of term loans, locked interest included, not accruing through borrow_index
+    pub term_interest_per_sec: Balance, //locked interest of running term loans earned by lenders each second
+    pub term_maturities: TreeMap<u64, (Balance, Balance)>, //per second interest and rounding remainder ending at a maturity
+    pub status: PoolStatus,
+    pub pending_config: Option<PendingPoolConfig>,
+    pub restrict_lenders: bool, //only the owner and allowed_lenders can lend
+    pub restrict_borrowers: bool, //only the owner and allowed_borrowers can borrow
+    pub allowed_lenders: UnorderedSet<AccountId>,
+    pub allowed_borrowers: UnorderedSet<AccountId>,
+    //queued amounts leave total_lend_asset_deposit and stop earning interest
+    pub withdrawal_queue: Vec<QueuedWithdrawal>,
+    pub total_queued_withdrawal: Balance, //not filled yet
+    pub total_claimable_withdrawal: Balance, //filled, waiting to be claimed
+    pub reserve_factor: u64, //share of interest kept in the reserve to cover bad debt
+    pub reserve: Balance,
+    pub total_bad_debt: Balance, //debt written off, covered by the reserve or lenders
+    pub extra_collaterals: Vec<CollateralConfig>,
+    pub owner_commission: u64, //share of interest earned by the pool owner
+    pub owner_revenue: Balance, //owner share accrued, not claimed yet
+    pub total_owner_revenue: Balance,
 }
 
 impl Pool {
@@ -60,13 +134,25 @@ impl Pool {
         max_utilization: u64,
         min_lend_token_deposit: Balance,
         min_lend_token_borrow: Balance,
-        fixed_interest_rate: u64,
+        interest_rate_model: InterestRateModel,
         liquidation_bonus: u64,
+        loan_terms: Vec<LoanTerm>,
+        maturity_grace_period_sec: u64,
+        reserve_factor: u64,
+        owner_commission: u64,
     ) -> Pool {
         require!(
             lend_token_id.clone() != collateral_token_id.clone(),
             "lend and collateral tokens must be different"
         );
+        interest_rate_model.assert_valid();
+        for term in loan_terms.iter() {
+            require!(term.duration_sec > 0, "loan term duration must be positive");
+        }
+        require!(
+            (reserve_factor as u128) + (owner_commission as u128) < RESERVE_FACTOR_DIVISOR,
+            "reserve_factor and owner_commission too high"
+        );
         Pool {
             pool_id: pool_id.clone(),
             owner_id: owner_id.clone(),
@@ -82,12 +168,311 @@ impl Pool {
             account_deposits: UnorderedMap::new(
                 format!("p_account_deposits_{}", pool_id).as_bytes(),
             ),
-            fixed_interest_rate: fixed_interest_rate,
-            acc_interest_per_share: 0,
-            last_acc_interest_update_timestamp_sec: 0,
+            interest_rate_model: interest_rate_model,
+            borrow_index: INTEREST_INDEX_PRECISION,
+            supply_index: INTEREST_INDEX_PRECISION,
+            last_index_update_timestamp_sec: env::block_timestamp_ms() / 1000,
             liquidation_bonus: liquidation_bonus,
             liquidation_history: vec![],
+            loan_terms: loan_terms,
+            maturity_grace_period_sec: maturity_grace_period_sec,
+            total_term_borrow: 0,
+            term_interest_per_sec: 0,
+            term_maturities: TreeMap::new(format!("p_term_maturities_{}", pool_id).as_bytes()),
+            status: PoolStatus::Active,
+            pending_config: None,
+            restrict_lenders: false,
+            restrict_borrowers: false,
+            allowed_lenders: UnorderedSet::new(
+                format!("p_allowed_lenders_{}", pool_id).as_bytes(),
+            ),
+            allowed_borrowers: UnorderedSet::new(
+                format!("p_allowed_borrowers_{}", pool_id).as_bytes(),
+            ),
+            withdrawal_queue: vec![],
+            total_queued_withdrawal: 0,
+            total_claimable_withdrawal: 0,
+            reserve_factor: reserve_factor,
+            reserve: 0,
+            total_bad_debt: 0,
+            extra_collaterals: vec![],
+            owner_commission: owner_commission,
+            owner_revenue: 0,
+            total_owner_revenue: 0,
+        }
+    }
+
+    pub fn is_allowed_lender(&self, account_id: &AccountId) -> bool {
+        !self.restrict_lenders
+            || account_id.clone() == self.owner_id
+            || self.allowed_lenders.contains(account_id)
+    }
+
+    pub fn is_allowed_borrower(&self, account_id: &AccountId) -> bool {
+        !self.restrict_borrowers
+            || account_id.clone() == self.owner_id
+            || self.allowed_borrowers.contains(account_id)
+    }
+
+    //lend tokens up to the debt only repay the loan, anything more is lending and needs the lender allow-list,
+    //accounts with a loan can always top up their collateral
+    pub fn assert_can_deposit(&self, account_id: &AccountId, token_id: &AssetId, amount: Balance) {
+        let debt = self.get_borrow_and_interest(account_id);
+        if token_id.clone() == self.lend_token_id {
+            if amount <= debt {
+                return;
+            }
+            require!(self.status != PoolStatus::Sunset, "pool is sunset");
+            require!(self.is_allowed_lender(account_id), "not an allowed lender");
+        } else {
+            if debt > 0 {
+                return;
+            }
+            require!(self.status != PoolStatus::Sunset, "pool is sunset");
+            require!(
+                self.is_allowed_borrower(account_id),
+                "not an allowed borrower"
+            );
+        }
+    }
+
+    pub fn get_extra_collateral_index(&self, token_id: &AssetId) -> Option<usize> {
+        self.extra_collaterals
+            .iter()
+            .position(|c| c.token_id == token_id.clone())
+    }
+
+    /// Proposes another collateral token, it is accepted once the pending config is applied.
+    pub fn propose_extra_collateral(&mut self, token_id: &AssetId, min_cr: u64, liquidation_bonus: u64) {
+        let mut config = self.get_pending_config_or_current();
+        require!(
+            token_id.clone() != self.lend_token_id
+                && token_id.clone() != self.collateral_token_id
+                && !config.extra_collaterals.iter().any(|c| c.token_id == token_id.clone()),
+            "token already used by the pool"
+        );
+        config.extra_collaterals.push(CollateralConfig {
+            token_id: token_id.clone(),
+            min_cr: min_cr,
+            liquidation_bonus: liquidation_bonus,
+            total_deposit: U128(0),
+        });
+        self.set_pending_config(config);
+    }
+
+    /// Proposes new parameters for an extra collateral token, its min_cr can only be increased.
+    pub fn propose_extra_collateral_update(&mut self, token_id: &AssetId, min_cr: u64, liquidation_bonus: u64) {
+        let mut config = self.get_pending_config_or_current();
+        let collateral = config
+            .extra_collaterals
+            .iter_mut()
+            .find(|c| c.token_id == token_id.clone())
+            .expect("not a collateral token of the pool");
+        if let Some(index) = self.get_extra_collateral_index(token_id) {
+            require!(
+                min_cr >= self.extra_collaterals[index].min_cr,
+                "min_cr can only be increased"
+            );
+        }
+        collateral.min_cr = min_cr;
+        collateral.liquidation_bonus = liquidation_bonus;
+        self.set_pending_config(config);
+    }
+
+    /// Stops accepting an extra collateral token nobody deposited, it is also dropped from the pending config.
+    pub fn remove_extra_collateral(&mut self, token_id: &AssetId) {
+        let index = self.get_extra_collateral_index(token_id);
+        let pending_index = self.pending_config.as_ref().and_then(|config| {
+            config
+                .extra_collaterals
+                .iter()
+                .position(|c| c.token_id == token_id.clone())
+        });
+        require!(
+            index.is_some() || pending_index.is_some(),
+            "not a collateral token of the pool"
+        );
+        if let Some(index) = index {
+            require!(
+                self.extra_collaterals[index].total_deposit.0 == 0,
+                "collateral token still deposited"
+            );
+            self.extra_collaterals.remove(index);
         }
+        if let Some(pending_index) = pending_index {
+            self.pending_config
+                .as_mut()
+                .unwrap()
+                .extra_collaterals
+                .remove(pending_index);
+        }
+    }
+
+    /// Other collateral tokens of the account converted to collateral_token_id units, weighted
+    /// by min_cr / their min_cr so the CR checks against the pool min_cr cover all of them.
+    /// `extra_collateral_prices` follows the order of `extra_collaterals`.
+    pub fn compute_extra_collateral(
+        &self,
+        account_deposit: &AccountDeposit,
+        collateral_token_info: &TokenInfo,
+        collateral_token_price: &Price,
+        extra_collateral_prices: &Vec<(TokenInfo, Price)>,
+    ) -> Balance {
+        let mut extra_collateral = U256::from(0);
+        for (collateral, (token_info, price)) in
+            self.extra_collaterals.iter().zip(extra_collateral_prices.iter())
+        {
+            require!(token_info.token_id == collateral.token_id, "invalid token info");
+            let amount = account_deposit.get_token_deposit(&collateral.token_id);
+            if amount == 0 {
+                continue;
+            }
+            extra_collateral = extra_collateral
+                + compute_token_value(amount, price)
+                    * U256::from(10u128.pow(collateral_token_info.decimals as u32))
+                    * U256::from(10u128.pow(collateral_token_price.decimals as u32))
+                    * U256::from(self.min_cr)
+                    / (U256::from(10u128.pow(token_info.decimals as u32))
+                        * U256::from(collateral_token_price.multiplier.0)
+                        * U256::from(collateral.min_cr));
+        }
+        extra_collateral.as_u128()
+    }
+
+    //token info, price, min_cr and liquidation bonus of any collateral token of the pool
+    pub fn get_collateral_params(
+        &self,
+        token_id: &AssetId,
+        collateral_token_info: &TokenInfo,
+        collateral_token_price: &Price,
+        extra_collateral_prices: &Vec<(TokenInfo, Price)>,
+    ) -> (TokenInfo, Price, u64, u64) {
+        if token_id.clone() == self.collateral_token_id {
+            return (
+                collateral_token_info.clone(),
+                collateral_token_price.clone(),
+                self.min_cr,
+                self.liquidation_bonus,
+            );
+        }
+        let index = self
+            .get_extra_collateral_index(token_id)
+            .expect("not a collateral token of the pool");
+        let (token_info, price) = extra_collateral_prices[index].clone();
+        require!(token_info.token_id == token_id.clone(), "invalid token info");
+        let collateral = &self.extra_collaterals[index];
+        (token_info, price, collateral.min_cr, collateral.liquidation_bonus)
+    }
+
+    //the pending config being edited, or the current parameters
+    fn get_pending_config_or_current(&self) -> PendingPoolConfig {
+        self.pending_config.clone().unwrap_or(PendingPoolConfig {
+            min_cr: self.min_cr,
+            max_utilization: self.max_utilization,
+            min_lend_token_deposit: U128(self.min_lend_token_deposit),
+            min_lend_token_borrow: U128(self.min_lend_token_borrow),
+            liquidation_bonus: self.liquidation_bonus,
+            interest_rate_model: self.interest_rate_model.clone(),
+            reserve_factor: self.reserve_factor,
+            owner_commission: self.owner_commission,
+            extra_collaterals: self.extra_collaterals.clone(),
+            effective_timestamp_sec: 0,
+        })
+    }
+
+    //checks the config and restarts the notice period
+    fn set_pending_config(&mut self, mut config: PendingPoolConfig) {
+        require!(self.status != PoolStatus::Sunset, "pool is sunset");
+        //lowering min_cr would let borrowers take more risk on lenders' funds
+        require!(config.min_cr >= self.min_cr, "min_cr can only be increased");
+        require!(
+            config.max_utilization > 0 && (config.max_utilization as u128) <= UTILIZATION_DIVISOR,
+            "invalid max_utilization"
+        );
+        require!(
+            (config.liquidation_bonus as u128) < LIQUIDATION_BONUS_DIVISOR,
+            "liquidation_bonus too high"
+        );
+        config.interest_rate_model.assert_valid();
+        require!(
+            (config.reserve_factor as u128) + (config.owner_commission as u128)
+                < RESERVE_FACTOR_DIVISOR,
+            "reserve_factor and owner_commission too high"
+        );
+        //an extra collateral is at least as risky for lenders as the pool collateral
+        for collateral in config.extra_collaterals.iter() {
+            require!(
+                collateral.min_cr >= config.min_cr,
+                "extra collateral min_cr below the pool min_cr"
+            );
+            require!(
+                (collateral.liquidation_bonus as u128) < LIQUIDATION_BONUS_DIVISOR,
+                "liquidation_bonus too high"
+            );
+        }
+        config.effective_timestamp_sec =
+            env::block_timestamp_ms() / 1000 + POOL_CONFIG_NOTICE_PERIOD_SEC;
+        self.pending_config = Some(config);
+    }
+
+    /// Pool parameters replace the pending ones, the proposed extra collaterals are kept.
+    pub fn propose_config(
+        &mut self,
+        min_cr: Option<u64>,
+        max_utilization: Option<u64>,
+        min_lend_token_deposit: Option<U128>,
+        min_lend_token_borrow: Option<U128>,
+        liquidation_bonus: Option<u64>,
+        interest_rate_model: Option<InterestRateModel>,
+        reserve_factor: Option<u64>,
+        owner_commission: Option<u64>,
+    ) {
+        let config = PendingPoolConfig {
+            min_cr: min_cr.unwrap_or(self.min_cr),
+            max_utilization: max_utilization.unwrap_or(self.max_utilization),
+            min_lend_token_deposit: min_lend_token_deposit
+                .unwrap_or(U128(self.min_lend_token_deposit)),
+            min_lend_token_borrow: min_lend_token_borrow
+                .unwrap_or(U128(self.min_lend_token_borrow)),
+            liquidation_bonus: liquidation_bonus.unwrap_or(self.liquidation_bonus),
+            interest_rate_model: interest_rate_model
+                .unwrap_or(self.interest_rate_model.clone()),
+            reserve_factor: reserve_factor.unwrap_or(self.reserve_factor),
+            owner_commission: owner_commission.unwrap_or(self.owner_commission),
+            extra_collaterals: self.get_pending_config_or_current().extra_collaterals,
+            effective_timestamp_sec: 0,
+        };
+        self.set_pending_config(config);
+    }
+
+    //interest accrues at the old rate until the new config is applied, returns the foundation commission
+    pub fn apply_pending_config(&mut self, foundation_commission: u64) -> Balance {
+        let config = self.pending_config.clone().expect("no pending config");
+        require!(
+            env::block_timestamp_ms() / 1000 >= config.effective_timestamp_sec,
+            "notice period not over"
+        );
+        let commission = self.update_indexes(foundation_commission);
+        self.min_cr = config.min_cr;
+        self.max_utilization = config.max_utilization;
+        self.min_lend_token_deposit = config.min_lend_token_deposit.0;
+        self.min_lend_token_borrow = config.min_lend_token_borrow.0;
+        self.liquidation_bonus = config.liquidation_bonus;
+        self.interest_rate_model = config.interest_rate_model;
+        self.reserve_factor = config.reserve_factor;
+        self.owner_commission = config.owner_commission;
+        self.extra_collaterals = config
+            .extra_collaterals
+            .into_iter()
+            .map(|mut collateral| {
+                if let Some(index) = self.get_extra_collateral_index(&collateral.token_id) {
+                    collateral.total_deposit = self.extra_collaterals[index].total_deposit;
+                }
+                collateral
+            })
+            .collect();
+        self.pending_config = None;
+        commission
     }
 
     pub fn internal_deposit(
@@ -103,9 +488,8 @@ impl Pool {
                 "lower than min deposit"
             );
         }
-        //update acc_interest_per_share
-        log!("update_acc_interest_per_share");
-        self.update_acc_interest_per_share();
+        log!("update_indexes");
+        let commission = self.update_indexes(foundation_commission);
 
         log!(
             "reading account deposit {} for token {}, lend {}, collateral {}",
@@ -115,11 +499,7 @@ impl Pool {
             self.collateral_token_id
         );
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        let commission = account_deposit.update_account(
-            self.fixed_interest_rate,
-            &self.acc_interest_per_share,
-            foundation_commission,
-        );
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
         log!(
             "success reading account deposit {} from pool {}",
             account_id.clone(),
@@ -131,11 +511,14 @@ impl Pool {
         } else if token_id.clone() == self.collateral_token_id {
             account_deposit.internal_deposit_collateral(&amount);
             self.total_collateral_deposit += amount;
+        } else if let Some(index) = self.get_extra_collateral_index(token_id) {
+            account_deposit.internal_deposit_extra_collateral(token_id, &amount);
+            self.extra_collaterals[index].total_deposit.0 += amount;
         } else {
             env::panic_str("unsupported token for pool")
         }
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
         self.account_deposits.insert(account_id, &account_deposit);
+        self.fill_withdrawal_queue();
         (self.lend_token_id.clone(), commission)
     }
 
@@ -147,14 +530,29 @@ impl Pool {
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
+        extra_collateral_prices: &Vec<(TokenInfo, Price)>,
+        term_id: Option<u32>,
         foundation_commission: u64,
     ) -> (Balance, AssetId, Balance) {
-        self.update_acc_interest_per_share();
+        require!(
+            self.status == PoolStatus::Active,
+            "pool does not accept new borrows"
+        );
+        require!(
+            self.is_allowed_borrower(account_id),
+            "not an allowed borrower"
+        );
+        require!(
+            self.withdrawal_queue.is_empty(),
+            "liquidity is reserved for queued withdrawals"
+        );
+        let commission = self.update_indexes(foundation_commission);
 
         require!(
             amount.clone() >= self.min_lend_token_borrow,
             "lower than min borrow"
         );
+        let loan_term = self.get_loan_term(term_id);
         //pub fn borrow(&mut self, account_id: &AccountId, amount: &Balance, contract: &mut Contract) {
         require!(
             self.lend_token_id == lend_token_info.token_id
@@ -162,7 +560,7 @@ impl Pool {
             "invalid token info"
         );
 
-        let total_borrow_after = self.total_borrow + amount.clone();
+        let total_borrow_after = self.get_total_borrow() + amount.clone();
         if self.total_lend_asset_deposit * self.max_utilization as u128 / UTILIZATION_DIVISOR
             < total_borrow_after
         {
@@ -173,10 +571,18 @@ impl Pool {
         }
 
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        let commission = account_deposit.update_account(
-            self.fixed_interest_rate,
-            &self.acc_interest_per_share,
-            foundation_commission,
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
+        if loan_term.is_some() {
+            require!(
+                account_deposit.get_owed_lend_token_amount() == 0,
+                "repay the current loan before borrowing with a new term"
+            );
+        }
+        let extra_collateral = self.compute_extra_collateral(
+            &account_deposit,
+            collateral_token_info,
+            collateral_token_price,
+            extra_collateral_prices,
         );
         let actual_borrow_amount = account_deposit.internal_borrow(
             amount,
@@ -184,17 +590,34 @@ impl Pool {
             lend_token_price,
             collateral_token_info,
             collateral_token_price,
-            self.fixed_interest_rate,
+            extra_collateral,
+            &self.borrow_index,
             self.min_cr,
         );
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
-        self.account_deposits.insert(account_id, &account_deposit);
         if actual_borrow_amount != amount.clone() {
             self.total_lend_asset_deposit -= amount.clone() - actual_borrow_amount;
-            self.total_borrow += actual_borrow_amount.clone();
+        }
+        if let Some(loan_term) = loan_term {
+            let locked_interest = account_deposit.lock_in_term(
+                &loan_term,
+                lend_token_info,
+                lend_token_price,
+                collateral_token_info,
+                collateral_token_price,
+                extra_collateral,
+                &self.borrow_index,
+                self.min_cr,
+            );
+            self.total_term_borrow += actual_borrow_amount + locked_interest;
+            self.add_term_interest(
+                locked_interest,
+                loan_term.duration_sec,
+                account_deposit.maturity_timestamp_sec,
+            );
         } else {
-            self.total_borrow += amount.clone();
+            self.total_borrow += actual_borrow_amount;
         }
+        self.account_deposits.insert(account_id, &account_deposit);
         (actual_borrow_amount, self.lend_token_id.clone(), commission)
     }
 
@@ -219,43 +642,64 @@ impl Pool {
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
+        extra_collateral_prices: &Vec<(TokenInfo, Price)>,
         foundation_commission: u64,
     ) -> (AssetId, Balance) {
-        self.update_acc_interest_per_share();
-        let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        let commission = account_deposit.update_account(
-            self.fixed_interest_rate,
-            &self.acc_interest_per_share,
-            foundation_commission,
-        );
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
-        self.account_deposits.insert(account_id, &account_deposit);
+        let commission = self.update_indexes(foundation_commission);
+        if token_id.clone() == self.lend_token_id {
+            require!(
+                self.withdrawal_queue.is_empty()
+                    && amount <= self.total_lend_asset_deposit.saturating_sub(self.get_total_borrow()),
+                "insufficient liquidity, queue the withdrawal instead"
+            );
+        }
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
 
         log!(
             "balance before withdraw {}",
             account_deposit.get_token_deposit(token_id)
         );
-        let withdrawn_amount_from_deposit = account_deposit.internal_withdraw_from_account(
-            token_id,
-            amount.clone(),
-            lend_token_info,
-            lend_token_price,
-            collateral_token_info,
-            collateral_token_price,
-            self.fixed_interest_rate,
-            &self.acc_interest_per_share,
-            self.min_cr,
-        );
-        if token_id.clone() == self.lend_token_id.clone() {
-            log!(
-                "withdraw {} of {}, of which {} is extracted from generated lending interest",
-                amount.clone(),
-                token_id.clone(),
-                amount.clone() - withdrawn_amount_from_deposit.clone()
-            );
-        }
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
+        let withdrawn_amount_from_deposit =
+            if let Some(index) = self.get_extra_collateral_index(token_id) {
+                account_deposit.reduce_extra_collateral(token_id, amount);
+                let cr = account_deposit.compute_current_cr(
+                    lend_token_info,
+                    lend_token_price,
+                    collateral_token_info,
+                    collateral_token_price,
+                    Some(self.compute_extra_collateral(
+                        &account_deposit,
+                        collateral_token_info,
+                        collateral_token_price,
+                        extra_collateral_prices,
+                    )),
+                    None,
+                    None,
+                    &self.borrow_index,
+                );
+                require!(cr >= self.min_cr, "collateral ratio after withdrawal too low");
+                self.extra_collaterals[index].total_deposit.0 -= amount;
+                amount
+            } else {
+                let extra_collateral = self.compute_extra_collateral(
+                    &account_deposit,
+                    collateral_token_info,
+                    collateral_token_price,
+                    extra_collateral_prices,
+                );
+                account_deposit.internal_withdraw_from_account(
+                    token_id,
+                    amount.clone(),
+                    lend_token_info,
+                    lend_token_price,
+                    collateral_token_info,
+                    collateral_token_price,
+                    extra_collateral,
+                    &self.borrow_index,
+                    self.min_cr,
+                )
+            };
         self.account_deposits.insert(account_id, &account_deposit);
         log!(
             "balance after withdraw {}",
@@ -271,9 +715,27 @@ impl Pool {
         (self.lend_token_id.clone(), commission)
     }
 
-    fn update_acc_interest_per_share(&mut self) {
-        self.acc_interest_per_share = self.get_current_acc_interest_per_share();
-        self.last_acc_interest_update_timestamp_sec = get_next_interest_recal_time_sec();
+    //accrues interest since the last update, returns the foundation commission on it
+    pub fn update_indexes(&mut self, foundation_commission: u64) -> Balance {
+        let current_time_sec = env::block_timestamp_ms() / 1000;
+        if current_time_sec <= self.last_index_update_timestamp_sec {
+            return 0;
+        }
+        let (borrow_index, supply_index, interest, commission) =
+            self.compute_current_indexes(foundation_commission);
+        //term interest is already in total_term_borrow
+        let term_interest = self.compute_term_interest(current_time_sec);
+        let reserve_cut = self.compute_reserve_cut(interest, commission);
+        let owner_cut = self.compute_owner_cut(interest, commission);
+        self.borrow_index = borrow_index;
+        self.supply_index = supply_index;
+        self.total_borrow += interest - term_interest;
+        self.reserve += reserve_cut;
+        self.add_owner_revenue(owner_cut);
+        self.total_lend_asset_deposit += interest - commission - reserve_cut - owner_cut;
+        self.release_matured_terms(current_time_sec);
+        self.last_index_update_timestamp_sec = current_time_sec;
+        commission
     }
 
     pub fn internal_pay_loan(
@@ -282,21 +744,18 @@ impl Pool {
         pay_amount: Balance,
         foundation_commission: u64,
     ) -> (AssetId, Balance) {
-        self.update_acc_interest_per_share();
+        let commission = self.update_indexes(foundation_commission);
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        let commission = account_deposit.update_account(
-            self.fixed_interest_rate,
-            &self.acc_interest_per_share,
-            foundation_commission,
-        );
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
         log!("pool: paying amount {}", pay_amount);
-        let (paid_borrow, added_liquidity) = account_deposit.internal_pay_loan(pay_amount);
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
+        let is_term_loan = account_deposit.is_term_loan();
+        let (paid_debt, added_liquidity) = account_deposit.internal_pay_loan(pay_amount);
         self.account_deposits.insert(account_id, &account_deposit);
 
-        self.total_borrow -= paid_borrow;
+        self.reduce_total_borrow(is_term_loan, paid_debt);
         self.total_lend_asset_deposit =
             self.total_lend_asset_deposit + added_liquidity - pay_amount;
+        self.fill_withdrawal_queue();
         (self.lend_token_id.clone(), commission)
     }
 
@@ -305,26 +764,27 @@ impl Pool {
         liquidated_account_id: AccountId,
         liquidated_borrow_amount: Balance,
         liquidator_account_id: AccountId,
+        seized_token_id: &AssetId,
         lend_token_info: &TokenInfo,
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
+        extra_collateral_prices: &Vec<(TokenInfo, Price)>,
         liquidation_marginal: u64,
         foundation_commission: u64,
     ) -> HashMap<AssetId, Balance> {
-        self.update_acc_interest_per_share();
+        let commission = self.update_indexes(foundation_commission);
         let mut ret = HashMap::new();
         let mut liquidator_account_deposit =
             self.get_account_deposit_or_revert(&liquidator_account_id);
-        let mut commission = liquidator_account_deposit.update_account(
-            self.fixed_interest_rate,
-            &self.acc_interest_per_share,
-            foundation_commission,
-        );
+        liquidator_account_deposit.update_account(&self.borrow_index, &self.supply_index);
         let liquidator_account_lend_token_deposit_amount =
             liquidator_account_deposit.get_token_deposit(&self.lend_token_id);
         require!(
-            liquidator_account_lend_token_deposit_amount >= liquidated_borrow_amount,
+            liquidator_account_lend_token_deposit_amount
+                >= liquidated_borrow_amount
+                    + liquidator_account_deposit.delegated_out
+                    + liquidator_account_deposit.queued_withdrawal,
             "liquidation maker insufficient deposit"
         );
 
@@ -336,61 +796,61 @@ impl Pool {
         let mut liquidated_account_deposit =
             self.get_account_deposit_or_revert(&liquidated_account_id);
         let borrowed_before = liquidated_account_deposit.borrow_amount;
-        commission += liquidated_account_deposit.update_account(
-            self.fixed_interest_rate,
-            &self.acc_interest_per_share,
-            foundation_commission,
-        );
+        liquidated_account_deposit.update_account(&self.borrow_index, &self.supply_index);
+        let is_term_loan = liquidated_account_deposit.is_term_loan();
+        let is_expired = liquidated_account_deposit.is_expired(self.maturity_grace_period_sec);
         let liquidated_account_cr = self.compute_current_cr(
             liquidated_account_id.clone(),
             lend_token_info,
             lend_token_price,
             collateral_token_info,
             collateral_token_price,
+            extra_collateral_prices,
             None,
             None,
             None,
         );
         require!(
-            liquidated_account_cr < self.min_cr,
+            liquidated_account_cr < self.min_cr || is_expired,
             "Liquidated account is not under collateral ratio for liquidation"
         );
 
-        let liquidated_borrow_value =
-            compute_token_value(liquidated_borrow_amount.clone(), lend_token_price);
-        let mut collateral_token_price_after_bonus = collateral_token_price.clone();
-        collateral_token_price_after_bonus.multiplier = U128(
-            collateral_token_price_after_bonus.multiplier.0
-                * (LIQUIDATION_BONUS_DIVISOR - (self.liquidation_bonus as u128))
-                / LIQUIDATION_BONUS_DIVISOR,
-        );
-        let to_liquidate_collateral_amount =
-            U256::from(10u128.pow(collateral_token_info.decimals as u32))
-                * liquidated_borrow_value
-                * U256::from(10u128.pow(collateral_token_price_after_bonus.decimals as u32))
-                / (U256::from(collateral_token_price_after_bonus.multiplier.0)
-                    * U256::from(10u128.pow(lend_token_info.decimals as u32)));
-        let to_liquidate_collateral_amount = to_liquidate_collateral_amount.as_u128();
-        let to_liquidate_collateral_amount_to_cover_liquidator =
-            U256::from(10u128.pow(collateral_token_info.decimals as u32))
-                * liquidated_borrow_value
-                * U256::from(10u128.pow(collateral_token_price.decimals as u32))
-                / (U256::from(collateral_token_price.multiplier.0)
-                    * U256::from(10u128.pow(lend_token_info.decimals as u32)));
-        let to_liquidate_collateral_amount_to_cover_liquidator =
-            to_liquidate_collateral_amount_to_cover_liquidator.as_u128();
-
-        let (actual_borrow_paid, remain) =
+        let (seized_token_info, seized_token_price, _, seized_liquidation_bonus) = self
+            .get_collateral_params(
+                seized_token_id,
+                collateral_token_info,
+                collateral_token_price,
+                extra_collateral_prices,
+            );
+        let collateral_token_price_after_bonus =
+            get_price_after_bonus(&seized_token_price, seized_liquidation_bonus);
+        let (to_liquidate_collateral_amount, collateral_to_liquidator, collateral_to_foundation) =
+            compute_liquidation_amounts(
+                liquidated_borrow_amount,
+                lend_token_info,
+                lend_token_price,
+                &seized_token_info,
+                &seized_token_price,
+                seized_liquidation_bonus,
+                liquidation_marginal,
+            );
+
+        let (paid_debt, remain) =
             liquidated_account_deposit.internal_pay_loan(liquidated_borrow_amount);
 
         let liquidated_collateral_amount_before =
-            liquidated_account_deposit.get_token_deposit(&self.collateral_token_id);
-        liquidated_account_deposit.reduce_collateral(to_liquidate_collateral_amount.clone());
+            liquidated_account_deposit.get_token_deposit(seized_token_id);
+        if seized_token_id.clone() == self.collateral_token_id {
+            liquidated_account_deposit.reduce_collateral(to_liquidate_collateral_amount.clone());
+        } else {
+            liquidated_account_deposit
+                .reduce_extra_collateral(seized_token_id, to_liquidate_collateral_amount);
+        }
         liquidator_account_deposit.reduce_lend_token_deposit(liquidated_borrow_amount);
 
         self.total_lend_asset_deposit =
             self.total_lend_asset_deposit - liquidated_borrow_amount + remain;
-        self.total_borrow = self.total_borrow - actual_borrow_paid;
+        self.reduce_total_borrow(is_term_loan, paid_debt);
 
         let liquidated_account_cr = self.compute_current_cr(
             liquidated_account_id.clone(),
@@ -398,27 +858,23 @@ impl Pool {
             lend_token_price,
             collateral_token_info,
             collateral_token_price,
+            extra_collateral_prices,
             None,
             None,
             None,
         );
+        //an expired term loan can be liquidated until fully repaid
         require!(
-            liquidated_account_cr < self.min_cr,
+            liquidated_account_cr < self.min_cr || is_expired,
             "invalid collateral ratio after liquidation"
         );
 
-        let collateral_to_foundation = (to_liquidate_collateral_amount
-            - to_liquidate_collateral_amount_to_cover_liquidator)
-            * (liquidation_marginal as u128)
-            / LIQUIDATION_MARGINAL_DIVISOR;
-        let collateral_to_liquidator =
-            to_liquidate_collateral_amount - collateral_to_foundation.clone();
-        liquidator_account_deposit.internal_deposit_collateral(&collateral_to_liquidator);
-
-        liquidated_account_deposit
-            .update_lending_interest_profit_debt(&self.acc_interest_per_share);
-        liquidator_account_deposit
-            .update_lending_interest_profit_debt(&self.acc_interest_per_share);
+        if seized_token_id.clone() == self.collateral_token_id {
+            liquidator_account_deposit.internal_deposit_collateral(&collateral_to_liquidator);
+        } else {
+            liquidator_account_deposit
+                .internal_deposit_extra_collateral(seized_token_id, &collateral_to_liquidator);
+        }
 
         //save accounts
         self.account_deposits
@@ -427,7 +883,7 @@ impl Pool {
             .insert(&liquidator_account_id, &liquidator_account_deposit);
 
         let liquidated_collateral_amount_after =
-            liquidated_account_deposit.get_token_deposit(&self.collateral_token_id);
+            liquidated_account_deposit.get_token_deposit(seized_token_id);
         let borrowed_after = liquidated_account_deposit.borrow_amount;
 
         let liquidaion_history = Liquidation {
@@ -435,7 +891,7 @@ impl Pool {
             liquidated_account_id: liquidated_account_id,
             liquidator_account_id: liquidator_account_id,
             lend_token_id: self.lend_token_id.clone(),
-            collateral_token_id: self.collateral_token_id.clone(),
+            collateral_token_id: seized_token_id.clone(),
             liquidated_collateral_amount_before: U128(liquidated_collateral_amount_before),
             liquidated_collateral_amount_after: U128(liquidated_collateral_amount_after),
             borrowed_before: U128(borrowed_before),
@@ -444,40 +900,341 @@ impl Pool {
             liquidator_collateral_amount_received: U128(collateral_to_liquidator),
             treasury_collateral_amount_received: U128(collateral_to_foundation),
             liquidation_price: collateral_token_price_after_bonus, //price with liquidation fee
-            price: collateral_token_price.clone(),
+            price: seized_token_price,
         };
         self.liquidation_history.push(liquidaion_history);
+        self.fill_withdrawal_queue();
 
-        ret.insert(self.collateral_token_id.clone(), collateral_to_foundation);
+        ret.insert(seized_token_id.clone(), collateral_to_foundation);
         ret.insert(self.lend_token_id.clone(), commission);
         ret
     }
 
-    pub fn internal_pay_borrowing_and_interest(
+    /// Sets the credit line of `delegator_id` to `borrower_id`, 0 revokes it.
+    /// A line cannot be cut below the borrower's debt nor shortened while it is drawn.
+    pub fn internal_delegate_credit(
+        &mut self,
+        delegator_id: &AccountId,
+        borrower_id: &AccountId,
+        amount: Balance,
+        expiry_timestamp_sec: u64,
+        foundation_commission: u64,
+    ) -> (AssetId, Balance) {
+        require!(delegator_id != borrower_id, "cannot delegate credit to self");
+        let commission = self.update_indexes(foundation_commission);
+        let mut delegator_deposit = self.get_account_deposit_or_revert(delegator_id);
+        delegator_deposit.update_account(&self.borrow_index, &self.supply_index);
+        let mut borrower_deposit = self.get_account_deposit_or_revert(borrower_id);
+        borrower_deposit.update_account(&self.borrow_index, &self.supply_index);
+
+        if let Some(current_delegator_id) = borrower_deposit.credit_delegator_id.clone() {
+            require!(
+                current_delegator_id == delegator_id.clone(),
+                "borrower already has a credit line from another account"
+            );
+        }
+        let owed = borrower_deposit.get_owed_lend_token_amount();
+        if owed > 0 && borrower_deposit.delegated_in > 0 {
+            require!(amount >= owed, "credit line lower than the borrower debt");
+            require!(
+                expiry_timestamp_sec >= borrower_deposit.credit_expiry_timestamp_sec,
+                "credit line cannot be shortened while drawn"
+            );
+        }
+        if amount > 0 {
+            require!(
+                self.status == PoolStatus::Active,
+                "pool does not accept new borrows"
+            );
+            require!(
+                self.is_allowed_borrower(borrower_id),
+                "not an allowed borrower"
+            );
+            require!(
+                expiry_timestamp_sec > env::block_timestamp_ms() / 1000,
+                "credit line already expired"
+            );
+        }
+
+        delegator_deposit.delegated_out =
+            delegator_deposit.delegated_out - borrower_deposit.delegated_in + amount;
+        require!(
+            delegator_deposit.get_token_deposit(&self.lend_token_id)
+                >= delegator_deposit.delegated_out + delegator_deposit.queued_withdrawal,
+            "lend token deposit lower than delegated credit"
+        );
+        borrower_deposit.delegated_in = amount;
+        if amount > 0 {
+            borrower_deposit.credit_delegator_id = Some(delegator_id.clone());
+            borrower_deposit.credit_expiry_timestamp_sec = expiry_timestamp_sec;
+        } else {
+            borrower_deposit.credit_delegator_id = None;
+            borrower_deposit.credit_expiry_timestamp_sec = 0;
+        }
+
+        self.account_deposits.insert(delegator_id, &delegator_deposit);
+        self.account_deposits.insert(borrower_id, &borrower_deposit);
+        (self.lend_token_id.clone(), commission)
+    }
+
+    /// Charges the debt left on an expired credit line to the delegator's lend token deposit
+    /// and closes the line. Anyone can call it, liquidators should first take the collateral.
+    pub fn internal_settle_credit_default(
+        &mut self,
+        borrower_id: &AccountId,
+        foundation_commission: u64,
+    ) -> (AssetId, Balance) {
+        let commission = self.update_indexes(foundation_commission);
+        let mut borrower_deposit = self.get_account_deposit_or_revert(borrower_id);
+        borrower_deposit.update_account(&self.borrow_index, &self.supply_index);
+        let delegator_id = borrower_deposit
+            .credit_delegator_id
+            .clone()
+            .expect("no credit line");
+        require!(
+            env::block_timestamp_ms() / 1000 > borrower_deposit.credit_expiry_timestamp_sec,
+            "credit line not expired"
+        );
+        let mut delegator_deposit = self.get_account_deposit_or_revert(&delegator_id);
+        delegator_deposit.update_account(&self.borrow_index, &self.supply_index);
+
+        let is_term_loan = borrower_deposit.is_term_loan();
+        let charged = std::cmp::min(
+            std::cmp::min(
+                borrower_deposit.get_owed_lend_token_amount(),
+                borrower_deposit.delegated_in,
+            ),
+            delegator_deposit.get_token_deposit(&self.lend_token_id),
+        );
+        let written_off = borrower_deposit.write_off_debt(charged);
+        delegator_deposit.reduce_lend_token_deposit(written_off);
+        self.reduce_total_borrow(is_term_loan, written_off);
+        self.total_lend_asset_deposit = self.total_lend_asset_deposit.saturating_sub(written_off);
+
+        delegator_deposit.delegated_out = delegator_deposit
+            .delegated_out
+            .saturating_sub(borrower_deposit.delegated_in);
+        borrower_deposit.delegated_in = 0;
+        borrower_deposit.credit_delegator_id = None;
+        borrower_deposit.credit_expiry_timestamp_sec = 0;
+        log!(
+            "credit default of {} charged {} to {}",
+            borrower_id,
+            written_off,
+            delegator_id
+        );
+
+        self.account_deposits.insert(&delegator_id, &delegator_deposit);
+        self.account_deposits.insert(borrower_id, &borrower_deposit);
+        (self.lend_token_id.clone(), commission)
+    }
+
+    //registers the account with an empty lend token deposit so receiving receipts needs no storage
+    pub fn share_register(&mut self, account_id: &AccountId) {
+        self.internal_register_account_if_not(account_id);
+        let mut account_deposit = self.get_account_deposit(account_id);
+        if account_deposit.deposits.get(&self.lend_token_id).is_none() {
+            account_deposit.deposits.insert(&self.lend_token_id, &0);
+            self.account_deposits.insert(account_id, &account_deposit);
+        }
+    }
+
+    /// Moves lend token deposit, which is the pool receipt balance, with the interest earned
+    /// up to now. Both accounts continue from the current supply index.
+    /// The receiver is checked against the lender allow-list by the caller.
+    pub fn internal_share_transfer(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: Balance,
+        foundation_commission: u64,
+    ) -> (AssetId, Balance) {
+        let commission = self.update_indexes(foundation_commission);
+        let mut sender_deposit = self.get_account_deposit_or_revert(sender_id);
+        sender_deposit.update_account(&self.borrow_index, &self.supply_index);
+        let mut receiver_deposit = self.get_account_deposit_or_revert(receiver_id);
+        receiver_deposit.update_account(&self.borrow_index, &self.supply_index);
+
+        require!(
+            amount <= sender_deposit.get_free_lend_token_deposit(),
+            "insufficient lend token deposit"
+        );
+        sender_deposit.reduce_lend_token_deposit(amount);
+        receiver_deposit.internal_deposit_lend_token(&amount);
+
+        self.account_deposits.insert(sender_id, &sender_deposit);
+        self.account_deposits.insert(receiver_id, &receiver_deposit);
+        (self.lend_token_id.clone(), commission)
+    }
+
+    //lend token deposits of all accounts, pending interest included
+    pub fn share_total_balance(&self, foundation_commission: u64) -> Balance {
+        let (_, _, interest, commission) = self.compute_current_indexes(foundation_commission);
+        self.total_lend_asset_deposit + interest
+            - commission
+            - self.compute_reserve_cut(interest, commission)
+            - self.compute_owner_cut(interest, commission)
+    }
+
+    /// Queues `amount` of the lend token deposit for withdrawal and fills what the current
+    /// liquidity allows. The amount stays in the deposit, earning interest and sharing bad debt, until filled.
+    pub fn internal_queue_withdrawal(
+        &mut self,
+        account_id: &AccountId,
+        amount: Balance,
+        foundation_commission: u64,
+    ) -> (AssetId, Balance) {
+        require!(amount > 0, "amount must be positive");
+        require!(
+            amount >= self.min_lend_token_deposit,
+            "lower than min deposit"
+        );
+        let commission = self.update_indexes(foundation_commission);
+        let mut account_deposit = self.get_account_deposit_or_revert(account_id);
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
+        require!(
+            amount <= account_deposit.get_free_lend_token_deposit(),
+            "insufficient lend token deposit"
+        );
+        account_deposit.queued_withdrawal += amount;
+        self.account_deposits.insert(account_id, &account_deposit);
+
+        self.total_queued_withdrawal += amount;
+        self.withdrawal_queue.push(QueuedWithdrawal {
+            account_id: account_id.clone(),
+            amount: U128(amount),
+            timestamp_sec: env::block_timestamp_ms() / 1000,
+        });
+        self.fill_withdrawal_queue();
+        (self.lend_token_id.clone(), commission)
+    }
+
+    //fills queued withdrawals in order with the liquidity left, up to MAX_WITHDRAWAL_FILLS_PER_CALL of them,
+    //pool indexes must be up to date
+    pub fn fill_withdrawal_queue(&mut self) {
+        let mut available = self
+            .total_lend_asset_deposit
+            .saturating_sub(self.get_total_borrow());
+        let mut filled_requests = 0;
+        for request in self
+            .withdrawal_queue
+            .iter_mut()
+            .take(MAX_WITHDRAWAL_FILLS_PER_CALL)
+        {
+            if available == 0 {
+                break;
+            }
+            let mut account_deposit = self
+                .account_deposits
+                .get(&request.account_id)
+                .expect("account not found");
+            account_deposit.update_account(&self.borrow_index, &self.supply_index);
+            //bad debt can leave less than what was queued
+            let cancelled = request.amount.0.saturating_sub(
+                account_deposit
+                    .get_token_deposit(&self.lend_token_id)
+                    .saturating_sub(account_deposit.delegated_out),
+            );
+            let filled = std::cmp::min(available, request.amount.0 - cancelled);
+            account_deposit.reduce_lend_token_deposit(filled);
+            account_deposit.queued_withdrawal -= filled + cancelled;
+            account_deposit.claimable_withdrawal += filled;
+            self.account_deposits
+                .insert(&request.account_id, &account_deposit);
+
+            request.amount = U128(request.amount.0 - filled - cancelled);
+            available -= filled;
+            self.total_lend_asset_deposit -= filled;
+            self.total_queued_withdrawal -= filled + cancelled;
+            self.total_claimable_withdrawal += filled;
+            if request.amount.0 == 0 {
+                filled_requests += 1;
+            }
+        }
+        self.withdrawal_queue.drain(0..filled_requests);
+    }
+
+    //returns the filled amount to send to the account
+    pub fn internal_claim_withdrawal(&mut self, account_id: &AccountId) -> Balance {
+        let mut account_deposit = self.get_account_deposit_or_revert(account_id);
+        let amount = account_deposit.claimable_withdrawal;
+        require!(amount > 0, "nothing to claim");
+        account_deposit.claimable_withdrawal = 0;
+        self.account_deposits.insert(account_id, &account_deposit);
+        self.total_claimable_withdrawal -= amount;
+        amount
+    }
+
+    /// Writes off the debt of an account with no collateral left, covering it from the
+    /// reserve first and charging the rest to lenders by lowering the supply index.
+    pub fn internal_write_off_bad_debt(
         &mut self,
         account_id: &AccountId,
         foundation_commission: u64,
     ) -> (AssetId, Balance) {
-        self.update_acc_interest_per_share();
+        let commission = self.update_indexes(foundation_commission);
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        let commission = account_deposit.update_account(
-            self.fixed_interest_rate,
-            &self.acc_interest_per_share,
-            foundation_commission,
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
+        require!(
+            account_deposit.get_token_deposit(&self.collateral_token_id) == 0
+                && self
+                    .extra_collaterals
+                    .iter()
+                    .all(|c| account_deposit.get_token_deposit(&c.token_id) == 0),
+            "collateral left, liquidate first"
+        );
+        require!(
+            account_deposit.credit_delegator_id.is_none(),
+            "settle the credit line first"
         );
+        let is_term_loan = account_deposit.is_term_loan();
+        let bad_debt = account_deposit.write_off_debt(account_deposit.get_owed_lend_token_amount());
+        require!(bad_debt > 0, "no debt to write off");
+        self.account_deposits.insert(account_id, &account_deposit);
+        self.reduce_total_borrow(is_term_loan, bad_debt);
+
+        let from_reserve = std::cmp::min(self.reserve, bad_debt);
+        self.reserve -= from_reserve;
+        let lenders_loss = std::cmp::min(bad_debt - from_reserve, self.total_lend_asset_deposit);
+        if lenders_loss > 0 {
+            self.supply_index -= (U256::from(self.supply_index) * U256::from(lenders_loss)
+                / U256::from(self.total_lend_asset_deposit))
+            .as_u128();
+            self.total_lend_asset_deposit -= lenders_loss;
+        }
+        self.total_bad_debt += bad_debt;
+        log!(
+            "bad debt {} of {} written off, {} from reserve, {} from lenders",
+            bad_debt,
+            account_id,
+            from_reserve,
+            lenders_loss
+        );
+        (self.lend_token_id.clone(), commission)
+    }
+
+    pub fn internal_pay_borrowing_and_interest(
+        &mut self,
+        account_id: &AccountId,
+        foundation_commission: u64,
+    ) -> (AssetId, Balance) {
+        let commission = self.update_indexes(foundation_commission);
+        let mut account_deposit = self.get_account_deposit_or_revert(account_id);
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
         let borrowing_and_interest = self.get_borrow_and_interest(account_id);
         log!(
             "internal_pay_borrowing_and_interest: borrowing_and_interest {}",
             borrowing_and_interest
         );
-        let (paid_borrow, added_liquidity) =
+        let is_term_loan = account_deposit.is_term_loan();
+        let (paid_debt, added_liquidity) =
             account_deposit.internal_pay_loan(borrowing_and_interest);
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
         self.account_deposits.insert(account_id, &account_deposit);
 
-        self.total_borrow -= paid_borrow;
+        self.reduce_total_borrow(is_term_loan, paid_debt);
         self.total_lend_asset_deposit =
             self.total_lend_asset_deposit + added_liquidity - borrowing_and_interest;
+        self.fill_withdrawal_queue();
         (self.lend_token_id.clone(), commission)
     }
 
@@ -489,23 +1246,15 @@ impl Pool {
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
+        extra_collateral_prices: &Vec<(TokenInfo, Price)>,
         foundation_commission: u64,
     ) -> (Balance, AssetId, Balance) {
-        self.update_acc_interest_per_share();
+        let mut commission = self.update_indexes(foundation_commission);
         let mut account_deposit = self.get_account_deposit_or_revert(account_id);
-        let mut commission = account_deposit.update_account(
-            self.fixed_interest_rate,
-            &self.acc_interest_per_share,
-            foundation_commission,
-        );
-        account_deposit.update_lending_interest_profit_debt(&self.acc_interest_per_share);
+        account_deposit.update_account(&self.borrow_index, &self.supply_index);
         self.account_deposits.insert(account_id, &account_deposit);
-        //get deposit
-        let mut remaining_deposit = account_deposit.get_token_deposit(token_id);
-        remaining_deposit += account_deposit.get_pending_unpaid_lending_interest_profit(
-            &self.acc_interest_per_share,
-            foundation_commission,
-        );
+        //get deposit, lending interest included
+        let remaining_deposit = account_deposit.get_token_deposit(token_id);
         //withdraw
         if remaining_deposit > 0 {
             let (_, amount1) = self.internal_withdraw_from_account(
@@ -516,6 +1265,7 @@ impl Pool {
                 lend_token_price,
                 collateral_token_info,
                 collateral_token_price,
+                extra_collateral_prices,
                 foundation_commission,
             );
             commission += amount1;
@@ -549,6 +1299,7 @@ impl Pool {
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
+        extra_collateral_prices: &Vec<(TokenInfo, Price)>,
         collateral_amount: Option<Balance>,
         borrow: Option<Balance>,     //borrow more
         pay_amount: Option<Balance>, //pay back
@@ -565,15 +1316,22 @@ impl Pool {
         }
 
         let account_deposit = self.get_account_deposit(&account_id);
+        let (borrow_index, _, _, _) = self.compute_current_indexes(0);
+        let extra_collateral = self.compute_extra_collateral(
+            &account_deposit,
+            collateral_token_info,
+            collateral_token_price,
+            extra_collateral_prices,
+        );
         account_deposit.compute_current_cr(
             lend_token_info,
             lend_token_price,
             collateral_token_info,
             collateral_token_price,
-            collateral_amount,
+            Some(collateral_amount.unwrap_or(0) + extra_collateral),
             borrow,
             pay_amount,
-            self.fixed_interest_rate,
+            &borrow_index,
         )
     }
 
@@ -584,19 +1342,27 @@ impl Pool {
         lend_token_price: &Price,
         collateral_token_info: &TokenInfo,
         collateral_token_price: &Price,
+        extra_collateral_prices: &Vec<(TokenInfo, Price)>,
         additional_collateral_amount: Option<Balance>,
     ) -> Balance {
         if self.account_deposits.get(account_id).is_none() {
             return 0;
         }
         let account_deposit = self.get_account_deposit(account_id);
+        let (borrow_index, _, _, _) = self.compute_current_indexes(0);
+        let extra_collateral = self.compute_extra_collateral(
+            &account_deposit,
+            collateral_token_info,
+            collateral_token_price,
+            extra_collateral_prices,
+        );
         let max_borrowable = account_deposit.compute_max_borrowable(
             lend_token_info,
             lend_token_price,
             collateral_token_info,
             collateral_token_price,
-            additional_collateral_amount,
-            self.fixed_interest_rate,
+            Some(additional_collateral_amount.unwrap_or(0) + extra_collateral),
+            &borrow_index,
             self.min_cr,
         );
         let max_of_pool = self.get_max_borrowable_of_pool();
@@ -606,70 +1372,322 @@ impl Pool {
         max_borrowable
     }
 
+    /// Largest debt repayment `internal_liquidate` accepts for the account when seizing
+    /// `seized_token_id`, 0 if it cannot be liquidated. It is bounded by that collateral and,
+    /// unless the term loan expired, by the CR having to stay under min_cr after liquidation.
+    pub fn compute_max_liquidatable_borrow(
+        &self,
+        account_id: &AccountId,
+        seized_token_id: &AssetId,
+        lend_token_info: &TokenInfo,
+        lend_token_price: &Price,
+        collateral_token_info: &TokenInfo,
+        collateral_token_price: &Price,
+        extra_collateral_prices: &Vec<(TokenInfo, Price)>,
+    ) -> Balance {
+        if self.account_deposits.get(account_id).is_none() {
+            return 0;
+        }
+        let account_deposit = self.get_account_deposit(account_id);
+        let (borrow_index, _, _, _) = self.compute_current_indexes(0);
+        let owed = account_deposit.borrow_amount + account_deposit.get_interest_owed(&borrow_index);
+        if owed == 0 {
+            return 0;
+        }
+        let is_expired = account_deposit.is_expired(self.maturity_grace_period_sec);
+        let extra_collateral = self.compute_extra_collateral(
+            &account_deposit,
+            collateral_token_info,
+            collateral_token_price,
+            extra_collateral_prices,
+        );
+        let cr = account_deposit.compute_current_cr(
+            lend_token_info,
+            lend_token_price,
+            collateral_token_info,
+            collateral_token_price,
+            Some(extra_collateral),
+            None,
+            None,
+            &borrow_index,
+        );
+        if cr >= self.min_cr && !is_expired {
+            return 0;
+        }
+
+        //collateral valued in lend token
+        let to_lend_token = |amount: Balance, token_info: &TokenInfo, price: &Price| -> Balance {
+            (compute_token_value(amount, price)
+                * U256::from(10u128.pow(lend_token_info.decimals as u32))
+                * U256::from(10u128.pow(lend_token_price.decimals as u32))
+                / (U256::from(10u128.pow(token_info.decimals as u32))
+                    * U256::from(lend_token_price.multiplier.0)))
+            .as_u128()
+        };
+        let (seized_token_info, seized_token_price, seized_min_cr, seized_liquidation_bonus) = self
+            .get_collateral_params(
+                seized_token_id,
+                collateral_token_info,
+                collateral_token_price,
+                extra_collateral_prices,
+            );
+        let seized_amount = account_deposit.get_token_deposit(seized_token_id);
+        let discount = LIQUIDATION_BONUS_DIVISOR - seized_liquidation_bonus as u128;
+        let mut max_repay = std::cmp::min(
+            owed,
+            to_lend_token(seized_amount, &seized_token_info, &seized_token_price) * discount
+                / LIQUIDATION_BONUS_DIVISOR,
+        );
+        if !is_expired {
+            //(collateral - repay / discount * min_cr / seized_min_cr) < min_cr * (debt - repay),
+            //the seized collateral is rounded down so one more unit is assumed to stay with the account
+            let collateral_upper_bound = to_lend_token(
+                account_deposit.get_token_deposit(&self.collateral_token_id) + extra_collateral,
+                collateral_token_info,
+                collateral_token_price,
+            ) + (to_lend_token(1, &seized_token_info, &seized_token_price) + 1)
+                * (self.min_cr as u128)
+                / (seized_min_cr as u128)
+                + 1;
+            let min_cr = self.min_cr as u128;
+            let seized_min_cr = seized_min_cr as u128;
+            let debt = account_deposit.get_collateralized_debt(&borrow_index);
+            let denominator = discount * seized_min_cr;
+            let divisor_squared = COLLATERAL_RATIO_DIVISOR * LIQUIDATION_BONUS_DIVISOR;
+            if denominator > divisor_squared {
+                let numerator = U256::from(min_cr * debt)
+                    .saturating_sub(U256::from(COLLATERAL_RATIO_DIVISOR * collateral_upper_bound))
+                    * U256::from(denominator)
+                    / (U256::from(min_cr) * U256::from(denominator - divisor_squared));
+                max_repay = std::cmp::min(max_repay, numerator.as_u128().saturating_sub(1));
+            }
+        }
+        max_repay
+    }
+
     pub fn get_max_borrowable_of_pool(&self) -> Balance {
         let max_of_pool =
             self.total_lend_asset_deposit * self.max_utilization as u128 / UTILIZATION_DIVISOR;
-        max_of_pool - self.total_borrow
+        max_of_pool.saturating_sub(self.get_total_borrow())
     }
 
-    pub fn get_deposits(&self, account_id: &AccountId) -> HashMap<AssetId, U128> {
-        if self.account_deposits.get(account_id).is_none() {
-            return HashMap::new();
+    pub fn get_total_borrow(&self) -> Balance {
+        self.total_borrow + self.total_term_borrow
+    }
+
+    //None for an open-ended loan, a pool with terms only lends for one of its terms
+    pub fn get_loan_term(&self, term_id: Option<u32>) -> Option<LoanTerm> {
+        if self.loan_terms.is_empty() {
+            require!(term_id.is_none(), "pool does not offer loan terms");
+            return None;
         }
-        let account_deposit = self.get_account_deposit(account_id);
-        account_deposit.get_deposits()
+        let term_id = term_id.expect("loan term required") as usize;
+        Some(self.loan_terms.get(term_id).expect("invalid loan term").clone())
     }
 
-    pub fn get_current_acc_interest_per_share(&self) -> Balance {
-        if self.total_lend_asset_deposit == 0 {
-            return self.acc_interest_per_share;
+    fn reduce_total_borrow(&mut self, is_term_loan: bool, paid_debt: Balance) {
+        if is_term_loan {
+            self.total_term_borrow = self.total_term_borrow.saturating_sub(paid_debt);
+        } else {
+            self.total_borrow = self.total_borrow.saturating_sub(paid_debt);
         }
-        let mut last_acc_interest_update_timestamp_sec =
-            self.last_acc_interest_update_timestamp_sec;
-        if self.total_borrow == 0 {
-            last_acc_interest_update_timestamp_sec = get_last_interest_recal_time_sec();
+    }
+
+    //the locked interest of a new term loan is earned by lenders second by second until maturity
+    pub fn add_term_interest(&mut self, interest: Balance, duration_sec: u64, maturity_timestamp_sec: u64) {
+        let interest_per_sec = interest / duration_sec as u128;
+        let remainder = interest - interest_per_sec * duration_sec as u128;
+        let (ending_per_sec, ending_remainder) =
+            self.term_maturities.get(&maturity_timestamp_sec).unwrap_or((0, 0));
+        self.term_maturities.insert(
+            &maturity_timestamp_sec,
+            &(ending_per_sec + interest_per_sec, ending_remainder + remainder),
+        );
+        self.term_interest_per_sec += interest_per_sec;
+    }
+
+    //term interest earned by lenders from the last index update to `current_time_sec`
+    pub fn compute_term_interest(&self, current_time_sec: u64) -> Balance {
+        let mut interest = 0;
+        let mut interest_per_sec = self.term_interest_per_sec;
+        let mut from_sec = self.last_index_update_timestamp_sec;
+        for (maturity_timestamp_sec, (ending_per_sec, remainder)) in self.term_maturities.iter() {
+            if maturity_timestamp_sec > current_time_sec {
+                break;
+            }
+            interest += interest_per_sec * (maturity_timestamp_sec - from_sec) as u128 + remainder;
+            interest_per_sec -= ending_per_sec;
+            from_sec = maturity_timestamp_sec;
         }
-        let elapsed_time =
-            get_next_interest_recal_time_sec() - last_acc_interest_update_timestamp_sec;
-        let elapsed_time = elapsed_time as u128;
-        let generated_interest =
-            self.total_borrow * elapsed_time * (self.fixed_interest_rate as u128)
-                / (SECONDS_PER_YEAR * INTEREST_RATE_DIVISOR);
-        return self.acc_interest_per_share
-            + generated_interest * ACC_INTEREST_PER_SHARE_MULTIPLIER
-                / self.total_lend_asset_deposit;
+        interest + interest_per_sec * (current_time_sec - from_sec) as u128
     }
 
-    pub fn get_pending_unpaid_lending_interest_profit(
+    //stops the per second interest of term loans matured by `current_time_sec`
+    fn release_matured_terms(&mut self, current_time_sec: u64) {
+        let matured: Vec<(u64, (Balance, Balance))> = self
+            .term_maturities
+            .iter()
+            .take_while(|(maturity_timestamp_sec, _)| *maturity_timestamp_sec <= current_time_sec)
+            .collect();
+        for (maturity_timestamp_sec, (ending_per_sec, _)) in matured {
+            self.term_maturities.remove(&maturity_timestamp_sec);
+            self.term_interest_per_sec -= ending_per_sec;
+        }
+    }
+
+    pub fn get_deposits(
         &self,
         account_id: &AccountId,
         foundation_commission: u64,
-    ) -> Balance {
+    ) -> HashMap<AssetId, U128> {
         if self.account_deposits.get(account_id).is_none() {
-            return 0;
+            return HashMap::new();
         }
         let account_deposit = self.get_account_deposit(account_id);
-        account_deposit.get_pending_unpaid_lending_interest_profit(
-            &self.get_current_acc_interest_per_share(),
-            foundation_commission,
+        let (_, supply_index, _, _) = self.compute_current_indexes(foundation_commission);
+        account_deposit.get_deposits(&supply_index)
+    }
+
+    //lend token deposit the account can move now, pending interest included
+    pub fn get_free_lend_token_deposit(&self, account_id: &AccountId, foundation_commission: u64) -> Balance {
+        match self.account_deposits.get(account_id) {
+            Some(account_deposit) => {
+                let (_, supply_index, _, _) = self.compute_current_indexes(foundation_commission);
+                apply_index_growth(
+                    account_deposit.get_token_deposit(&self.lend_token_id),
+                    &supply_index,
+                    &account_deposit.supply_index,
+                )
+                .saturating_sub(account_deposit.delegated_out + account_deposit.queued_withdrawal)
+            }
+            None => 0,
+        }
+    }
+
+    /// Borrow index, supply index, interest generated and the foundation commission on it,
+    /// accrued from the last update to now at the rate of the current utilization.
+    pub fn compute_current_indexes(
+        &self,
+        foundation_commission: u64,
+    ) -> (Balance, Balance, Balance, Balance) {
+        let current_time_sec = env::block_timestamp_ms() / 1000;
+        if current_time_sec <= self.last_index_update_timestamp_sec {
+            return (self.borrow_index, self.supply_index, 0, 0);
+        }
+        let elapsed_time = (current_time_sec - self.last_index_update_timestamp_sec) as u128;
+        let rate_over_elapsed_time = U256::from(self.get_current_borrow_rate())
+            * U256::from(elapsed_time)
+            * U256::from(INTEREST_INDEX_PRECISION)
+            / U256::from(SECONDS_PER_YEAR * INTEREST_RATE_DIVISOR);
+
+        let borrow_index = self.borrow_index
+            + (U256::from(self.borrow_index) * rate_over_elapsed_time
+                / U256::from(INTEREST_INDEX_PRECISION))
+            .as_u128();
+        let interest = (U256::from(self.total_borrow) * rate_over_elapsed_time
+            / U256::from(INTEREST_INDEX_PRECISION))
+        .as_u128()
+            + self.compute_term_interest(current_time_sec);
+        if interest == 0 {
+            return (borrow_index, self.supply_index, 0, 0);
+        }
+        if self.total_lend_asset_deposit == 0 {
+            return (borrow_index, self.supply_index, interest, interest);
+        }
+        let commission =
+            interest * (foundation_commission as u128) / FOUNDATION_COMMISSION_DIVISOR;
+        let lenders_interest = interest
+            - commission
+            - self.compute_reserve_cut(interest, commission)
+            - self.compute_owner_cut(interest, commission);
+        let supply_index = self.supply_index
+            + (U256::from(self.supply_index) * U256::from(lenders_interest)
+                / U256::from(self.total_lend_asset_deposit))
+            .as_u128();
+        (borrow_index, supply_index, interest, commission)
+    }
+
+    //share of the interest kept by the pool reserve, never more than what is left after commission
+    pub fn compute_reserve_cut(&self, interest: Balance, commission: Balance) -> Balance {
+        std::cmp::min(
+            interest * (self.reserve_factor as u128) / RESERVE_FACTOR_DIVISOR,
+            interest - commission,
         )
     }
 
-    pub fn get_pending_total_lending_interest_profit(&self, account_id: &AccountId) -> Balance {
-        if self.account_deposits.get(account_id).is_none() {
+    //share of the interest earned by the pool owner, taken after the commission and the reserve cut
+    pub fn compute_owner_cut(&self, interest: Balance, commission: Balance) -> Balance {
+        std::cmp::min(
+            interest * (self.owner_commission as u128) / RESERVE_FACTOR_DIVISOR,
+            interest - commission - self.compute_reserve_cut(interest, commission),
+        )
+    }
+
+    fn add_owner_revenue(&mut self, amount: Balance) {
+        self.owner_revenue += amount;
+        self.total_owner_revenue += amount;
+    }
+
+    //owner revenue including the share of the interest not accrued yet
+    pub fn get_pending_owner_revenue(&self, foundation_commission: u64) -> Balance {
+        let (_, _, interest, commission) = self.compute_current_indexes(foundation_commission);
+        self.owner_revenue + self.compute_owner_cut(interest, commission)
+    }
+
+    pub fn internal_claim_owner_revenue(&mut self, foundation_commission: u64) -> (Balance, Balance) {
+        let commission = self.update_indexes(foundation_commission);
+        let amount = self.owner_revenue;
+        require!(amount > 0, "no revenue to claim");
+        self.owner_revenue = 0;
+        (amount, commission)
+    }
+
+    pub fn get_utilization(&self) -> u64 {
+        let total_borrow = self.get_total_borrow();
+        if total_borrow == 0 {
             return 0;
         }
-        let account_deposit = self.get_account_deposit(account_id);
-        account_deposit
-            .get_pending_total_lending_interest_profit(&self.get_current_acc_interest_per_share())
+        //queued withdrawals do not count as liquidity, so rates rise while lenders wait to exit
+        let total_deposit = self
+            .total_lend_asset_deposit
+            .saturating_sub(self.total_queued_withdrawal);
+        if total_deposit <= total_borrow {
+            return UTILIZATION_DIVISOR as u64;
+        }
+        (total_borrow * UTILIZATION_DIVISOR / total_deposit) as u64
+    }
+
+    //totals only change right after an update, so this is the rate since the last update
+    pub fn get_current_borrow_rate(&self) -> u64 {
+        self.interest_rate_model
+            .compute_borrow_rate(self.get_utilization())
+    }
+
+    //open-ended loans at the current borrow rate plus the interest of running term loans
+    pub fn get_current_supply_rate(&self, foundation_commission: u64) -> u64 {
+        if self.total_lend_asset_deposit == 0 {
+            return 0;
+        }
+        let rate = ((self.get_current_borrow_rate() as u128) * self.total_borrow
+            + self.term_interest_per_sec * SECONDS_PER_YEAR * INTEREST_RATE_DIVISOR)
+            / self.total_lend_asset_deposit;
+        let lenders_share = (FOUNDATION_COMMISSION_DIVISOR - foundation_commission as u128)
+            .saturating_sub(self.reserve_factor as u128)
+            .saturating_sub(self.owner_commission as u128);
+        (rate * lenders_share / FOUNDATION_COMMISSION_DIVISOR) as u64
     }
 
-    pub fn get_total_interest_reward(&self, account_id: &AccountId) -> Balance {
+    pub fn get_pending_total_lending_interest_profit(
+        &self,
+        account_id: &AccountId,
+        foundation_commission: u64,
+    ) -> Balance {
         if self.account_deposits.get(account_id).is_none() {
             return 0;
         }
         let account_deposit = self.get_account_deposit(account_id);
-        account_deposit.get_total_interest_reward(&self.get_current_acc_interest_per_share())
+        let (_, supply_index, _, _) = self.compute_current_indexes(foundation_commission);
+        account_deposit.get_pending_total_lending_interest_profit(&supply_index)
     }
 
     pub fn get_pending_unpaid_borrowing_interest(&self, account_id: &AccountId) -> Balance {
@@ -677,7 +1695,8 @@ impl Pool {
             return 0;
         }
         let account_deposit = self.get_account_deposit(account_id);
-        account_deposit.get_pending_unpaid_borrowing_interest(self.fixed_interest_rate)
+        let (borrow_index, _, _, _) = self.compute_current_indexes(0);
+        account_deposit.get_pending_unpaid_borrowing_interest(&borrow_index)
     }
 
     pub fn compute_unrecorded_interest(&self, account_id: &AccountId) -> Balance {
@@ -685,7 +1704,8 @@ impl Pool {
             return 0;
         }
         let account_deposit = self.get_account_deposit(account_id);
-        account_deposit.compute_unrecorded_interest(self.fixed_interest_rate)
+        let (borrow_index, _, _, _) = self.compute_current_indexes(0);
+        account_deposit.compute_unrecorded_interest(&borrow_index)
     }
 
     pub fn get_pending_total_borrowing_interest(&self, account_id: &AccountId) -> Balance {
@@ -693,7 +1713,8 @@ impl Pool {
             return 0;
         }
         let account_deposit = self.get_account_deposit(account_id);
-        account_deposit.get_pending_total_borrowing_interest(self.fixed_interest_rate)
+        let (borrow_index, _, _, _) = self.compute_current_indexes(0);
+        account_deposit.get_pending_total_borrowing_interest(&borrow_index)
     }
 
     pub fn get_token_deposit(&self, account_id: &AccountId, token_id: &AssetId) -> Balance {
@@ -733,7 +1754,11 @@ pub fn new_pool_default(
         9000,
         0,
         0,
+        InterestRateModel::Fixed { rate: 1000 },
         1000,
-        1000,
+        vec![],
+        DEFAULT_MATURITY_GRACE_PERIOD_SEC,
+        0,
+        0,
     )
 }
@@ -0,0 +1,236 @@
+use crate::*;
+use near_sdk::require;
+
+#[near_bindgen]
+impl Contract {
+    pub(crate) fn assert_pool_owner(&self, pool_id: u32) {
+        self.abort_if_pool_id_valid(pool_id as usize);
+        if env::predecessor_account_id() != self.pools[pool_id as usize].owner_id {
+            env::panic_str("This method can be called only by the pool owner")
+        }
+    }
+
+    /// Announces new pool parameters, they take effect after POOL_CONFIG_NOTICE_PERIOD_SEC
+    /// through `apply_pool_config`. Omitted parameters keep their current value.
+    #[payable]
+    pub fn propose_pool_config(
+        &mut self,
+        pool_id: u32,
+        min_cr: Option<u64>,
+        max_utilization: Option<u64>,
+        min_lend_token_deposit: Option<U128>,
+        min_lend_token_borrow: Option<U128>,
+        liquidation_bonus: Option<u64>,
+        interest_rate_model: Option<InterestRateModel>,
+        reserve_factor: Option<u64>,
+        owner_commission: Option<u64>,
+    ) {
+        assert_one_yocto();
+        self.assert_pool_owner(pool_id);
+        self.pools[pool_id as usize].propose_config(
+            min_cr,
+            max_utilization,
+            min_lend_token_deposit,
+            min_lend_token_borrow,
+            liquidation_bonus,
+            interest_rate_model,
+            reserve_factor,
+            owner_commission,
+        );
+    }
+
+    /// Sends the owner share of the pool interest, in lend token, to the pool owner.
+    #[payable]
+    pub fn claim_pool_owner_revenue(&mut self, pool_id: u32) -> Promise {
+        assert_one_yocto();
+        self.abort_if_pause();
+        self.assert_pool_owner(pool_id);
+        let pool = &mut self.pools[pool_id as usize];
+        let token_id = pool.lend_token_id.clone();
+        let owner_id = pool.owner_id.clone();
+        let (amount, amount_for_foundation) =
+            pool.internal_claim_owner_revenue(self.foundation_commission);
+        self.deposit_foundation(&token_id, &amount_for_foundation);
+        self.internal_send_tokens(pool_id, &token_id, &owner_id, amount)
+    }
+
+    #[payable]
+    pub fn cancel_pool_config(&mut self, pool_id: u32) {
+        assert_one_yocto();
+        self.assert_pool_owner(pool_id);
+        let pool = &mut self.pools[pool_id as usize];
+        require!(pool.pending_config.is_some(), "no pending config");
+        pool.pending_config = None;
+    }
+
+    /// Can be called by anyone once the notice period is over.
+    pub fn apply_pool_config(&mut self, pool_id: u32) {
+        self.abort_if_pause();
+        self.abort_if_pool_id_valid(pool_id as usize);
+        let pool = &mut self.pools[pool_id as usize];
+        let token_id = pool.lend_token_id.clone();
+        let amount_for_foundation = pool.apply_pending_config(self.foundation_commission);
+        self.deposit_foundation(&token_id, &amount_for_foundation);
+    }
+
+    #[payable]
+    pub fn freeze_pool_borrows(&mut self, pool_id: u32) {
+        assert_one_yocto();
+        self.assert_pool_owner(pool_id);
+        let pool = &mut self.pools[pool_id as usize];
+        require!(pool.status == PoolStatus::Active, "pool is not active");
+        pool.status = PoolStatus::BorrowsFrozen;
+    }
+
+    #[payable]
+    pub fn unfreeze_pool_borrows(&mut self, pool_id: u32) {
+        assert_one_yocto();
+        self.assert_pool_owner(pool_id);
+        let pool = &mut self.pools[pool_id as usize];
+        require!(
+            pool.status == PoolStatus::BorrowsFrozen,
+            "pool borrows are not frozen"
+        );
+        pool.status = PoolStatus::Active;
+    }
+
+    /// Winds the pool down: no new borrows nor lend deposits, borrowers can still repay
+    /// and lenders withdraw as loans are paid back. Cannot be undone.
+    #[payable]
+    pub fn sunset_pool(&mut self, pool_id: u32) {
+        assert_one_yocto();
+        self.assert_pool_owner(pool_id);
+        let pool = &mut self.pools[pool_id as usize];
+        require!(pool.status != PoolStatus::Sunset, "pool already sunset");
+        pool.status = PoolStatus::Sunset;
+        pool.pending_config = None;
+    }
+
+    /// Restricted sides only accept the pool owner and the accounts on their allow-list.
+    /// Existing loans and deposits can still be repaid and withdrawn.
+    #[payable]
+    pub fn set_pool_access(&mut self, pool_id: u32, restrict_lenders: bool, restrict_borrowers: bool) {
+        assert_one_yocto();
+        self.assert_pool_owner(pool_id);
+        let pool = &mut self.pools[pool_id as usize];
+        pool.restrict_lenders = restrict_lenders;
+        pool.restrict_borrowers = restrict_borrowers;
+    }
+
+    #[payable]
+    pub fn add_pool_allowed_lenders(&mut self, pool_id: u32, account_ids: Vec<AccountId>) {
+        self.assert_pool_owner(pool_id);
+        let prev_storage = env::storage_usage();
+        let pool = &mut self.pools[pool_id as usize];
+        for account_id in account_ids.iter() {
+            pool.allowed_lenders.insert(account_id);
+        }
+        self.verify_storage(
+            &env::predecessor_account_id(),
+            prev_storage,
+            Some(env::attached_deposit()),
+        );
+    }
+
+    #[payable]
+    pub fn remove_pool_allowed_lenders(&mut self, pool_id: u32, account_ids: Vec<AccountId>) {
+        assert_one_yocto();
+        self.assert_pool_owner(pool_id);
+        let pool = &mut self.pools[pool_id as usize];
+        for account_id in account_ids.iter() {
+            pool.allowed_lenders.remove(account_id);
+        }
+    }
+
+    #[payable]
+    pub fn add_pool_allowed_borrowers(&mut self, pool_id: u32, account_ids: Vec<AccountId>) {
+        self.assert_pool_owner(pool_id);
+        let prev_storage = env::storage_usage();
+        let pool = &mut self.pools[pool_id as usize];
+        for account_id in account_ids.iter() {
+            pool.allowed_borrowers.insert(account_id);
+        }
+        self.verify_storage(
+            &env::predecessor_account_id(),
+            prev_storage,
+            Some(env::attached_deposit()),
+        );
+    }
+
+    #[payable]
+    pub fn remove_pool_allowed_borrowers(&mut self, pool_id: u32, account_ids: Vec<AccountId>) {
+        assert_one_yocto();
+        self.assert_pool_owner(pool_id);
+        let pool = &mut self.pools[pool_id as usize];
+        for account_id in account_ids.iter() {
+            pool.allowed_borrowers.remove(account_id);
+        }
+    }
+
+    /// Proposes another collateral token for the pool, accepted after POOL_CONFIG_NOTICE_PERIOD_SEC
+    /// through `apply_pool_config`. Borrowing capacity adds up over all collateral tokens, each
+    /// weighted by its own min_cr, which cannot be below the pool min_cr.
+    #[payable]
+    pub fn add_pool_collateral(
+        &mut self,
+        pool_id: u32,
+        token_id: AssetId,
+        min_cr: u64,
+        liquidation_bonus: u64,
+    ) {
+        self.assert_pool_owner(pool_id);
+        self.abort_if_unsupported_token(token_id.clone());
+        let prev_storage = env::storage_usage();
+        self.pools[pool_id as usize].propose_extra_collateral(&token_id, min_cr, liquidation_bonus);
+
+        //listed from the proposal, removed with the collateral
+        let mut token_to_list_collateral_pools = self
+            .token_to_list_collateral_pools
+            .get(&token_id)
+            .unwrap_or(vec![]);
+        if !token_to_list_collateral_pools.contains(&pool_id) {
+            token_to_list_collateral_pools.push(pool_id);
+            self.token_to_list_collateral_pools
+                .insert(&token_id, &token_to_list_collateral_pools);
+        }
+        self.verify_storage(
+            &env::predecessor_account_id(),
+            prev_storage,
+            Some(env::attached_deposit()),
+        );
+    }
+
+    /// Proposes new parameters for an extra collateral token, they take effect like `add_pool_collateral`.
+    #[payable]
+    pub fn update_pool_collateral(
+        &mut self,
+        pool_id: u32,
+        token_id: AssetId,
+        min_cr: u64,
+        liquidation_bonus: u64,
+    ) {
+        self.assert_pool_owner(pool_id);
+        let prev_storage = env::storage_usage();
+        self.pools[pool_id as usize].propose_extra_collateral_update(&token_id, min_cr, liquidation_bonus);
+        self.verify_storage(
+            &env::predecessor_account_id(),
+            prev_storage,
+            Some(env::attached_deposit()),
+        );
+    }
+
+    /// Stops accepting an extra collateral token, only while nobody has it deposited.
+    #[payable]
+    pub fn remove_pool_collateral(&mut self, pool_id: u32, token_id: AssetId) {
+        assert_one_yocto();
+        self.assert_pool_owner(pool_id);
+        self.pools[pool_id as usize].remove_extra_collateral(&token_id);
+        let mut token_to_list_collateral_pools = self
+            .token_to_list_collateral_pools
+            .get(&token_id)
+            .unwrap_or(vec![]);
+        token_to_list_collateral_pools.retain(|id| *id != pool_id);
+        self.token_to_list_collateral_pools
+            .insert(&token_id, &token_to_list_collateral_pools);
+    }
+}
@@ -11,6 +11,35 @@ pub enum TokenReceiverMessage {
     Deposit {
         pool_id: u32
     },
+    /// Offer market actions, tagged by name, e.g. `{"RepayLoan": {"loan_id": 1}}`.
+    LoanMarket(LoanMessage),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum LoanMessage {
+    /// Funds a loan offer with the transferred lend tokens.
+    PostLoanOffer {
+        collateral_token_id: AccountId,
+        interest_rate: u64,
+        min_cr: u64,
+        duration_sec: u64,
+    },
+    /// Takes an offer with the transferred collateral.
+    TakeLoanOffer {
+        offer_id: u64,
+        borrow_amount: U128,
+    },
+    RepayLoan {
+        loan_id: u64,
+    },
+    AddLoanCollateral {
+        loan_id: u64,
+    },
+    /// Pays the whole debt of a liquidatable loan for its collateral.
+    LiquidateLoan {
+        loan_id: u64,
+    },
 }
 
 #[near_bindgen]
@@ -39,9 +68,24 @@ impl FungibleTokenReceiver for Contract {
                 TokenReceiverMessage::Deposit {
                     pool_id
                 } => {
+                    self.abort_if_pool_id_valid(pool_id as usize);
+                    //refunds of failed withdrawals go through internal_deposit without this check
+                    self.pools[pool_id as usize].assert_can_deposit(&sender_id, &token_in, amount.0);
                     self.internal_deposit(pool_id, &sender_id, &token_in, amount.0);
+                    //lend tokens of a borrower repay the loan first
+                    let pool = &self.pools[pool_id as usize];
+                    if token_in == pool.lend_token_id {
+                        let pay_amount =
+                            std::cmp::min(amount.0, pool.get_borrow_and_interest(&sender_id));
+                        if pay_amount > 0 {
+                            self.internal_pay_loan(pool_id, &sender_id, &token_in, U128(pay_amount));
+                        }
+                    }
                     PromiseOrValue::Value(U128(0))
                 }
+                TokenReceiverMessage::LoanMarket(message) => {
+                    self.internal_on_loan_message(message, &sender_id, &token_in, amount.0)
+                }
             }
         }
     }
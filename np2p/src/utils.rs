@@ -4,10 +4,12 @@ use near_sdk::{ext_contract, AccountId, Balance, Gas, PromiseResult};
 /// Amount of gas for fungible token transfers, increased to 20T to support AS token contracts.
 pub const GAS_FOR_FT_TRANSFER: Gas = Gas(20_000_000_000_000);
 pub const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas(20_000_000_000_000);
+pub const GAS_FOR_MFT_TRANSFER_CALL: Gas = Gas(25_000_000_000_000 + 20_000_000_000_000);
 
 #[ext_contract(ext_self)]
 pub trait NaiVault {
     fn callback_post_withdraw(&mut self, pool_id: u32, token_id: AccountId, receiver_id: AccountId, amount: U128);
+    fn callback_market_transfer(&mut self, token_id: AccountId, receiver_id: AccountId, amount: U128);
 }
 
 #[ext_contract(ext_ft_core)]
@@ -111,10 +113,62 @@ pub fn compute_cr(
     cr.as_u64()
 }
 
-pub fn get_last_interest_recal_time_sec() -> u64 {
-    ((env::block_timestamp_ms() / 1000) / INTEREST_RECAL_PERIOD_SEC) * INTEREST_RECAL_PERIOD_SEC
+//amount grown from the index at its last update to the current index
+pub fn get_price_after_bonus(price: &Price, liquidation_bonus: u64) -> Price {
+    let mut price_after_bonus = price.clone();
+    price_after_bonus.multiplier = U128(
+        price.multiplier.0 * (LIQUIDATION_BONUS_DIVISOR - (liquidation_bonus as u128))
+            / LIQUIDATION_BONUS_DIVISOR,
+    );
+    price_after_bonus
 }
 
-pub fn get_next_interest_recal_time_sec() -> u64 {
-    get_last_interest_recal_time_sec() + INTEREST_RECAL_PERIOD_SEC
+/// Collateral seized for repaying `repay_amount` at the liquidation bonus discount,
+/// and its split between the liquidator and the foundation.
+pub fn compute_liquidation_amounts(
+    repay_amount: Balance,
+    lend_token_info: &TokenInfo,
+    lend_token_price: &Price,
+    collateral_token_info: &TokenInfo,
+    collateral_token_price: &Price,
+    liquidation_bonus: u64,
+    liquidation_marginal: u64,
+) -> (Balance, Balance, Balance) {
+    let liquidated_borrow_value = compute_token_value(repay_amount, lend_token_price);
+    let collateral_token_price_after_bonus =
+        get_price_after_bonus(collateral_token_price, liquidation_bonus);
+    let to_liquidate_collateral_amount =
+        U256::from(10u128.pow(collateral_token_info.decimals as u32))
+            * liquidated_borrow_value
+            * U256::from(10u128.pow(collateral_token_price_after_bonus.decimals as u32))
+            / (U256::from(collateral_token_price_after_bonus.multiplier.0)
+                * U256::from(10u128.pow(lend_token_info.decimals as u32)));
+    let to_liquidate_collateral_amount = to_liquidate_collateral_amount.as_u128();
+    let to_liquidate_collateral_amount_to_cover_liquidator =
+        U256::from(10u128.pow(collateral_token_info.decimals as u32))
+            * liquidated_borrow_value
+            * U256::from(10u128.pow(collateral_token_price.decimals as u32))
+            / (U256::from(collateral_token_price.multiplier.0)
+                * U256::from(10u128.pow(lend_token_info.decimals as u32)));
+    let to_liquidate_collateral_amount_to_cover_liquidator =
+        to_liquidate_collateral_amount_to_cover_liquidator.as_u128();
+
+    let collateral_to_foundation = (to_liquidate_collateral_amount
+        - to_liquidate_collateral_amount_to_cover_liquidator)
+        * (liquidation_marginal as u128)
+        / LIQUIDATION_MARGINAL_DIVISOR;
+    let collateral_to_liquidator = to_liquidate_collateral_amount - collateral_to_foundation;
+    (
+        to_liquidate_collateral_amount,
+        collateral_to_liquidator,
+        collateral_to_foundation,
+    )
+}
+
+pub fn apply_index_growth(amount: Balance, current_index: &Balance, last_index: &Balance) -> Balance {
+    if amount == 0 || last_index.clone() == 0 {
+        return amount;
+    }
+    (U256::from(amount) * U256::from(current_index.clone()) / U256::from(last_index.clone()))
+        .as_u128()
 }
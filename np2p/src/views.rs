@@ -1,7 +1,7 @@
 use crate::*;
 use near_sdk::{near_bindgen, AccountId};
 use std::collections::HashMap;
-use utils::{compute_cr, compute_token_value, compute_token_value_usd};
+use utils::{compute_cr, compute_liquidation_amounts, compute_token_value, compute_token_value_usd};
 uint::construct_uint!(
     pub struct U256(4);
 );
@@ -13,6 +13,7 @@ pub struct TokenMetaInfo {
     collateral_token_price: Price,
     lend_token_info: TokenInfo,
     collateral_token_info: TokenInfo,
+    extra_collateral_prices: Vec<(TokenInfo, Price)>,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
@@ -34,11 +35,30 @@ pub struct PoolInfo {
     pub total_collateral_deposit_value_usd: U128,
     pub total_borrow: U128,
     pub total_borrow_value_usd: U128,
-    pub fixed_interest_rate: u64,
-    pub acc_interest_per_share: U128,
-    pub last_acc_interest_update_timestamp_sec: u64,
+    pub interest_rate_model: InterestRateModel,
+    pub borrow_apr: u64,
+    pub supply_apr: u64,
+    pub borrow_index: U128,
+    pub supply_index: U128,
     pub liquidation_bonus: u64,
+    pub extra_collaterals: Vec<CollateralConfig>,
     pub token_meta_info: TokenMetaInfo,
+    pub loan_terms: Vec<LoanTerm>,
+    pub maturity_grace_period_sec: u64,
+    pub total_term_borrow: U128,
+    pub status: PoolStatus,
+    pub pending_config: Option<PendingPoolConfig>,
+    pub restrict_lenders: bool,
+    pub restrict_borrowers: bool,
+    pub withdrawal_queue: Vec<QueuedWithdrawal>,
+    pub total_queued_withdrawal: U128,
+    pub total_claimable_withdrawal: U128,
+    pub reserve_factor: u64,
+    pub reserve: U128,
+    pub total_bad_debt: U128,
+    pub owner_commission: u64,
+    pub owner_revenue: U128, //claimable by the owner
+    pub total_owner_revenue: U128,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Clone, Serialize, Deserialize)]
@@ -55,25 +75,60 @@ pub struct AccountInfo {
     min_cr: u64,
     current_cr: u64,
 
-    //lending interest when account is a lender
-    lending_interest_profit_debt: U128, //interest debt when in a lending position, this is similar to rewardDebt in sushi farming
-    unpaid_lending_interest_profit: U128,
+    //lending interest when account is a lender, included in the lend token deposit
     total_lending_interest_profit: U128,
-    last_lending_interest_reward_update_timestamp_sec: u64,
+    total_lending_loss: U128, //bad debt socialized to the lend token deposit
 
     //borrowing interest when accc is a borrower
     unpaid_borrowing_interest: U128, //interest unpaid in a borrowing positions
     total_borrowing_interest: U128,  //interest unpaid in a borrowing positions
-    last_borrowing_interest_update_timestamp_sec: u64,
+    last_index_update_timestamp_sec: u64,
     max_borrowable: U128,
 
+    //fixed-term loan, maturity is 0 for an open-ended loan
+    maturity_timestamp_sec: u64,
+    term_interest_rate: u64,
+
+    //credit delegation
+    delegated_out: U128,
+    delegated_in: U128,
+    credit_delegator_id: Option<AccountId>,
+    credit_expiry_timestamp_sec: u64,
+
+    //withdrawal queue
+    queued_withdrawal: U128,
+    claimable_withdrawal: U128,
+
     //token info
     token_meta_info: TokenMetaInfo,
     liquidation_price: Price,
 
     unrecorded_interest: U128,
-    acc_interest_per_share: U128,
-    total_interest_reward: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LiquidatableAccount {
+    pub pool_id: u32,
+    pub account_id: AccountId,
+    pub current_cr: u64,
+    pub min_cr: u64,
+    pub is_expired: bool,
+    pub borrow_and_interest: U128,
+    pub collateral_token_id: AssetId, //collateral to seize for the largest repayment
+    pub max_repayable_borrow: U128, //largest liquidated_borrow_amount accepted by liquidate
+    pub collateral_seized: U128,
+    pub collateral_to_liquidator: U128,
+    pub collateral_to_foundation: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenLiquidationSummary {
+    pub token_id: AssetId,
+    pub liquidatable_accounts: u32,
+    pub total_max_repayable_borrow: U128,
+    pub collateral_seized: HashMap<AssetId, U128>,
 }
 
 #[near_bindgen]
@@ -119,6 +174,7 @@ impl Contract {
             lend_token_price: self.price_data.price(&pool.lend_token_id.clone()),
             collateral_token_info: self.get_token_info(pool.collateral_token_id.clone()),
             collateral_token_price: self.price_data.price(&pool.collateral_token_id.clone()),
+            extra_collateral_prices: self.get_extra_collateral_prices(pool_id as u32),
         }
     }
 
@@ -137,7 +193,7 @@ impl Contract {
         let pool = self.pools.get(pool_id).expect("pool_id out of bound");
         let token_meta_info = self.get_token_meta_info(pool_id);
         let account_deposit = pool.get_account_deposit(&account_id);
-        let deposits = pool.get_deposits(&account_id);
+        let deposits = pool.get_deposits(&account_id, self.foundation_commission);
         let mut deposits_value_usd = HashMap::<AssetId, U128>::new();
         for (token_id, deposited) in deposits.iter() {
             deposits_value_usd.insert(
@@ -171,23 +227,27 @@ impl Contract {
                 Some(borrow),
                 Some(pay_amount),
             ),
-            //lending interest when account is a lender
-            lending_interest_profit_debt: U128(account_deposit.lending_interest_profit_debt), //interest debt when in a lending position, this is similar to rewardDebt in sushi farming
-            unpaid_lending_interest_profit: U128(
-                pool.get_pending_unpaid_lending_interest_profit(&account_id, self.foundation_commission),
-            ),
+            //lending interest when account is a lender, included in the lend token deposit
             total_lending_interest_profit: U128(
-                pool.get_pending_total_lending_interest_profit(&account_id),
+                pool.get_pending_total_lending_interest_profit(&account_id, self.foundation_commission),
             ),
-            last_lending_interest_reward_update_timestamp_sec: account_deposit
-                .last_lending_interest_reward_update_timestamp_sec,
+            total_lending_loss: U128(account_deposit.get_pending_total_lending_loss(
+                &pool.compute_current_indexes(self.foundation_commission).1,
+            )),
             //borrowing interest when accc is a borrower
             unpaid_borrowing_interest: U128(
                 pool.get_pending_unpaid_borrowing_interest(&account_id),
             ),
             total_borrowing_interest: U128(pool.get_pending_total_borrowing_interest(&account_id)),
-            last_borrowing_interest_update_timestamp_sec: account_deposit
-                .last_borrowing_interest_update_timestamp_sec,
+            last_index_update_timestamp_sec: account_deposit.last_index_update_timestamp_sec,
+            maturity_timestamp_sec: account_deposit.maturity_timestamp_sec,
+            term_interest_rate: account_deposit.term_interest_rate,
+            delegated_out: U128(account_deposit.delegated_out),
+            delegated_in: U128(account_deposit.delegated_in),
+            credit_delegator_id: account_deposit.credit_delegator_id.clone(),
+            credit_expiry_timestamp_sec: account_deposit.credit_expiry_timestamp_sec,
+            queued_withdrawal: U128(account_deposit.queued_withdrawal),
+            claimable_withdrawal: U128(account_deposit.claimable_withdrawal),
             max_borrowable: pool
                 .compute_max_borrowable_for_account(
                     &account_id,
@@ -195,6 +255,7 @@ impl Contract {
                     &token_meta_info.lend_token_price,
                     &token_meta_info.collateral_token_info,
                     &token_meta_info.collateral_token_price,
+                    &token_meta_info.extra_collateral_prices,
                     Some(collateral_amount.0),
                 )
                 .into(),
@@ -207,8 +268,6 @@ impl Contract {
                 Some(pay_amount),
             ),
             unrecorded_interest: U128(pool.compute_unrecorded_interest(&account_id)),
-            acc_interest_per_share: pool.get_current_acc_interest_per_share().into(),
-            total_interest_reward: pool.get_total_interest_reward(&account_id).into(),
         }
     }
 
@@ -225,6 +284,7 @@ impl Contract {
             &token_meta_info.lend_token_price,
             &token_meta_info.collateral_token_info,
             &token_meta_info.collateral_token_price,
+            &token_meta_info.extra_collateral_prices,
             Some(0),
         )
         .into()
@@ -250,25 +310,25 @@ impl Contract {
     pub fn get_pool(&self, pool_id: usize) -> PoolInfo {
         let p = self.pools.get(pool_id).expect("pool_id out of bound");
         let token_meta_info = self.get_token_meta_info(pool_id.clone());
-        let mut current_utilization = 0u64;
-        if p.total_borrow > 0 && p.total_lend_asset_deposit > 0 {
-            current_utilization =
-                (p.total_borrow * UTILIZATION_DIVISOR as u128 / p.total_lend_asset_deposit) as u64;
-        }
+        let current_utilization = p.get_utilization();
+        let (borrow_index, supply_index, _, _) =
+            p.compute_current_indexes(self.foundation_commission);
+
+        let total_borrow = p.get_total_borrow();
 
         let current_pool_cr = compute_cr(
             p.total_collateral_deposit,
             token_meta_info.collateral_token_info.decimals,
             &token_meta_info.collateral_token_price,
-            p.total_borrow,
+            total_borrow,
             &token_meta_info.lend_token_price,
             token_meta_info.lend_token_info.decimals,
         );
 
         let mut pool_max_borrowable = 0u128;
         let max_for_pool_borrow = p.total_lend_asset_deposit.clone() * (p.max_utilization as u128) / UTILIZATION_DIVISOR;
-        if max_for_pool_borrow > p.total_borrow {
-            pool_max_borrowable = max_for_pool_borrow - p.total_borrow.clone();
+        if max_for_pool_borrow > total_borrow {
+            pool_max_borrowable = max_for_pool_borrow - total_borrow;
         }
 
         PoolInfo {
@@ -290,18 +350,52 @@ impl Contract {
                 token_meta_info.collateral_token_info.decimals,
                 &token_meta_info.collateral_token_price,
             ),
-            total_borrow: U128(p.total_borrow),
+            total_borrow: U128(total_borrow),
             total_borrow_value_usd: compute_token_value_usd(
-                p.total_borrow.clone(),
+                total_borrow,
                 token_meta_info.lend_token_info.decimals,
                 &token_meta_info.lend_token_price,
             ),
-            fixed_interest_rate: p.fixed_interest_rate,
-            acc_interest_per_share: U128(p.acc_interest_per_share),
-            last_acc_interest_update_timestamp_sec: p.last_acc_interest_update_timestamp_sec,
+            interest_rate_model: p.interest_rate_model.clone(),
+            borrow_apr: p.get_current_borrow_rate(),
+            supply_apr: p.get_current_supply_rate(self.foundation_commission),
+            borrow_index: U128(borrow_index),
+            supply_index: U128(supply_index),
             liquidation_bonus: p.liquidation_bonus,
+            extra_collaterals: p.extra_collaterals.clone(),
             token_meta_info: token_meta_info,
+            loan_terms: p.loan_terms.clone(),
+            maturity_grace_period_sec: p.maturity_grace_period_sec,
+            total_term_borrow: U128(p.total_term_borrow),
+            status: p.status.clone(),
+            pending_config: p.pending_config.clone(),
+            restrict_lenders: p.restrict_lenders,
+            restrict_borrowers: p.restrict_borrowers,
+            withdrawal_queue: p.withdrawal_queue.clone(),
+            total_queued_withdrawal: U128(p.total_queued_withdrawal),
+            total_claimable_withdrawal: U128(p.total_claimable_withdrawal),
+            reserve_factor: p.reserve_factor,
+            reserve: U128(p.reserve),
+            total_bad_debt: U128(p.total_bad_debt),
+            owner_commission: p.owner_commission,
+            owner_revenue: U128(p.get_pending_owner_revenue(self.foundation_commission)),
+            total_owner_revenue: U128(p.total_owner_revenue),
+        }
+    }
+
+    /// Revenue claimable by the owner over all the pools they created, per lend token.
+    pub fn get_pool_owner_revenues(&self, owner_id: AccountId) -> HashMap<AssetId, U128> {
+        let mut ret = HashMap::<AssetId, U128>::new();
+        for pool_id in self.created_pools.get(&owner_id).unwrap_or(vec![]).iter() {
+            let pool = &self.pools[pool_id.clone() as usize];
+            let revenue = pool.get_pending_owner_revenue(self.foundation_commission);
+            ret.entry(pool.lend_token_id.clone()).or_insert(U128(0)).0 += revenue;
         }
+        ret
+    }
+
+    pub fn get_pool_creation_fees(&self) -> Commission {
+        self.pool_creation_fees.clone()
     }
 
     pub fn get_pools(&self, from_index: Option<usize>, limit: Option<usize>) -> Vec<PoolInfo> {
@@ -316,6 +410,40 @@ impl Contract {
             .collect::<Vec<_>>()
     }
 
+    pub fn get_pool_allowed_lenders(
+        &self,
+        pool_id: usize,
+        from_index: Option<usize>,
+        limit: Option<usize>,
+    ) -> Vec<AccountId> {
+        let pool = self.pools.get(pool_id).expect("pool_id out of bound");
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        let start_index = from_index.unwrap_or(0);
+        pool.allowed_lenders
+            .iter()
+            .skip(start_index)
+            .take(limit)
+            .collect::<Vec<_>>()
+    }
+
+    pub fn get_pool_allowed_borrowers(
+        &self,
+        pool_id: usize,
+        from_index: Option<usize>,
+        limit: Option<usize>,
+    ) -> Vec<AccountId> {
+        let pool = self.pools.get(pool_id).expect("pool_id out of bound");
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        let start_index = from_index.unwrap_or(0);
+        pool.allowed_borrowers
+            .iter()
+            .skip(start_index)
+            .take(limit)
+            .collect::<Vec<_>>()
+    }
+
     pub fn get_storage_account(&self, account_id: AccountId) -> UserStorageUsage {
         let storage_account = self.storage_accounts.get(&account_id).unwrap_or_default();
         storage_account
@@ -337,7 +465,7 @@ impl Contract {
         account_id: AccountId,
     ) -> HashMap<AssetId, U128> {
         let pool = self.pools.get(pool_id).expect("pool_id out of bound");
-        pool.get_deposits(&account_id)
+        pool.get_deposits(&account_id, self.foundation_commission)
     }
 
     pub fn get_deposits(&self, account_id: AccountId) -> (Vec<usize>, Vec<HashMap<AssetId, U128>>) {
@@ -393,6 +521,7 @@ impl Contract {
             &lend_token_price,
             &collateral_token_info,
             &collateral_token_price,
+            &self.get_extra_collateral_prices(pool_id),
             Some(collateral_amount.unwrap_or(U128(0)).0),
             Some(borrow.unwrap_or(U128(0)).0),
             Some(pay_amount.unwrap_or(U128(0)).0),
@@ -416,8 +545,7 @@ impl Contract {
 
         let total_collateral_amount =
             collateral_amount.0 + pool.get_token_deposit(&account_id, &pool.collateral_token_id);
-        let mut total_borrow =
-            borrow_amount.0 + pool.get_borrow_amount(&account_id) + pool.compute_unrecorded_interest(&account_id);
+        let mut total_borrow = borrow_amount.0 + pool.get_borrow_and_interest(&account_id);
 
         if total_borrow == 0 {
             return Price::default();
@@ -456,6 +584,91 @@ impl Contract {
         }
     }
 
+    /// Accounts of the pool below min_cr or past their term, with what liquidating them at
+    /// the max repayable amount would seize. Pages over the pool accounts.
+    pub fn get_liquidatable_accounts(
+        &self,
+        pool_id: u32,
+        from_index: Option<usize>,
+        limit: Option<usize>,
+    ) -> Vec<LiquidatableAccount> {
+        let limit = limit.unwrap_or(usize::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        let pool = self
+            .pools
+            .get(pool_id as usize)
+            .expect("pool_id out of range");
+        let token_meta_info = self.get_token_meta_info(pool_id as usize);
+        let keys = pool.account_deposits.keys_as_vector();
+        let start_index = from_index.unwrap_or(0) as u64;
+        let end_index = std::cmp::min(keys.len(), start_index.saturating_add(limit as u64));
+        (start_index..end_index)
+            .filter_map(|index| {
+                let account_id = keys.get(index).unwrap();
+                self.internal_get_liquidatable_account(pool_id, &account_id, &token_meta_info)
+            })
+            .collect()
+    }
+
+    /// Liquidation opportunities aggregated per lend token, paged over the accounts of all pools
+    /// taken in pool order, `from_index` and `limit` count accounts.
+    pub fn get_liquidation_summary(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<TokenLiquidationSummary> {
+        let mut limit = limit.unwrap_or(u64::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        let mut skip = from_index.unwrap_or(0);
+        let mut summaries: Vec<TokenLiquidationSummary> = vec![];
+        for pool in self.pools.iter() {
+            if limit == 0 {
+                break;
+            }
+            let account_count = pool.account_deposits.len();
+            if skip >= account_count {
+                skip -= account_count;
+                continue;
+            }
+            let take = std::cmp::min(limit, account_count - skip);
+            let accounts = self.get_liquidatable_accounts(
+                pool.pool_id,
+                Some(skip as usize),
+                Some(take as usize),
+            );
+            skip = 0;
+            limit -= take;
+            if accounts.is_empty() {
+                continue;
+            }
+            let position = summaries
+                .iter()
+                .position(|summary| summary.token_id == pool.lend_token_id);
+            let summary = match position {
+                Some(position) => &mut summaries[position],
+                None => {
+                    summaries.push(TokenLiquidationSummary {
+                        token_id: pool.lend_token_id.clone(),
+                        liquidatable_accounts: 0,
+                        total_max_repayable_borrow: U128(0),
+                        collateral_seized: HashMap::new(),
+                    });
+                    summaries.last_mut().unwrap()
+                }
+            };
+            for account in accounts.iter() {
+                summary.liquidatable_accounts += 1;
+                summary.total_max_repayable_borrow.0 += account.max_repayable_borrow.0;
+                summary
+                    .collateral_seized
+                    .entry(account.collateral_token_id.clone())
+                    .or_insert(U128(0))
+                    .0 += account.collateral_seized.0;
+            }
+        }
+        summaries
+    }
+
     pub fn get_account_list_count(&self) -> usize {
         self.account_list.len()
     }
@@ -477,6 +690,76 @@ impl Contract {
 }
 
 impl Contract {
+    fn internal_get_liquidatable_account(
+        &self,
+        pool_id: u32,
+        account_id: &AccountId,
+        token_meta_info: &TokenMetaInfo,
+    ) -> Option<LiquidatableAccount> {
+        let pool = &self.pools[pool_id as usize];
+        let (collateral_token_id, max_repayable_borrow) = std::iter::once(&pool.collateral_token_id)
+            .chain(pool.extra_collaterals.iter().map(|c| &c.token_id))
+            .map(|token_id| {
+                let max_repayable_borrow = pool.compute_max_liquidatable_borrow(
+                    account_id,
+                    token_id,
+                    &token_meta_info.lend_token_info,
+                    &token_meta_info.lend_token_price,
+                    &token_meta_info.collateral_token_info,
+                    &token_meta_info.collateral_token_price,
+                    &token_meta_info.extra_collateral_prices,
+                );
+                (token_id.clone(), max_repayable_borrow)
+            })
+            .max_by_key(|(_, max_repayable_borrow)| *max_repayable_borrow)
+            .unwrap();
+        if max_repayable_borrow == 0 {
+            return None;
+        }
+        let (seized_token_info, seized_token_price, _, liquidation_bonus) = pool
+            .get_collateral_params(
+                &collateral_token_id,
+                &token_meta_info.collateral_token_info,
+                &token_meta_info.collateral_token_price,
+                &token_meta_info.extra_collateral_prices,
+            );
+        let account_deposit = pool.get_account_deposit(account_id);
+        let current_cr = pool.compute_current_cr(
+            account_id.clone(),
+            &token_meta_info.lend_token_info,
+            &token_meta_info.lend_token_price,
+            &token_meta_info.collateral_token_info,
+            &token_meta_info.collateral_token_price,
+            &token_meta_info.extra_collateral_prices,
+            None,
+            None,
+            None,
+        );
+        let (collateral_seized, collateral_to_liquidator, collateral_to_foundation) =
+            compute_liquidation_amounts(
+                max_repayable_borrow,
+                &token_meta_info.lend_token_info,
+                &token_meta_info.lend_token_price,
+                &seized_token_info,
+                &seized_token_price,
+                liquidation_bonus,
+                self.liquidation_marginal,
+            );
+        Some(LiquidatableAccount {
+            pool_id,
+            account_id: account_id.clone(),
+            current_cr,
+            min_cr: pool.min_cr,
+            is_expired: account_deposit.is_expired(pool.maturity_grace_period_sec),
+            borrow_and_interest: U128(pool.get_borrow_and_interest(account_id)),
+            collateral_token_id: collateral_token_id,
+            max_repayable_borrow: U128(max_repayable_borrow),
+            collateral_seized: U128(collateral_seized),
+            collateral_to_liquidator: U128(collateral_to_liquidator),
+            collateral_to_foundation: U128(collateral_to_foundation),
+        })
+    }
+
     pub fn storage_cost(&self, prev_storage: StorageUsage) -> Balance {
         let storage_cost = env::storage_usage()
             .checked_sub(prev_storage)
@@ -0,0 +1,241 @@
+use crate::*;
+use near_sdk::collections::LookupMap;
+use near_sdk::PromiseResult;
+use utils::{ext_self, ext_ve_nft, GAS_FOR_GET_VOTING_POWER, GAS_FOR_RESOLVE_TRANSFER};
+
+pub type VeNftTokenId = String;
+
+/// Guaranteed round opening a verified sale, until `round_end_sec` only registered accounts buy,
+/// each up to its share of `payment_allocation` (in the payment token) by voting power in ve-nft.
+/// The snapshot is kept per ve-nft lock, so a lock moved to another account counts once, for the
+/// account that registered it last. Registration closes at the sale start, which freezes the snapshot.
+/// The sale is first come first served up to the hard cap after the round.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AllocationRound {
+    pub round_end_sec: u64,
+    pub payment_allocation: Balance,
+    pub total_voting_power: Balance,
+    pub voting_powers: LookupMap<AccountId, Balance>,
+    pub locks: LookupMap<VeNftTokenId, (AccountId, Balance)>, //registered lock to its account and voting power
+}
+
+//the part of a ve-nft lock read here
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VeLockInfo {
+    pub voting_power: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AllocationInfo {
+    pub round_end_sec: u64,
+    pub payment_allocation: U128,
+    pub total_voting_power: U128,
+    pub voting_power: U128,
+    pub allocation: U128,
+}
+
+impl AllocationRound {
+    pub fn get_allocation(&self, account_id: &AccountId) -> Balance {
+        if self.total_voting_power == 0 {
+            return 0;
+        }
+        let voting_power = self.voting_powers.get(account_id).unwrap_or(0);
+        (U256::from(self.payment_allocation) * U256::from(voting_power) / U256::from(self.total_voting_power))
+            .as_u128()
+    }
+
+    fn add_voting_power(&mut self, account_id: &AccountId, voting_power: Balance) {
+        let current = self.voting_powers.get(account_id).unwrap_or(0);
+        self.voting_powers.insert(account_id, &(current + voting_power));
+        self.total_voting_power += voting_power;
+    }
+
+    fn remove_voting_power(&mut self, account_id: &AccountId, voting_power: Balance) {
+        let current = self.voting_powers.get(account_id).unwrap_or(0) - voting_power;
+        if current == 0 {
+            self.voting_powers.remove(account_id);
+        } else {
+            self.voting_powers.insert(account_id, &current);
+        }
+        self.total_voting_power -= voting_power;
+    }
+
+    //counts the lock for `account_id` only, returns the previous registration of the lock
+    pub fn register_lock(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &VeNftTokenId,
+        voting_power: Balance,
+    ) -> Option<(AccountId, Balance)> {
+        let previous = self.locks.insert(token_id, &(account_id.clone(), voting_power));
+        if let Some((previous_account_id, previous_voting_power)) = &previous {
+            self.remove_voting_power(previous_account_id, *previous_voting_power);
+        }
+        self.add_voting_power(account_id, voting_power);
+        previous
+    }
+
+    //undoes register_lock
+    pub fn unregister_lock(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &VeNftTokenId,
+        voting_power: Balance,
+        previous: Option<(AccountId, Balance)>,
+    ) {
+        self.remove_voting_power(account_id, voting_power);
+        match previous {
+            Some((previous_account_id, previous_voting_power)) => {
+                self.locks.insert(token_id, &(previous_account_id.clone(), previous_voting_power));
+                self.add_voting_power(&previous_account_id, previous_voting_power);
+            }
+            None => {
+                self.locks.remove(token_id);
+            }
+        }
+    }
+}
+
+impl Pool {
+    //payment `account_id` can still make, not capped outside of the guaranteed round
+    pub fn get_allocation_left(&self, account_id: &AccountId) -> Balance {
+        match &self.allocation_round {
+            Some(round) if env::block_timestamp_ms() / 1000 < round.round_end_sec => round
+                .get_allocation(account_id)
+                .saturating_sub(self.buyers.get(account_id).unwrap_or(0)),
+            _ => Balance::MAX,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Opens a verified sale with a guaranteed round, before the sale starts.
+    pub fn create_allocation_round(&mut self, pool_id: u32, round_end_sec: u64, payment_allocation: U128) {
+        self.assert_governance();
+        require!(self.ve_nft_id.is_some(), "ve_nft_id not set");
+        let pool = self.get_pool_mut(pool_id, true);
+        require!(pool.allocation_round.is_none(), "allocation round exists");
+        require!(
+            env::block_timestamp_ms() / 1000 < pool.sale_start_sec,
+            "sale already started"
+        );
+        require!(
+            pool.sale_start_sec < round_end_sec && round_end_sec <= pool.sale_end_sec,
+            "round must end during the sale"
+        );
+        require!(payment_allocation.0 <= pool.hard_cap, "payment_allocation above hard_cap");
+        pool.allocation_round = Some(AllocationRound {
+            round_end_sec,
+            payment_allocation: payment_allocation.0,
+            total_voting_power: 0,
+            voting_powers: LookupMap::new(format!("voting_powers{}", pool_id).as_bytes()),
+            locks: LookupMap::new(format!("allocation_locks{}", pool_id).as_bytes()),
+        });
+    }
+
+    /// Snapshots the voting power of the caller ve-nft locks for the guaranteed round, until the sale starts.
+    /// Registering again updates the snapshot. The attached deposit pays the storage, the rest is refunded.
+    #[payable]
+    pub fn register_for_allocation(&mut self, pool_id: u32) -> Promise {
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        let pool = self.get_pool(pool_id, true);
+        require!(pool.allocation_round.is_some(), "no allocation round");
+        require!(
+            env::block_timestamp_ms() / 1000 < pool.sale_start_sec,
+            "sale already started"
+        );
+        ext_ve_nft::get_token_metadata_for_account(
+            account_id.clone(),
+            self.ve_nft_id.clone().unwrap(),
+            0,
+            GAS_FOR_GET_VOTING_POWER,
+        )
+        .then(ext_self::callback_register_for_allocation(
+            pool_id,
+            account_id,
+            U128(env::attached_deposit()),
+            env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+    }
+
+    #[private]
+    pub fn callback_register_for_allocation(&mut self, pool_id: u32, account_id: AccountId, deposit: U128) -> U128 {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            "CALLBACK_REGISTER_FOR_ALLOCATION_INVALID"
+        );
+        let locks = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<(
+                Vec<VeNftTokenId>,
+                Vec<VeLockInfo>,
+                U128,
+                U128,
+                U128,
+            )>(&value)
+            .map(|(token_ids, lock_infos, _, _, _)| {
+                token_ids
+                    .into_iter()
+                    .zip(lock_infos.into_iter().map(|lock_info| lock_info.voting_power.0))
+                    .filter(|(_, voting_power)| *voting_power > 0)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default(),
+            PromiseResult::Failed => vec![],
+        };
+        let pool = self.get_pool(pool_id, true);
+        //the sale can start while ve-nft answers
+        if locks.is_empty() || env::block_timestamp_ms() / 1000 >= pool.sale_start_sec {
+            if deposit.0 > 0 {
+                Promise::new(account_id).transfer(deposit.0);
+            }
+            return U128(0);
+        }
+        let prev_storage = env::storage_usage();
+        let round = self.get_pool_mut(pool_id, true).allocation_round.as_mut().unwrap();
+        let previous = locks
+            .iter()
+            .map(|(token_id, voting_power)| round.register_lock(&account_id, token_id, *voting_power))
+            .collect::<Vec<_>>();
+        let voting_power = round.voting_powers.get(&account_id).unwrap_or(0);
+        let storage_cost = self.storage_cost(prev_storage);
+        //a panic here would keep the deposit, so the registration is undone instead
+        if storage_cost > deposit.0 {
+            log!("ERR_STORAGE_DEPOSIT need {}, attatched {}", storage_cost, deposit.0);
+            let round = self.get_pool_mut(pool_id, true).allocation_round.as_mut().unwrap();
+            for ((token_id, voting_power), previous) in locks.iter().zip(previous).rev() {
+                round.unregister_lock(&account_id, token_id, *voting_power, previous);
+            }
+            if deposit.0 > 0 {
+                Promise::new(account_id).transfer(deposit.0);
+            }
+            return U128(0);
+        }
+        if deposit.0 > storage_cost {
+            Promise::new(account_id).transfer(deposit.0 - storage_cost);
+        }
+        U128(voting_power)
+    }
+
+    pub fn get_allocation_info(&self, pool_id: u32, account_id: AccountId) -> Option<AllocationInfo> {
+        self.get_pool(pool_id, true)
+            .allocation_round
+            .as_ref()
+            .map(|round| AllocationInfo {
+                round_end_sec: round.round_end_sec,
+                payment_allocation: U128(round.payment_allocation),
+                total_voting_power: U128(round.total_voting_power),
+                voting_power: U128(round.voting_powers.get(&account_id).unwrap_or(0)),
+                allocation: U128(round.get_allocation(&account_id)),
+            })
+    }
+}
@@ -1,21 +1,33 @@
-// mod governance;
+mod allocation;
+mod owner;
+mod sale;
 // //mod storage;
 // mod storage_impl;
-// mod token_receiver;
-// mod utils;
+mod token_receiver;
+mod utils;
+mod verification;
+mod vesting;
 mod views;
+mod whitelist;
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap};
+use near_sdk::collections::{LookupMap, UnorderedMap};
 use near_sdk::json_types::{U128};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, near_bindgen, require, AccountId, BorshStorageKey,
-    PanicOnDefault, Balance
+    assert_one_yocto, env, log, near_bindgen, require, AccountId, BorshStorageKey, CryptoHash,
+    PanicOnDefault, Balance, Promise
 };
 
+use allocation::AllocationRound;
+use utils::{compute_token_amount, U256};
+use verification::VerificationApplication;
+use vesting::VestingSchedule;
+use whitelist::WhitelistProof;
+
 
 const FEE_DIVISOR: u64 = 10000;
+const NEAR_DECIMALS: u8 = 24;
 
 #[derive(BorshStorageKey, BorshSerialize)]
 enum StorageKey {
@@ -23,7 +35,9 @@ enum StorageKey {
     VerifiedPoolIdsByAccount,
     UnverifiedPoolIdsByAccount,
     VerifiedPoolIdsByToken,
-    UnverifiedPoolIdsByToken
+    UnverifiedPoolIdsByToken,
+    VerificationApplications,
+    PaymentTokenDecimals
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
@@ -57,17 +71,29 @@ pub struct Pool {
     owner_id: AccountId,
     token_id: AccountId,
     amount_for_sale: Balance,
-    rate: Balance,     //how many token per one NEAR 
+    rate: Balance,     //how many token per one payment token, NEAR by default
     soft_cap: Balance,
     hard_cap: Balance,
     buyers: LookupMap<AccountId, Balance>,
-    buyer_list: Vec<AccountId>,
+    buyer_count: u64,
     token_claimed: LookupMap<AccountId, Balance>,
     sale_start_sec: u64,
     sale_end_sec: u64,
     sold_amount: Balance,
     verified_track: bool,
-    is_token_deposited: bool
+    is_token_deposited: bool,
+    total_raised: Balance,  //paid by buyers, in the payment token
+    proceeds_withdrawn: bool,
+    unsold_tokens_withdrawn: bool,
+    creation_fee: Balance,  //held until the verification is decided
+    verified_pool_id: Option<u32>,  //set on an unverified pool moved to the verified track
+    vesting: Option<VestingSchedule>,
+    allocation_round: Option<AllocationRound>,  //verified sales only
+    whitelist_root: Option<CryptoHash>,
+    whitelist_allocations: LookupMap<AccountId, Balance>,  //max allocation in payment token proven on the first purchase
+    payment_token_id: Option<AccountId>,  //NEAR when None
+    payment_decimals: u8,
+    storage_reserve: Balance  //NEAR paying the storage of buyers in a payment token
 }
 
 impl Pool {
@@ -81,13 +107,25 @@ impl Pool {
             soft_cap: soft_cap,
             hard_cap: hard_cap,
             buyers: LookupMap::new(format!("buyers{}{}", pool_id, verified_track).as_bytes()),
-            buyer_list: vec![],
+            buyer_count: 0,
             token_claimed: LookupMap::new(format!("token_claimed{}{}", pool_id, verified_track).as_bytes()),
             sale_start_sec: sale_start_sec,
             sale_end_sec: sale_end_sec,
             sold_amount: 0u128,
             verified_track: verified_track,
-            is_token_deposited: false
+            is_token_deposited: false,
+            total_raised: 0,
+            proceeds_withdrawn: false,
+            unsold_tokens_withdrawn: false,
+            creation_fee: 0,
+            verified_pool_id: None,
+            vesting: None,
+            allocation_round: None,
+            whitelist_root: None,
+            whitelist_allocations: LookupMap::new(format!("whitelist_allocations{}{}", pool_id, verified_track).as_bytes()),
+            payment_token_id: None,
+            payment_decimals: NEAR_DECIMALS,
+            storage_reserve: 0
         }
     }
 }
@@ -125,7 +163,10 @@ pub struct Contract {
     unverified_pool_ids_by_account: LookupMap<AccountId, Vec<u32>>,
     verified_pool_ids_by_token: LookupMap<AccountId, Vec<u32>>,
     unverified_pool_ids_by_token: LookupMap<AccountId, Vec<u32>>,
-    new_pool_creation_fee: Balance
+    new_pool_creation_fee: Balance,
+    verification_applications: UnorderedMap<u32, VerificationApplication>,  //by unverified pool id
+    ve_nft_id: Option<AccountId>,
+    payment_token_decimals: LookupMap<AccountId, u8>  //read from ft_metadata by register_payment_token
 }
 
 #[near_bindgen]
@@ -147,19 +188,41 @@ impl Contract {
             unverified_pool_ids_by_account: LookupMap::new(StorageKey::UnverifiedPoolIdsByAccount),
             verified_pool_ids_by_token: LookupMap::new(StorageKey::VerifiedPoolIdsByToken),
             unverified_pool_ids_by_token: LookupMap::new(StorageKey::UnverifiedPoolIdsByToken),
-            new_pool_creation_fee: new_pool_creation_fee.0
+            new_pool_creation_fee: new_pool_creation_fee.0,
+            verification_applications: UnorderedMap::new(StorageKey::VerificationApplications),
+            ve_nft_id: None,
+            payment_token_decimals: LookupMap::new(StorageKey::PaymentTokenDecimals)
         };
 
         this
     }
 
     #[payable]
-    pub fn create_new_unverified_pool(&mut self, token_id: AccountId, amount_for_sale: U128, rate: U128, soft_cap: U128, sale_start_sec: u64, sale_end_sec: u64) {
+    pub fn create_new_unverified_pool(&mut self, token_id: AccountId, amount_for_sale: U128, rate: U128, soft_cap: U128, sale_start_sec: u64, sale_end_sec: u64, hard_cap: Option<U128>, vesting: Option<VestingSchedule>, payment_token_id: Option<AccountId>) {
         let prev_storage = env::storage_usage();
         let account_id = env::predecessor_account_id();
         let pool_len = self.unverified_pools.len() as u32;
-        let hard_cap = 0 as Balance;
-        let pool = Pool::new(pool_len.clone(), account_id.clone(), token_id.clone(), amount_for_sale.0, rate.0, soft_cap.0, hard_cap.clone(), sale_start_sec.clone(), sale_end_sec.clone(), false);
+        require!(rate.0 > 0, "rate > 0");
+        require!(sale_start_sec < sale_end_sec, "sale must end after it starts");
+        let payment_decimals = match payment_token_id.as_ref() {
+            Some(payment_token_id) => {
+                require!(payment_token_id != &token_id, "payment token is the sale token");
+                self.payment_token_decimals.get(payment_token_id).expect("payment token not registered")
+            }
+            None => NEAR_DECIMALS,
+        };
+        //by default the hard cap is the payment that buys all the tokens for sale
+        let hard_cap = hard_cap.map(|v| v.0).unwrap_or((U256::from(amount_for_sale.0) * U256::from(10u128.pow(payment_decimals as u32)) / U256::from(rate.0)).as_u128());
+        require!(soft_cap.0 <= hard_cap, "soft_cap > hard_cap");
+        require!(compute_token_amount(hard_cap, rate.0, payment_decimals) <= amount_for_sale.0, "hard_cap buys more than amount_for_sale");
+        let mut pool = Pool::new(pool_len.clone(), account_id.clone(), token_id.clone(), amount_for_sale.0, rate.0, soft_cap.0, hard_cap.clone(), sale_start_sec.clone(), sale_end_sec.clone(), false);
+        pool.creation_fee = self.new_pool_creation_fee;
+        pool.payment_decimals = payment_decimals;
+        pool.payment_token_id = payment_token_id;
+        if let Some(vesting) = vesting.as_ref() {
+            vesting.assert_valid();
+        }
+        pool.vesting = vesting;
 
         self.unverified_pools.push(pool);
         let mut unverified_pools_by_token = self.unverified_pool_ids_by_token.get(&token_id).unwrap_or(vec![]);
@@ -169,6 +232,24 @@ impl Contract {
         let mut unverified_pools_by_account = self.unverified_pool_ids_by_account.get(&account_id).unwrap_or(vec![]);
         unverified_pools_by_account.push(pool_len.clone());
         self.unverified_pool_ids_by_account.insert(&account_id, &unverified_pools_by_account);
+
+        //the attached deposit pays the creation fee and the storage,
+        //what is left is the storage reserve of a sale paid in a token
+        let required = self.storage_cost(prev_storage) + self.new_pool_creation_fee;
+        let refund = env::attached_deposit().checked_sub(required).expect(
+            format!(
+                "ERR_STORAGE_DEPOSIT need {}, attatched {}",
+                required,
+                env::attached_deposit()
+            )
+            .as_str(),
+        );
+        let pool = self.unverified_pools.last_mut().unwrap();
+        if pool.payment_token_id.is_some() {
+            pool.storage_reserve = refund;
+        } else if refund > 0 {
+            Promise::new(account_id).transfer(refund);
+        }
     }
 
     // fn measure_account_storage_usage(&mut self) {
@@ -234,7 +315,15 @@ impl Contract {
 }
 
 impl Contract {
-    
+    pub(crate) fn get_pool(&self, pool_id: u32, verified_track: bool) -> &Pool {
+        let pools = if verified_track { &self.verified_pools } else { &self.unverified_pools };
+        pools.get(pool_id as usize).expect("pool_id out of range")
+    }
+
+    pub(crate) fn get_pool_mut(&mut self, pool_id: u32, verified_track: bool) -> &mut Pool {
+        let pools = if verified_track { &mut self.verified_pools } else { &mut self.unverified_pools };
+        pools.get_mut(pool_id as usize).expect("pool_id out of range")
+    }
 }
 
 #[no_mangle]
@@ -273,4 +362,343 @@ pub fn upgrade() {
 }
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
-mod tests {}
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+    use vesting::VestingSchedule;
+    use near_contract_standards::fungible_token::metadata::{FungibleTokenMetadata, FT_METADATA_SPEC};
+    use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, PromiseOrValue, PromiseResult};
+
+    const ONE_NEAR: Balance = 10u128.pow(24);
+
+    fn setup_contract() -> (VMContextBuilder, Contract) {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let contract = Contract::new(accounts(0), Some(500), None);
+        (context, contract)
+    }
+
+    //sale of 1000 tokens at 100 tokens per NEAR from second 100 to 200, owned by accounts(1)
+    fn create_sale(context: &mut VMContextBuilder, contract: &mut Contract, soft_cap: Balance) {
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(2 * ONE_NEAR)
+            .block_timestamp(0)
+            .build());
+        contract.create_new_unverified_pool(accounts(3), U128(1000), U128(100), U128(soft_cap), 100, 200, None, None, None);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let unused = contract.ft_on_transfer(
+            accounts(1),
+            U128(1100),
+            "{\"deposit_pool_id\": 0, \"verified_track\": false}".to_string(),
+        );
+        assert!(matches!(unused, PromiseOrValue::Value(U128(100))));
+    }
+
+    fn buy(context: &mut VMContextBuilder, contract: &mut Contract, account_id: AccountId, amount: Balance) -> Balance {
+        testing_env!(context
+            .predecessor_account_id(account_id)
+            .attached_deposit(amount)
+            .block_timestamp(150 * 10u64.pow(9))
+            .build());
+        contract.buy(0, false, None).0
+    }
+
+    #[test]
+    fn sale_fills_to_hard_cap_and_pays_out() {
+        let (mut context, mut contract) = setup_contract();
+        create_sale(&mut context, &mut contract, 5 * ONE_NEAR);
+        assert_eq!(contract.get_pool_info(0, false).hard_cap.0, 10 * ONE_NEAR);
+
+        //the storage of a new buyer is paid from the attached deposit
+        let first = buy(&mut context, &mut contract, accounts(2), 7 * ONE_NEAR);
+        assert!(first < 7 * ONE_NEAR && first > 69 * ONE_NEAR / 10);
+        //only what is left under the hard cap is taken
+        assert_eq!(buy(&mut context, &mut contract, accounts(4), 5 * ONE_NEAR), 10 * ONE_NEAR - first);
+        let pool = contract.get_pool_info(0, false);
+        assert_eq!(pool.total_raised.0, 10 * ONE_NEAR);
+        assert_eq!(pool.buyer_count, 2);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .block_timestamp(200 * 10u64.pow(9))
+            .build());
+        contract.claim_tokens(0, false);
+        let buyer = contract.get_buyer_info(0, false, accounts(2));
+        assert_eq!(buyer.purchased_tokens, buyer.token_claimed);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        //5% of the raised NEAR goes to governance
+        assert_eq!(contract.withdraw_sale_proceeds(0, false).0, 95 * ONE_NEAR / 10);
+        assert_eq!(contract.get_pool_info(0, false).sold_amount.0, 1000);
+    }
+
+    #[test]
+    fn failed_sale_refunds_buyers() {
+        let (mut context, mut contract) = setup_contract();
+        create_sale(&mut context, &mut contract, 5 * ONE_NEAR);
+        buy(&mut context, &mut contract, accounts(2), 2 * ONE_NEAR);
+        let paid = contract.get_buyer_info(0, false, accounts(2)).purchased_payment.0;
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .block_timestamp(200 * 10u64.pow(9))
+            .build());
+        assert_eq!(contract.refund(0, false).0, paid);
+        assert_eq!(contract.get_buyer_info(0, false, accounts(2)).purchased_payment.0, 0);
+
+        //all the tokens go back to the owner
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.withdraw_unsold_tokens(0, false);
+        assert!(contract.get_pool_info(0, false).unsold_tokens_withdrawn);
+    }
+
+    #[test]
+    fn verification_moves_sale_to_verified_track() {
+        let (mut context, mut contract) = setup_contract();
+        create_sale(&mut context, &mut contract, 5 * ONE_NEAR);
+        let metadata = verification::SaleMetadata {
+            website: "https://example.com".to_string(),
+            audit_link: None,
+            vesting_terms: None,
+        };
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(ONE_NEAR).build());
+        contract.apply_for_verification(0, metadata.clone());
+
+        //a rejection refunds the creation fee and the sale can apply again with a new fee
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        assert_eq!(contract.reject_verification(0).0, ONE_NEAR);
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(2 * ONE_NEAR).build());
+        contract.apply_for_verification(0, metadata.clone());
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        assert_eq!(contract.reject_verification(0).0, ONE_NEAR);
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(2 * ONE_NEAR).build());
+        contract.apply_for_verification(0, metadata);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        assert_eq!(contract.approve_verification(0), 0);
+
+        let verified = contract.get_pool_info(0, true);
+        assert!(verified.verified_track && verified.is_token_deposited);
+        assert_eq!(contract.get_pool_info(0, false).verified_pool_id, Some(0));
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(ONE_NEAR)
+            .block_timestamp(150 * 10u64.pow(9))
+            .build());
+        contract.buy(0, true, None);
+        assert_eq!(contract.get_pool_info(0, true).buyer_count, 1);
+    }
+
+    #[test]
+    fn vested_tokens_are_claimed_over_time() {
+        let (mut context, mut contract) = setup_contract();
+        //20% at the sale end, then 80% over 100 seconds after a 50 seconds cliff, in 25 seconds slices
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(2 * ONE_NEAR)
+            .block_timestamp(0)
+            .build());
+        let vesting = VestingSchedule {
+            tge_percent: 2000,
+            cliff_sec: 50,
+            vesting_duration_sec: 100,
+            slice_period_sec: 25,
+        };
+        contract.create_new_unverified_pool(accounts(3), U128(1000), U128(100), U128(0), 100, 200, None, Some(vesting), None);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.ft_on_transfer(accounts(1), U128(1000), "{\"deposit_pool_id\": 0, \"verified_track\": false}".to_string());
+        let paid = buy(&mut context, &mut contract, accounts(2), ONE_NEAR);
+        let purchased = compute_token_amount(paid, 100, NEAR_DECIMALS);
+
+        let claim_at = |context: &mut VMContextBuilder, contract: &mut Contract, now_sec: u64| {
+            testing_env!(context
+                .predecessor_account_id(accounts(2))
+                .attached_deposit(1)
+                .block_timestamp(now_sec * 10u64.pow(9))
+                .build());
+            contract.claim_tokens(0, false);
+            contract.get_buyer_info(0, false, accounts(2))
+        };
+        let buyer = claim_at(&mut context, &mut contract, 200);
+        assert_eq!(buyer.token_claimed.0, purchased / 5);
+        assert_eq!(buyer.locked_tokens.0, purchased - purchased / 5);
+
+        //two slices of the linear part have vested 74 seconds after the cliff
+        let buyer = claim_at(&mut context, &mut contract, 324);
+        let tge = purchased / 5;
+        assert_eq!(buyer.token_claimed.0, tge + (purchased - tge) / 2);
+        assert_eq!(buyer.claimable_tokens.0, 0);
+
+        let buyer = claim_at(&mut context, &mut contract, 350);
+        assert_eq!(buyer.token_claimed.0, purchased);
+        assert_eq!(buyer.locked_tokens.0, 0);
+    }
+
+    #[test]
+    fn guaranteed_round_caps_buyers_by_voting_power() {
+        let (mut context, mut contract) = setup_contract();
+        create_sale(&mut context, &mut contract, 0);
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(ONE_NEAR).build());
+        contract.apply_for_verification(
+            0,
+            verification::SaleMetadata {
+                website: "https://example.com".to_string(),
+                audit_link: None,
+                vesting_terms: None,
+            },
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.approve_verification(0);
+        contract.set_ve_nft_id("ve-nft.near".parse().unwrap());
+        //4 of the 10 NEAR hard cap are guaranteed until second 150
+        contract.create_allocation_round(0, 150, U128(4 * ONE_NEAR));
+
+        let register = |context: &mut VMContextBuilder, contract: &mut Contract, account_id: AccountId, locks: Vec<(&str, u128)>| {
+            let token_ids = locks.iter().map(|(token_id, _)| token_id.to_string()).collect::<Vec<_>>();
+            let lock_infos = locks
+                .iter()
+                .map(|(_, voting_power)| allocation::VeLockInfo { voting_power: U128(*voting_power) })
+                .collect::<Vec<_>>();
+            let result = (token_ids, lock_infos, U128(0), U128(0), U128(0));
+            testing_env!(
+                context.predecessor_account_id(accounts(0)).build(),
+                near_sdk::VMConfig::test(),
+                near_sdk::RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![PromiseResult::Successful(near_sdk::serde_json::to_vec(&result).unwrap())],
+            );
+            contract.callback_register_for_allocation(0, account_id, U128(ONE_NEAR)).0
+        };
+        assert_eq!(register(&mut context, &mut contract, accounts(2), vec![("1", 200), ("2", 100)]), 300);
+        assert_eq!(register(&mut context, &mut contract, accounts(4), vec![("3", 100)]), 100);
+        assert_eq!(contract.get_allocation_info(0, accounts(4)).unwrap().allocation.0, ONE_NEAR);
+        //lock 2 moved to accounts(4) with nft_transfer counts once
+        assert_eq!(register(&mut context, &mut contract, accounts(4), vec![("2", 100), ("3", 100)]), 200);
+        let info = contract.get_allocation_info(0, accounts(2)).unwrap();
+        assert_eq!(info.total_voting_power.0, 400);
+        assert_eq!(info.allocation.0, 2 * ONE_NEAR);
+
+        let buy_at = |context: &mut VMContextBuilder, contract: &mut Contract, account_id: AccountId, now_sec: u64| {
+            testing_env!(context
+                .predecessor_account_id(account_id)
+                .attached_deposit(5 * ONE_NEAR)
+                .block_timestamp(now_sec * 10u64.pow(9))
+                .build());
+            contract.buy(0, true, None).0
+        };
+        assert_eq!(buy_at(&mut context, &mut contract, accounts(2), 120), 2 * ONE_NEAR);
+        assert_eq!(buy_at(&mut context, &mut contract, accounts(4), 120), 2 * ONE_NEAR);
+        //the public round is first come first served up to the hard cap
+        assert_eq!(buy_at(&mut context, &mut contract, accounts(2), 160), 5 * ONE_NEAR);
+        assert_eq!(buy_at(&mut context, &mut contract, accounts(4), 160), ONE_NEAR);
+    }
+
+    #[test]
+    fn whitelist_proof_caps_first_purchase() {
+        let (mut context, mut contract) = setup_contract();
+        create_sale(&mut context, &mut contract, 0);
+        let leaf = |account_id: AccountId, max_allocation: Balance| -> CryptoHash {
+            env::sha256(format!("{}:{}", account_id, max_allocation).as_bytes()).try_into().unwrap()
+        };
+        let (leaf_2, leaf_4) = (leaf(accounts(2), 2 * ONE_NEAR), leaf(accounts(4), ONE_NEAR));
+        let (first, second) = if leaf_2 <= leaf_4 { (leaf_2, leaf_4) } else { (leaf_4, leaf_2) };
+        let root: CryptoHash = env::sha256(&[first, second].concat()).try_into().unwrap();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_whitelist_root(0, false, Some(root.into()));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(3 * ONE_NEAR)
+            .block_timestamp(150 * 10u64.pow(9))
+            .build());
+        let proof = |max_allocation: Balance, sibling: CryptoHash| WhitelistProof {
+            max_allocation: U128(max_allocation),
+            proof: vec![sibling.into()],
+        };
+        assert_eq!(contract.buy(0, false, Some(proof(2 * ONE_NEAR, leaf_4))).0, 2 * ONE_NEAR);
+        assert_eq!(contract.get_whitelist_allocation(0, false, accounts(2)), Some(U128(2 * ONE_NEAR)));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid whitelist proof")]
+    fn whitelist_rejects_wrong_allocation() {
+        let (mut context, mut contract) = setup_contract();
+        create_sale(&mut context, &mut contract, 0);
+        //a single leaf whitelist, the root is the leaf
+        let root: CryptoHash = env::sha256(format!("{}:{}", accounts(2), ONE_NEAR).as_bytes()).try_into().unwrap();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_whitelist_root(0, false, Some(root.into()));
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(ONE_NEAR)
+            .block_timestamp(150 * 10u64.pow(9))
+            .build());
+        contract.buy(0, false, Some(WhitelistProof { max_allocation: U128(2 * ONE_NEAR), proof: vec![] }));
+    }
+
+    #[test]
+    fn sale_paid_in_token() {
+        let (mut context, mut contract) = setup_contract();
+        //the decimals are read from the token metadata
+        let metadata = FungibleTokenMetadata {
+            spec: FT_METADATA_SPEC.to_string(),
+            name: "USD".to_string(),
+            symbol: "USD".to_string(),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: 6,
+        };
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(near_sdk::serde_json::to_vec(&metadata).unwrap())],
+        );
+        assert_eq!(contract.callback_register_payment_token(accounts(5), accounts(1), U128(ONE_NEAR)), Some(6));
+        assert_eq!(contract.get_payment_token_decimals(accounts(5)), Some(6));
+
+        //1000 tokens at 10 tokens per whole 6 decimals payment token, what is attached over the fee is the storage reserve
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(2 * ONE_NEAR)
+            .block_timestamp(0)
+            .build());
+        contract.create_new_unverified_pool(accounts(3), U128(1000), U128(10), U128(0), 100, 200, None, None, Some(accounts(5)));
+        let pool = contract.get_pool_info(0, false);
+        assert_eq!(pool.hard_cap.0, 100 * 10u128.pow(6));
+        assert!(pool.storage_reserve.0 > 0 && pool.storage_reserve.0 < ONE_NEAR);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.ft_on_transfer(accounts(1), U128(1000), "{\"deposit_pool_id\": 0, \"verified_track\": false}".to_string());
+        //anyone tops up the storage reserve
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(ONE_NEAR).build());
+        assert_eq!(contract.add_storage_reserve(0, false).0, pool.storage_reserve.0 + ONE_NEAR);
+
+        let msg = "{\"pool_id\": 0}".to_string();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .block_timestamp(150 * 10u64.pow(9))
+            .build());
+        let unused = contract.ft_on_transfer(accounts(2), U128(60 * 10u128.pow(6)), msg.clone());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+        //only the 40 tokens left under the hard cap are taken
+        let unused = contract.ft_on_transfer(accounts(4), U128(50 * 10u128.pow(6)), msg);
+        assert!(matches!(unused, PromiseOrValue::Value(U128(v)) if v == 10 * 10u128.pow(6)));
+        assert_eq!(contract.get_buyer_info(0, false, accounts(2)).purchased_tokens.0, 600);
+        assert!(contract.get_pool_info(0, false).storage_reserve.0 < pool.storage_reserve.0 + ONE_NEAR);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .block_timestamp(200 * 10u64.pow(9))
+            .build());
+        assert_eq!(contract.withdraw_sale_proceeds(0, false).0, 95 * 10u128.pow(6));
+        assert!(contract.withdraw_storage_reserve(0, false).0 > 0);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::*;
+use near_sdk::assert_one_yocto;
 
 #[near_bindgen]
 impl Contract {
@@ -8,12 +9,6 @@ impl Contract {
         }
     }
 
-    pub(crate) fn assert_price_feeder(&self) {
-        if env::predecessor_account_id() != self.price_feeder {
-            env::panic_str("This method can be called only by price feeder")
-        }
-    }
-
     pub fn set_governance(&mut self, governance: AccountId) {
         self.assert_governance();
         self.governance = governance;
@@ -23,6 +18,22 @@ impl Contract {
         self.governance.clone()
     }
 
+    pub fn set_fee(&mut self, fee: u64) {
+        self.assert_governance();
+        require!(fee < FEE_DIVISOR, "fee too high");
+        self.fee = fee;
+    }
+
+    pub fn set_new_pool_creation_fee(&mut self, new_pool_creation_fee: U128) {
+        self.assert_governance();
+        self.new_pool_creation_fee = new_pool_creation_fee.0;
+    }
+
+    pub fn set_ve_nft_id(&mut self, ve_nft_id: AccountId) {
+        self.assert_governance();
+        self.ve_nft_id = Some(ve_nft_id);
+    }
+
     pub fn add_to_blacklist(&mut self, account_id: &AccountId) {
         self.assert_governance();
         self.black_list.insert(account_id, &BlackListStatus::Banned);
@@ -48,9 +59,4 @@ impl Contract {
         self.assert_governance();
         self.status = ContractStatus::Working;
     }
-
-    pub fn set_price_feeder(&mut self, price_feeder: AccountId) {
-        self.assert_governance();
-        self.price_feeder = price_feeder;
-    }
 }
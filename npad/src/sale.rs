@@ -0,0 +1,447 @@
+use crate::*;
+use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+use near_sdk::PromiseResult;
+use utils::{ext_ft_metadata, ext_self, GAS_FOR_FT_METADATA, GAS_FOR_FT_TRANSFER, GAS_FOR_RESOLVE_TRANSFER};
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PoolInfo {
+    pub pool_id: u32,
+    pub owner_id: AccountId,
+    pub token_id: AccountId,
+    pub amount_for_sale: U128,
+    pub rate: U128,
+    pub soft_cap: U128,
+    pub hard_cap: U128,
+    pub sale_start_sec: u64,
+    pub sale_end_sec: u64,
+    pub sold_amount: U128,
+    pub total_raised: U128,
+    pub buyer_count: u64,
+    pub verified_track: bool,
+    pub is_token_deposited: bool,
+    pub proceeds_withdrawn: bool,
+    pub unsold_tokens_withdrawn: bool,
+    pub verified_pool_id: Option<u32>,
+    pub vesting: Option<VestingSchedule>,
+    pub payment_token_id: Option<AccountId>,
+    pub payment_decimals: u8,
+    pub storage_reserve: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BuyerInfo {
+    pub purchased_payment: U128, //in the payment token, NEAR when the sale has none
+    pub purchased_tokens: U128,
+    pub token_claimed: U128,
+    pub claimable_tokens: U128,
+    pub locked_tokens: U128,
+}
+
+impl Pool {
+    pub fn is_sale_active(&self) -> bool {
+        let now = env::block_timestamp_ms() / 1000;
+        self.sale_start_sec <= now && now < self.sale_end_sec
+    }
+
+    pub fn is_sale_ended(&self) -> bool {
+        env::block_timestamp_ms() / 1000 >= self.sale_end_sec
+    }
+
+    pub fn is_soft_cap_reached(&self) -> bool {
+        self.total_raised >= self.soft_cap
+    }
+
+    pub fn get_purchased_tokens(&self, account_id: &AccountId) -> Balance {
+        compute_token_amount(self.buyers.get(account_id).unwrap_or(0), self.rate, self.payment_decimals)
+    }
+
+    pub fn to_info(&self) -> PoolInfo {
+        PoolInfo {
+            pool_id: self.pool_id,
+            owner_id: self.owner_id.clone(),
+            token_id: self.token_id.clone(),
+            amount_for_sale: U128(self.amount_for_sale),
+            rate: U128(self.rate),
+            soft_cap: U128(self.soft_cap),
+            hard_cap: U128(self.hard_cap),
+            sale_start_sec: self.sale_start_sec,
+            sale_end_sec: self.sale_end_sec,
+            sold_amount: U128(self.sold_amount),
+            total_raised: U128(self.total_raised),
+            buyer_count: self.buyer_count,
+            verified_track: self.verified_track,
+            is_token_deposited: self.is_token_deposited,
+            proceeds_withdrawn: self.proceeds_withdrawn,
+            unsold_tokens_withdrawn: self.unsold_tokens_withdrawn,
+            verified_pool_id: self.verified_pool_id,
+            vesting: self.vesting.clone(),
+            payment_token_id: self.payment_token_id.clone(),
+            payment_decimals: self.payment_decimals,
+            storage_reserve: U128(self.storage_reserve),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Buys tokens of a sale paid in NEAR with the attached NEAR, the storage of a new buyer is paid from it.
+    /// What goes over the hard cap or the allocation is refunded.
+    /// Whitelisted sales need `whitelist_proof` on the first purchase.
+    #[payable]
+    pub fn buy(&mut self, pool_id: u32, verified_track: bool, whitelist_proof: Option<WhitelistProof>) -> U128 {
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        require!(
+            self.get_pool(pool_id, verified_track).payment_token_id.is_none(),
+            "sale is paid in a token"
+        );
+        let (amount, unused) = self.internal_buy(
+            pool_id,
+            verified_track,
+            &account_id,
+            env::attached_deposit(),
+            whitelist_proof,
+        );
+        if unused > 0 {
+            Promise::new(account_id).transfer(unused);
+        }
+        U128(amount)
+    }
+
+    /// After a successful sale, sends the bought tokens vested and not claimed yet.
+    #[payable]
+    pub fn claim_tokens(&mut self, pool_id: u32, verified_track: bool) -> Promise {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        let pool = self.get_pool_mut(pool_id, verified_track);
+        require!(pool.is_sale_ended(), "sale not ended");
+        require!(pool.is_soft_cap_reached(), "soft cap not reached");
+        let claimed = pool.token_claimed.get(&account_id).unwrap_or(0);
+        let amount = pool.get_claimable_tokens(&account_id);
+        require!(amount > 0, "nothing to claim");
+        pool.token_claimed.insert(&account_id, &(claimed + amount));
+        let token_id = pool.token_id.clone();
+        self.internal_send_tokens(&token_id, &account_id, amount)
+            .then(ext_self::callback_post_claim(
+                pool_id,
+                verified_track,
+                account_id,
+                U128(amount),
+                env::current_account_id(),
+                0,
+                GAS_FOR_RESOLVE_TRANSFER,
+            ))
+    }
+
+    /// Gives buyers their payment back when the sale ended under the soft cap.
+    #[payable]
+    pub fn refund(&mut self, pool_id: u32, verified_track: bool) -> U128 {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        let pool = self.get_pool_mut(pool_id, verified_track);
+        require!(pool.is_sale_ended(), "sale not ended");
+        require!(!pool.is_soft_cap_reached(), "soft cap reached");
+        let amount = pool.buyers.get(&account_id).unwrap_or(0);
+        require!(amount > 0, "nothing to refund");
+        pool.buyers.insert(&account_id, &0);
+        match pool.payment_token_id.clone() {
+            Some(payment_token_id) => {
+                self.internal_send_tokens(&payment_token_id, &account_id, amount)
+                    .then(ext_self::callback_post_refund(
+                        pool_id,
+                        verified_track,
+                        account_id,
+                        U128(amount),
+                        env::current_account_id(),
+                        0,
+                        GAS_FOR_RESOLVE_TRANSFER,
+                    ));
+            }
+            None => {
+                Promise::new(account_id).transfer(amount);
+            }
+        }
+        U128(amount)
+    }
+
+    /// After a successful sale, sends the raised payment minus the protocol fee to the owner
+    /// and the fee to governance.
+    #[payable]
+    pub fn withdraw_sale_proceeds(&mut self, pool_id: u32, verified_track: bool) -> U128 {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let fee = self.fee;
+        let governance = self.governance.clone();
+        let pool = self.get_pool_mut(pool_id, verified_track);
+        require!(env::predecessor_account_id() == pool.owner_id, "only the sale owner");
+        require!(pool.is_sale_ended(), "sale not ended");
+        require!(pool.is_soft_cap_reached(), "soft cap not reached");
+        require!(!pool.proceeds_withdrawn, "proceeds already withdrawn");
+        pool.proceeds_withdrawn = true;
+        let fee_amount = pool.total_raised * fee as u128 / FEE_DIVISOR as u128;
+        let proceeds = pool.total_raised - fee_amount;
+        let owner_id = pool.owner_id.clone();
+        match pool.payment_token_id.clone() {
+            Some(payment_token_id) => {
+                self.internal_send_tokens(&payment_token_id, &owner_id, proceeds)
+                    .then(ext_self::callback_post_proceeds_withdraw(
+                        pool_id,
+                        verified_track,
+                        U128(fee_amount),
+                        env::current_account_id(),
+                        0,
+                        GAS_FOR_RESOLVE_TRANSFER + GAS_FOR_FT_TRANSFER,
+                    ));
+            }
+            None => {
+                Promise::new(owner_id).transfer(proceeds);
+                if fee_amount > 0 {
+                    Promise::new(governance).transfer(fee_amount);
+                }
+            }
+        }
+        U128(proceeds)
+    }
+
+    /// Adds the attached NEAR to the storage reserve of a sale paid in a token, until the sale ends.
+    #[payable]
+    pub fn add_storage_reserve(&mut self, pool_id: u32, verified_track: bool) -> U128 {
+        self.abort_if_pause();
+        let pool = self.get_pool_mut(pool_id, verified_track);
+        require!(pool.payment_token_id.is_some(), "sale is paid in NEAR");
+        require!(pool.verified_pool_id.is_none(), "pool moved to the verified track");
+        require!(!pool.is_sale_ended(), "sale ended");
+        require!(env::attached_deposit() > 0, "attached deposit required");
+        pool.storage_reserve += env::attached_deposit();
+        U128(pool.storage_reserve)
+    }
+
+    /// Reads the decimals of a token from its metadata so that sales can be paid in it.
+    /// The attached deposit pays the storage, the rest is refunded.
+    #[payable]
+    pub fn register_payment_token(&mut self, token_id: AccountId) -> Promise {
+        self.abort_if_pause();
+        require!(
+            self.payment_token_decimals.get(&token_id).is_none(),
+            "payment token already registered"
+        );
+        ext_ft_metadata::ft_metadata(token_id.clone(), 0, GAS_FOR_FT_METADATA).then(
+            ext_self::callback_register_payment_token(
+                token_id,
+                env::predecessor_account_id(),
+                U128(env::attached_deposit()),
+                env::current_account_id(),
+                0,
+                GAS_FOR_RESOLVE_TRANSFER,
+            ),
+        )
+    }
+
+    #[private]
+    pub fn callback_register_payment_token(
+        &mut self,
+        token_id: AccountId,
+        account_id: AccountId,
+        deposit: U128,
+    ) -> Option<u8> {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            "CALLBACK_REGISTER_PAYMENT_TOKEN_INVALID"
+        );
+        let decimals = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<FungibleTokenMetadata>(&value)
+                    .ok()
+                    .map(|metadata| metadata.decimals)
+            }
+            PromiseResult::Failed => None,
+        };
+        //registered while ft_metadata answered
+        let decimals = decimals.filter(|_| self.payment_token_decimals.get(&token_id).is_none());
+        let prev_storage = env::storage_usage();
+        if let Some(decimals) = decimals {
+            self.payment_token_decimals.insert(&token_id, &decimals);
+        }
+        let storage_cost = self.storage_cost(prev_storage);
+        //a panic here would keep the deposit, so the registration is undone instead
+        if storage_cost > deposit.0 {
+            log!("ERR_STORAGE_DEPOSIT need {}, attatched {}", storage_cost, deposit.0);
+            self.payment_token_decimals.remove(&token_id);
+            if deposit.0 > 0 {
+                Promise::new(account_id).transfer(deposit.0);
+            }
+            return None;
+        }
+        if deposit.0 > storage_cost {
+            Promise::new(account_id).transfer(deposit.0 - storage_cost);
+        }
+        decimals
+    }
+
+    pub fn get_payment_token_decimals(&self, token_id: AccountId) -> Option<u8> {
+        self.payment_token_decimals.get(&token_id)
+    }
+
+    /// After the sale, returns the owner what is left of the storage reserve of a sale paid in a token.
+    #[payable]
+    pub fn withdraw_storage_reserve(&mut self, pool_id: u32, verified_track: bool) -> U128 {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let pool = self.get_pool_mut(pool_id, verified_track);
+        require!(env::predecessor_account_id() == pool.owner_id, "only the sale owner");
+        require!(pool.is_sale_ended(), "sale not ended");
+        let amount = std::mem::take(&mut pool.storage_reserve);
+        require!(amount > 0, "nothing to withdraw");
+        Promise::new(pool.owner_id.clone()).transfer(amount);
+        U128(amount)
+    }
+
+    /// After the sale, sends the owner the tokens not sold, or all of them if the soft cap was missed.
+    #[payable]
+    pub fn withdraw_unsold_tokens(&mut self, pool_id: u32, verified_track: bool) -> Promise {
+        assert_one_yocto();
+        self.abort_if_pause();
+        let pool = self.get_pool_mut(pool_id, verified_track);
+        require!(env::predecessor_account_id() == pool.owner_id, "only the sale owner");
+        require!(pool.is_sale_ended(), "sale not ended");
+        require!(pool.is_token_deposited, "sale tokens not deposited");
+        require!(!pool.unsold_tokens_withdrawn, "unsold tokens already withdrawn");
+        let amount = if pool.is_soft_cap_reached() {
+            pool.amount_for_sale - pool.sold_amount
+        } else {
+            pool.amount_for_sale
+        };
+        require!(amount > 0, "nothing to withdraw");
+        pool.unsold_tokens_withdrawn = true;
+        let (token_id, owner_id) = (pool.token_id.clone(), pool.owner_id.clone());
+        self.internal_send_tokens(&token_id, &owner_id, amount)
+            .then(ext_self::callback_post_unsold_withdraw(
+                pool_id,
+                verified_track,
+                env::current_account_id(),
+                0,
+                GAS_FOR_RESOLVE_TRANSFER,
+            ))
+    }
+}
+
+impl Contract {
+    //records a purchase of at most `payment`, returns the amount paid and what is left of `payment`.
+    //the storage of a new buyer is paid from `payment` in NEAR, from the storage reserve otherwise
+    pub(crate) fn internal_buy(
+        &mut self,
+        pool_id: u32,
+        verified_track: bool,
+        account_id: &AccountId,
+        payment: Balance,
+        whitelist_proof: Option<WhitelistProof>,
+    ) -> (Balance, Balance) {
+        let prev_storage = env::storage_usage();
+        let pool = self.get_pool_mut(pool_id, verified_track);
+        require!(pool.verified_pool_id.is_none(), "pool moved to the verified track");
+        require!(pool.is_token_deposited, "sale tokens not deposited");
+        require!(pool.is_sale_active(), "sale is not active");
+        let purchased = pool.buyers.get(account_id).unwrap_or(0);
+        //the claim entry is created with the purchase so that its storage is paid here
+        if purchased == 0 {
+            pool.buyers.insert(account_id, &0);
+            pool.token_claimed.insert(account_id, &0);
+            pool.buyer_count += 1;
+        }
+        let whitelist_left = pool.check_whitelist(account_id, whitelist_proof);
+        let storage_cost = self.storage_cost(prev_storage);
+
+        let pool = self.get_pool_mut(pool_id, verified_track);
+        let payment = if pool.payment_token_id.is_some() {
+            pool.storage_reserve = pool
+                .storage_reserve
+                .checked_sub(storage_cost)
+                .expect("storage reserve exhausted");
+            payment
+        } else {
+            payment
+                .checked_sub(storage_cost)
+                .expect("attached deposit does not cover storage")
+        };
+        let amount = std::cmp::min(payment, pool.hard_cap - pool.total_raised);
+        require!(amount > 0, "hard cap reached");
+        let amount = std::cmp::min(amount, pool.get_allocation_left(account_id));
+        require!(amount > 0, "no allocation left in the guaranteed round");
+        let amount = std::cmp::min(amount, whitelist_left);
+        require!(amount > 0, "no whitelist allocation left");
+        pool.buyers.insert(account_id, &(purchased + amount));
+        pool.total_raised += amount;
+        pool.sold_amount = compute_token_amount(pool.total_raised, pool.rate, pool.payment_decimals);
+        (amount, payment - amount)
+    }
+
+    //returns the tokens deposited over amount_for_sale
+    pub(crate) fn internal_deposit_sale_tokens(
+        &mut self,
+        pool_id: u32,
+        verified_track: bool,
+        sender_id: &AccountId,
+        token_in: &AccountId,
+        amount: Balance,
+    ) -> Balance {
+        let pool = self.get_pool_mut(pool_id, verified_track);
+        require!(sender_id == &pool.owner_id, "only the sale owner");
+        require!(token_in == &pool.token_id, "invalid sale token");
+        require!(pool.verified_pool_id.is_none(), "pool moved to the verified track");
+        require!(!pool.is_token_deposited, "sale tokens already deposited");
+        require!(
+            env::block_timestamp_ms() / 1000 < pool.sale_start_sec,
+            "sale already started"
+        );
+        require!(amount >= pool.amount_for_sale, "amount below amount_for_sale");
+        pool.is_token_deposited = true;
+        amount - pool.amount_for_sale
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_pool_info(&self, pool_id: u32, verified_track: bool) -> PoolInfo {
+        self.get_pool(pool_id, verified_track).to_info()
+    }
+
+    pub fn get_pools(&self, verified_track: bool, from_index: Option<usize>, limit: Option<usize>) -> Vec<PoolInfo> {
+        let limit = limit.unwrap_or(usize::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        let pools = if verified_track { &self.verified_pools } else { &self.unverified_pools };
+        pools
+            .iter()
+            .skip(from_index.unwrap_or(0))
+            .take(limit)
+            .map(|pool| pool.to_info())
+            .collect::<Vec<_>>()
+    }
+
+    pub fn get_buyer_info(&self, pool_id: u32, verified_track: bool, account_id: AccountId) -> BuyerInfo {
+        let pool = self.get_pool(pool_id, verified_track);
+        let purchased_tokens = pool.get_purchased_tokens(&account_id);
+        //nothing is claimable from a sale that missed its soft cap
+        let vested_tokens = if pool.is_soft_cap_reached() {
+            pool.get_vested_tokens(&account_id)
+        } else {
+            0
+        };
+        let token_claimed = pool.token_claimed.get(&account_id).unwrap_or(0);
+        BuyerInfo {
+            purchased_payment: U128(pool.buyers.get(&account_id).unwrap_or(0)),
+            purchased_tokens: U128(purchased_tokens),
+            token_claimed: U128(token_claimed),
+            claimable_tokens: U128(vested_tokens - token_claimed),
+            locked_tokens: U128(purchased_tokens - vested_tokens),
+        }
+    }
+}
@@ -9,18 +9,24 @@ use crate::*;
 #[serde(crate = "near_sdk::serde")]
 #[serde(untagged)]
 enum TokenReceiverMessage {
-    /// Alternative to deposit + execute actions call.
-    Borrow {
-        borrow_amount: U128,
-        /// List of sequential actions.
-        receiver_id: Option<AccountId>,
+    /// Buys tokens of a sale paid in the transferred token, what is not used is refunded.
+    /// `verified_track` is false when omitted.
+    Buy {
+        pool_id: u32,
+        verified_track: Option<bool>,
+        whitelist_proof: Option<WhitelistProof>,
+    },
+    /// The sale owner deposits the tokens for sale before the sale starts.
+    DepositSaleTokens {
+        deposit_pool_id: u32,
+        verified_track: bool,
     },
 }
 
 #[near_bindgen]
 impl FungibleTokenReceiver for Contract {
     /// Callback on receiving tokens by this contract.
-    /// `msg` format is either "" for deposit or `TokenReceiverMessage`.
+    /// `msg` format is `TokenReceiverMessage`.
     #[allow(unreachable_code)]
     fn ft_on_transfer(
         &mut self,
@@ -32,24 +38,38 @@ impl FungibleTokenReceiver for Contract {
         self.abort_if_blacklisted(sender_id.clone());
         let token_in = env::predecessor_account_id();
 
-        self.abort_if_unsupported_token(token_in.clone());
         if msg.is_empty() {
-            // Simple deposit.
-            self.deposit_to_vault(&token_in, &amount.0, &sender_id);
-            PromiseOrValue::Value(U128(0))
+            env::panic_str("unsupported operation");
         } else {
-            // instant swap
             let message =
                 serde_json::from_str::<TokenReceiverMessage>(&msg).expect("wrong message format");
             match message {
-                TokenReceiverMessage::Borrow {
-                    borrow_amount,
-                    receiver_id,
+                TokenReceiverMessage::Buy {
+                    pool_id,
+                    verified_track,
+                    whitelist_proof,
+                } => {
+                    let verified_track = verified_track.unwrap_or(false);
+                    require!(
+                        self.get_pool(pool_id, verified_track).payment_token_id.as_ref() == Some(&token_in),
+                        "invalid payment token"
+                    );
+                    let (_, unused) =
+                        self.internal_buy(pool_id, verified_track, &sender_id, amount.0, whitelist_proof);
+                    PromiseOrValue::Value(U128(unused))
+                }
+                TokenReceiverMessage::DepositSaleTokens {
+                    deposit_pool_id,
+                    verified_track,
                 } => {
-                    let receiver_id = receiver_id.unwrap_or(sender_id.clone());
-                    self.borrow(&token_in, amount.0, borrow_amount.0, receiver_id);
-                    // Even if send tokens fails, we don't return funds back to sender.
-                    PromiseOrValue::Value(U128(0))
+                    let unused = self.internal_deposit_sale_tokens(
+                        deposit_pool_id,
+                        verified_track,
+                        &sender_id,
+                        &token_in,
+                        amount.0,
+                    );
+                    PromiseOrValue::Value(U128(unused))
                 }
             }
         }
@@ -1,13 +1,29 @@
 use crate::*;
-use near_sdk::{ext_contract, log, AccountId, Balance, Gas, PromiseResult};
+use near_sdk::{ext_contract, AccountId, Balance, Gas, PromiseResult};
 
 /// Amount of gas for fungible token transfers, increased to 20T to support AS token contracts.
 pub const GAS_FOR_FT_TRANSFER: Gas = Gas(20_000_000_000_000);
 pub const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas(20_000_000_000_000);
+pub const GAS_FOR_GET_VOTING_POWER: Gas = Gas(30_000_000_000_000);
+pub const GAS_FOR_FT_METADATA: Gas = Gas(10_000_000_000_000);
+
+uint::construct_uint!(
+    pub struct U256(4);
+);
 
 #[ext_contract(ext_self)]
-pub trait NaiVault {
-    fn callback_post_withdraw(&mut self, token_id: AccountId, receiver_id: AccountId, amount: U128);
+pub trait NPad {
+    fn callback_post_claim(&mut self, pool_id: u32, verified_track: bool, receiver_id: AccountId, amount: U128);
+    fn callback_post_unsold_withdraw(&mut self, pool_id: u32, verified_track: bool);
+    fn callback_post_refund(&mut self, pool_id: u32, verified_track: bool, account_id: AccountId, amount: U128);
+    fn callback_post_proceeds_withdraw(&mut self, pool_id: u32, verified_track: bool, fee_amount: U128);
+    fn callback_register_for_allocation(&mut self, pool_id: u32, account_id: AccountId, deposit: U128) -> U128;
+    fn callback_register_payment_token(&mut self, token_id: AccountId, account_id: AccountId, deposit: U128) -> Option<u8>;
+}
+
+#[ext_contract(ext_ve_nft)]
+pub trait VeNft {
+    fn get_token_metadata_for_account(&self, account_id: AccountId) -> (Vec<allocation::VeNftTokenId>, Vec<allocation::VeLockInfo>, U128, U128, U128);
 }
 
 #[ext_contract(ext_ft_core)]
@@ -15,12 +31,18 @@ pub trait FungibleTokenCore {
     fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
 }
 
+#[ext_contract(ext_ft_metadata)]
+pub trait FungibleTokenMetadataProvider {
+    fn ft_metadata(&self) -> near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+}
+
 #[near_bindgen]
 impl Contract {
     #[private]
-    pub fn callback_post_withdraw(
+    pub fn callback_post_claim(
         &mut self,
-        token_id: AccountId,
+        pool_id: u32,
+        verified_track: bool,
         receiver_id: AccountId,
         amount: U128,
     ) {
@@ -28,45 +50,82 @@ impl Contract {
             env::promise_results_count(),
             1,
             "{}",
-            "CALLBACK_POST_WITHDRAW_INVALID"
+            "CALLBACK_POST_CLAIM_INVALID"
         );
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
             PromiseResult::Successful(_) => {}
             PromiseResult::Failed => {
-                // This reverts the changes from withdraw function.
-                // If account doesn't exit, deposits to the owner's account as lostfound.
-                let mut failed = false;
-                if self.accounts.contains_key(&receiver_id) {
-                    let mut account_deposit = self.get_account_info(receiver_id.clone());
-                    let vault_index = account_deposit.get_vault_index(token_id.clone());
-                    if vault_index < account_deposit.vaults.len() {
-                        // cause storage already checked, here can directly save
-                        let mut vault = account_deposit.get_vault(token_id.clone());
-                        vault.deposited = U128(vault.deposited.0 + amount.0);
-                        account_deposit.vaults[vault_index] = vault;
-                        self.accounts.insert(&receiver_id, &account_deposit);
-                    } else {
-                        // we can ensure that internal_get_account here would NOT cause a version upgrade,
-                        // cause it is callback, the account must be the current version or non-exist,
-                        // so, here we can just leave it without insert, won't cause storage collection inconsistency.
-                        log!(format!(
-                            "Account {} has not enough storage. Depositing to owner.",
-                            receiver_id
-                        ));
-                        failed = true;
-                    }
-                } else {
-                    log!(format!(
-                        "Account {} is not registered. Depositing to owner.",
-                        receiver_id
-                    ));
-                    failed = true;
-                }
-                if failed {
-                    //lost fund
+                // This reverts the claim so the buyer can claim again.
+                let pool = self.get_pool_mut(pool_id, verified_track);
+                let claimed = pool.token_claimed.get(&receiver_id).unwrap_or(0);
+                pool.token_claimed.insert(&receiver_id, &(claimed - amount.0));
+            }
+        };
+    }
+
+    #[private]
+    pub fn callback_post_refund(
+        &mut self,
+        pool_id: u32,
+        verified_track: bool,
+        account_id: AccountId,
+        amount: U128,
+    ) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            "CALLBACK_POST_REFUND_INVALID"
+        );
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {}
+            PromiseResult::Failed => {
+                // This reverts the refund so the buyer can ask again.
+                self.get_pool_mut(pool_id, verified_track).buyers.insert(&account_id, &amount.0);
+            }
+        };
+    }
+
+    /// The fee goes to governance once the owner got the proceeds.
+    #[private]
+    pub fn callback_post_proceeds_withdraw(&mut self, pool_id: u32, verified_track: bool, fee_amount: U128) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            "CALLBACK_POST_PROCEEDS_WITHDRAW_INVALID"
+        );
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                if fee_amount.0 > 0 {
+                    let pool = self.get_pool(pool_id, verified_track);
+                    let payment_token_id = pool.payment_token_id.clone().unwrap();
+                    self.internal_send_tokens(&payment_token_id, &self.governance, fee_amount.0);
                 }
             }
+            PromiseResult::Failed => {
+                self.get_pool_mut(pool_id, verified_track).proceeds_withdrawn = false;
+            }
+        };
+    }
+
+    #[private]
+    pub fn callback_post_unsold_withdraw(&mut self, pool_id: u32, verified_track: bool) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            "CALLBACK_POST_UNSOLD_WITHDRAW_INVALID"
+        );
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {}
+            PromiseResult::Failed => {
+                self.get_pool_mut(pool_id, verified_track).unsold_tokens_withdrawn = false;
+            }
         };
     }
 }
@@ -86,23 +145,10 @@ impl Contract {
             1,
             GAS_FOR_FT_TRANSFER,
         )
-        .then(ext_self::callback_post_withdraw(
-            token_id.clone(),
-            receiver_id.clone(),
-            U128(amount),
-            env::current_account_id(),
-            0,
-            GAS_FOR_RESOLVE_TRANSFER,
-        ))
-    }
-
-    pub fn compute_cr(&self, collateral_value: &U256, borrowed: &Balance, collateral_decimals: u8) -> u64 {
-        let ret = collateral_value * U256::from(10u128.pow(18 as u32)) * U256::from(COLLATERAL_RATIO_DIVISOR as u64)
-                / (U256::from(borrowed.clone()) * U256::from(10u128.pow(collateral_decimals as u32)));
-        ret.as_u64()
     }
+}
 
-    pub fn compute_collateral_value(&self, collateral_amount: &Balance, price: &Price) -> U256 {
-        return U256::from(collateral_amount.clone()) * U256::from(price.multiplier.0) * U256::from(10u128.pow(price.decimals as u32));
-    }
+//tokens bought with `payment_amount` at `rate` tokens per whole payment token
+pub fn compute_token_amount(payment_amount: Balance, rate: Balance, payment_decimals: u8) -> Balance {
+    (U256::from(payment_amount) * U256::from(rate) / U256::from(10u128.pow(payment_decimals as u32))).as_u128()
 }
@@ -0,0 +1,205 @@
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleMetadata {
+    pub website: String,
+    pub audit_link: Option<String>,
+    pub vesting_terms: Option<String>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum VerificationStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VerificationApplication {
+    pub pool_id: u32,
+    pub metadata: SaleMetadata,
+    pub status: VerificationStatus,
+    pub verified_pool_id: Option<u32>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// The owner of an unverified sale applies for the verified track before the sale starts.
+    /// A rejected sale can apply again and pays the creation fee again, it was refunded on the rejection.
+    /// The attached deposit pays the storage.
+    #[payable]
+    pub fn apply_for_verification(&mut self, pool_id: u32, metadata: SaleMetadata) {
+        self.abort_if_pause();
+        let account_id = env::predecessor_account_id();
+        self.abort_if_blacklisted(account_id.clone());
+        let pool = self.get_pool(pool_id, false);
+        require!(account_id == pool.owner_id, "only the sale owner");
+        require!(pool.verified_pool_id.is_none(), "pool moved to the verified track");
+        require!(
+            env::block_timestamp_ms() / 1000 < pool.sale_start_sec,
+            "sale already started"
+        );
+        if let Some(application) = self.verification_applications.get(&pool_id) {
+            require!(
+                application.status == VerificationStatus::Rejected,
+                "verification already applied for"
+            );
+        }
+        let prev_storage = env::storage_usage();
+        self.verification_applications.insert(
+            &pool_id,
+            &VerificationApplication {
+                pool_id,
+                metadata,
+                status: VerificationStatus::Pending,
+                verified_pool_id: None,
+            },
+        );
+        let new_pool_creation_fee = self.new_pool_creation_fee;
+        let pool = self.get_pool_mut(pool_id, false);
+        let creation_fee = if pool.creation_fee == 0 {
+            pool.creation_fee = new_pool_creation_fee;
+            new_pool_creation_fee
+        } else {
+            0
+        };
+        let required = self.storage_cost(prev_storage) + creation_fee;
+        let refund = env::attached_deposit().checked_sub(required).expect(
+            format!(
+                "ERR_STORAGE_DEPOSIT need {}, attatched {}",
+                required,
+                env::attached_deposit()
+            )
+            .as_str(),
+        );
+        if refund > 0 {
+            Promise::new(account_id).transfer(refund);
+        }
+    }
+
+    /// Moves the sale to the verified track before it starts, the creation fee goes to governance.
+    pub fn approve_verification(&mut self, pool_id: u32) -> u32 {
+        self.assert_governance();
+        let mut application = self.get_pending_application(pool_id);
+        let verified_pool_id = self.verified_pools.len() as u32;
+        let governance = self.governance.clone();
+
+        let pool = self.get_pool_mut(pool_id, false);
+        require!(
+            env::block_timestamp_ms() / 1000 < pool.sale_start_sec,
+            "sale already started"
+        );
+        let mut verified_pool = Pool::new(
+            verified_pool_id,
+            pool.owner_id.clone(),
+            pool.token_id.clone(),
+            pool.amount_for_sale,
+            pool.rate,
+            pool.soft_cap,
+            pool.hard_cap,
+            pool.sale_start_sec,
+            pool.sale_end_sec,
+            true,
+        );
+        //the deposited tokens move with the sale
+        verified_pool.is_token_deposited = pool.is_token_deposited;
+        verified_pool.vesting = pool.vesting.clone();
+        verified_pool.whitelist_root = pool.whitelist_root;
+        verified_pool.payment_token_id = pool.payment_token_id.clone();
+        verified_pool.payment_decimals = pool.payment_decimals;
+        verified_pool.storage_reserve = std::mem::take(&mut pool.storage_reserve);
+        pool.is_token_deposited = false;
+        pool.verified_pool_id = Some(verified_pool_id);
+        let creation_fee = std::mem::take(&mut pool.creation_fee);
+        let (owner_id, token_id) = (pool.owner_id.clone(), pool.token_id.clone());
+        self.verified_pools.push(verified_pool);
+
+        let mut pool_ids = self.unverified_pool_ids_by_account.get(&owner_id).unwrap_or(vec![]);
+        pool_ids.retain(|id| *id != pool_id);
+        self.unverified_pool_ids_by_account.insert(&owner_id, &pool_ids);
+        let mut pool_ids = self.unverified_pool_ids_by_token.get(&token_id).unwrap_or(vec![]);
+        pool_ids.retain(|id| *id != pool_id);
+        self.unverified_pool_ids_by_token.insert(&token_id, &pool_ids);
+        let mut pool_ids = self.verified_pool_ids_by_account.get(&owner_id).unwrap_or(vec![]);
+        pool_ids.push(verified_pool_id);
+        self.verified_pool_ids_by_account.insert(&owner_id, &pool_ids);
+        let mut pool_ids = self.verified_pool_ids_by_token.get(&token_id).unwrap_or(vec![]);
+        pool_ids.push(verified_pool_id);
+        self.verified_pool_ids_by_token.insert(&token_id, &pool_ids);
+
+        application.status = VerificationStatus::Approved;
+        application.verified_pool_id = Some(verified_pool_id);
+        self.verification_applications.insert(&pool_id, &application);
+        if creation_fee > 0 {
+            Promise::new(governance).transfer(creation_fee);
+        }
+        verified_pool_id
+    }
+
+    /// The sale stays unverified and its creation fee is refunded to the owner.
+    pub fn reject_verification(&mut self, pool_id: u32) -> U128 {
+        self.assert_governance();
+        let mut application = self.get_pending_application(pool_id);
+        application.status = VerificationStatus::Rejected;
+        self.verification_applications.insert(&pool_id, &application);
+        let pool = self.get_pool_mut(pool_id, false);
+        let creation_fee = std::mem::take(&mut pool.creation_fee);
+        if creation_fee > 0 {
+            Promise::new(pool.owner_id.clone()).transfer(creation_fee);
+        }
+        U128(creation_fee)
+    }
+
+    /// Sends governance the creation fee of an unverified sale that ended without a pending application.
+    pub fn collect_creation_fee(&mut self, pool_id: u32) -> U128 {
+        self.assert_governance();
+        let pending = self
+            .verification_applications
+            .get(&pool_id)
+            .map(|application| application.status == VerificationStatus::Pending)
+            .unwrap_or(false);
+        require!(!pending, "verification pending");
+        let governance = self.governance.clone();
+        let pool = self.get_pool_mut(pool_id, false);
+        require!(pool.is_sale_ended(), "sale not ended");
+        let creation_fee = std::mem::take(&mut pool.creation_fee);
+        require!(creation_fee > 0, "no creation fee");
+        Promise::new(governance).transfer(creation_fee);
+        U128(creation_fee)
+    }
+
+    pub fn get_verification_application(&self, pool_id: u32) -> Option<VerificationApplication> {
+        self.verification_applications.get(&pool_id)
+    }
+
+    pub fn get_verification_applications(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<VerificationApplication> {
+        let limit = limit.unwrap_or(u64::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        self.verification_applications
+            .values()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit as usize)
+            .collect::<Vec<_>>()
+    }
+}
+
+impl Contract {
+    fn get_pending_application(&self, pool_id: u32) -> VerificationApplication {
+        let application = self
+            .verification_applications
+            .get(&pool_id)
+            .expect("no verification application");
+        require!(
+            application.status == VerificationStatus::Pending,
+            "verification not pending"
+        );
+        application
+    }
+}
@@ -0,0 +1,61 @@
+use crate::*;
+
+/// Release schedule of purchased tokens, counted from the sale end (TGE).
+/// `tge_percent` per FEE_DIVISOR is released at TGE, the rest vests linearly
+/// over `vesting_duration_sec` after `cliff_sec`, in steps of `slice_period_sec`.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VestingSchedule {
+    pub tge_percent: u64,
+    pub cliff_sec: u64,
+    pub vesting_duration_sec: u64,
+    pub slice_period_sec: u64,
+}
+
+impl VestingSchedule {
+    pub fn assert_valid(&self) {
+        require!(self.tge_percent <= FEE_DIVISOR, "tge_percent too high");
+        require!(self.slice_period_sec > 0, "slice_period_sec > 0");
+        require!(
+            self.vesting_duration_sec == 0 || self.slice_period_sec <= self.vesting_duration_sec,
+            "slice_period_sec longer than vesting_duration_sec"
+        );
+    }
+
+    //part of `total` released `now_sec` for a sale that ended at `tge_sec`
+    pub fn compute_vested_amount(&self, total: Balance, tge_sec: u64, now_sec: u64) -> Balance {
+        if now_sec < tge_sec {
+            return 0;
+        }
+        let tge_amount = total * self.tge_percent as u128 / FEE_DIVISOR as u128;
+        let vesting_start_sec = tge_sec + self.cliff_sec;
+        if now_sec < vesting_start_sec {
+            return tge_amount;
+        }
+        if now_sec >= vesting_start_sec + self.vesting_duration_sec {
+            return total;
+        }
+        let elapsed = (now_sec - vesting_start_sec) / self.slice_period_sec * self.slice_period_sec;
+        tge_amount
+            + (U256::from(total - tge_amount) * U256::from(elapsed)
+                / U256::from(self.vesting_duration_sec))
+            .as_u128()
+    }
+}
+
+impl Pool {
+    //purchased tokens of `account_id` released so far, all of them once the sale ended without vesting
+    pub fn get_vested_tokens(&self, account_id: &AccountId) -> Balance {
+        let purchased = self.get_purchased_tokens(account_id);
+        let now_sec = env::block_timestamp_ms() / 1000;
+        match &self.vesting {
+            Some(vesting) => vesting.compute_vested_amount(purchased, self.sale_end_sec, now_sec),
+            None if now_sec >= self.sale_end_sec => purchased,
+            None => 0,
+        }
+    }
+
+    pub fn get_claimable_tokens(&self, account_id: &AccountId) -> Balance {
+        self.get_vested_tokens(account_id) - self.token_claimed.get(account_id).unwrap_or(0)
+    }
+}
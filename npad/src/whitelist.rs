@@ -0,0 +1,93 @@
+use crate::*;
+use near_sdk::json_types::Base58CryptoHash;
+use near_sdk::CryptoHash;
+use std::convert::TryInto;
+
+/// Proof that `(account_id, max_allocation)` is a leaf of the sale whitelist.
+/// A leaf is sha256 of "<account_id>:<max_allocation>" and each parent is sha256
+/// of its two children concatenated in ascending order, so no path bits are needed.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WhitelistProof {
+    pub max_allocation: U128,
+    pub proof: Vec<Base58CryptoHash>,
+}
+
+fn hash_pair(a: &CryptoHash, b: &CryptoHash) -> CryptoHash {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let mut value = first.to_vec();
+    value.extend_from_slice(second);
+    env::sha256(&value).try_into().unwrap()
+}
+
+pub fn verify_whitelist_proof(
+    root: &CryptoHash,
+    account_id: &AccountId,
+    max_allocation: Balance,
+    proof: Vec<Base58CryptoHash>,
+) -> bool {
+    let leaf: CryptoHash = env::sha256(format!("{}:{}", account_id, max_allocation).as_bytes())
+        .try_into()
+        .unwrap();
+    let computed = proof
+        .into_iter()
+        .fold(leaf, |hash, sibling| hash_pair(&hash, &sibling.into()));
+    &computed == root
+}
+
+#[near_bindgen]
+impl Contract {
+    /// The sale owner sets or removes the whitelist before the sale starts.
+    pub fn set_whitelist_root(&mut self, pool_id: u32, verified_track: bool, merkle_root: Option<Base58CryptoHash>) {
+        self.abort_if_pause();
+        let pool = self.get_pool_mut(pool_id, verified_track);
+        require!(env::predecessor_account_id() == pool.owner_id, "only the sale owner");
+        require!(pool.verified_pool_id.is_none(), "pool moved to the verified track");
+        require!(
+            env::block_timestamp_ms() / 1000 < pool.sale_start_sec,
+            "sale already started"
+        );
+        pool.whitelist_root = merkle_root.map(|root| root.into());
+    }
+
+    pub fn get_whitelist_root(&self, pool_id: u32, verified_track: bool) -> Option<Base58CryptoHash> {
+        self.get_pool(pool_id, verified_track).whitelist_root.map(|root| root.into())
+    }
+
+    /// The max allocation in the payment token proven by `account_id`, None before its first purchase.
+    pub fn get_whitelist_allocation(&self, pool_id: u32, verified_track: bool, account_id: AccountId) -> Option<U128> {
+        self.get_pool(pool_id, verified_track)
+            .whitelist_allocations
+            .get(&account_id)
+            .map(U128)
+    }
+}
+
+impl Pool {
+    //payment `account_id` can still make under the whitelist, the proof is only needed on the first purchase
+    pub fn check_whitelist(&mut self, account_id: &AccountId, whitelist_proof: Option<WhitelistProof>) -> Balance {
+        let root = match self.whitelist_root {
+            Some(root) => root,
+            None => return Balance::MAX,
+        };
+        let max_allocation = match self.whitelist_allocations.get(account_id) {
+            Some(max_allocation) => max_allocation,
+            None => {
+                let whitelist_proof = whitelist_proof.expect("whitelist proof required");
+                require!(
+                    verify_whitelist_proof(
+                        &root,
+                        account_id,
+                        whitelist_proof.max_allocation.0,
+                        whitelist_proof.proof
+                    ),
+                    "invalid whitelist proof"
+                );
+                self.whitelist_allocations
+                    .insert(account_id, &whitelist_proof.max_allocation.0);
+                whitelist_proof.max_allocation.0
+            }
+        };
+        max_allocation.saturating_sub(self.buyers.get(account_id).unwrap_or(0))
+    }
+}